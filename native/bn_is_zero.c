@@ -2,3 +2,8 @@
 
 int bn_is_zero(BIGNUM *x) { return BN_is_zero(x); }
 
+int bn_is_one(BIGNUM *x) { return BN_is_one(x); }
+
+int bn_is_odd(BIGNUM *x) { return BN_is_odd(x); }
+
+int bn_is_word(BIGNUM *x, BN_ULONG w) { return BN_is_word(x, w); }
@@ -1 +1,4 @@
 int bn_is_zero(BIGNUM *x);
+int bn_is_one(BIGNUM *x);
+int bn_is_odd(BIGNUM *x);
+int bn_is_word(BIGNUM *x, BN_ULONG w);
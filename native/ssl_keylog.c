@@ -0,0 +1,18 @@
+#include <string.h>
+#include <openssl/ssl.h>
+
+int ssl_get_client_random(const SSL *s, unsigned char *out) {
+    if (s->s3 == NULL) {
+        return 0;
+    }
+    memcpy(out, s->s3->client_random, SSL3_RANDOM_SIZE);
+    return SSL3_RANDOM_SIZE;
+}
+
+int ssl_get_master_key(const SSL *s, unsigned char *out, int outlen) {
+    if (s->session == NULL || s->session->master_key_length > outlen) {
+        return 0;
+    }
+    memcpy(out, s->session->master_key, s->session->master_key_length);
+    return s->session->master_key_length;
+}
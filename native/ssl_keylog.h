@@ -0,0 +1,2 @@
+int ssl_get_client_random(const SSL *s, unsigned char *out);
+int ssl_get_master_key(const SSL *s, unsigned char *out, int outlen);
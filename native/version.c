@@ -0,0 +1,5 @@
+#include <openssl/opensslv.h>
+
+unsigned long openssl_header_version(void) { return OPENSSL_VERSION_NUMBER; }
+
+const char *openssl_header_version_text(void) { return OPENSSL_VERSION_TEXT; }
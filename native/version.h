@@ -0,0 +1,2 @@
+unsigned long openssl_header_version(void);
+const char *openssl_header_version_text(void);
@@ -1,9 +1,12 @@
-use libc::{c_long};
+use libc::{c_int, c_long};
 use std::ptr;
+use std::slice;
 
 use ffi;
-use ssl::error::{SslError};
+use ssl::error::{SslError, InvalidInput};
 
+pub mod object;
+pub mod parse;
 
 pub struct Asn1Time {
     handle: *mut ffi::ASN1_TIME,
@@ -36,6 +39,47 @@ impl Asn1Time {
     pub unsafe fn get_handle(&self) -> *mut ffi::ASN1_TIME {
         return self.handle
     }
+
+    /// Returns the time as an RFC 3339 string in UTC, e.g.
+    /// `2014-10-15T12:30:00Z`
+    ///
+    /// Two digit UTCTime years are interpreted following RFC 5280: values
+    /// from 50 onwards are in the 20th century, the rest in the 21st.
+    pub fn to_rfc3339(&self) -> Result<String, SslError> {
+        let (ty, value) = unsafe {
+            let ty = ffi::ASN1_STRING_type(self.handle);
+            let data = ffi::ASN1_STRING_data(self.handle);
+            let len = ffi::ASN1_STRING_length(self.handle);
+            let value = slice::raw::buf_as_slice(data as *const u8, len as uint,
+                                                 |s| s.to_vec());
+            (ty, value)
+        };
+
+        match parse_asn1_time(ty, value.as_slice()) {
+            Some((secs, frac)) => Ok(format_rfc3339(secs, frac.as_slice())),
+            None => Err(InvalidInput("malformed ASN.1 time".to_string()))
+        }
+    }
+
+    /// Creates a time from an RFC 3339 string such as
+    /// `2014-10-15T14:30:00+02:00`
+    ///
+    /// The time is converted to UTC and stored as a UTCTime for the years
+    /// 1950 through 2049 and as a GeneralizedTime otherwise, as required by
+    /// RFC 5280. Fractional seconds are discarded.
+    pub fn from_rfc3339(s: &str) -> Result<Asn1Time, SslError> {
+        let value = match rfc3339_to_asn1(s) {
+            Some(value) => value,
+            None => return Err(InvalidInput(format!("malformed RFC 3339 time: {}", s)))
+        };
+
+        unsafe {
+            let handle = try_ssl_null!(ffi::ASN1_TIME_new());
+            let time = Asn1Time::new(handle);
+            try_ssl!(value.with_c_str(|value| ffi::ASN1_TIME_set_string(handle, value)));
+            Ok(time)
+        }
+    }
 }
 
 impl Drop for Asn1Time {
@@ -45,3 +89,300 @@ impl Drop for Asn1Time {
         }
     }
 }
+
+/// An ASN.1 INTEGER, such as a certificate serial number
+///
+/// Use `BigNum::to_asn1_integer` and `BigNum::from_asn1_integer` to convert
+/// to and from numbers.
+pub struct Asn1Integer {
+    handle: *mut ffi::ASN1_INTEGER,
+    owned: bool
+}
+
+impl Asn1Integer {
+    /// Wraps existing ASN1_INTEGER and takes ownership
+    pub fn new(handle: *mut ffi::ASN1_INTEGER) -> Asn1Integer {
+        Asn1Integer {
+            handle: handle,
+            owned: true
+        }
+    }
+
+    /// Wraps an ASN1_INTEGER owned by another structure
+    pub fn new_borrowed(handle: *mut ffi::ASN1_INTEGER) -> Asn1Integer {
+        Asn1Integer {
+            handle: handle,
+            owned: false
+        }
+    }
+
+    /// Returns raw handle
+    pub unsafe fn get_handle(&self) -> *mut ffi::ASN1_INTEGER {
+        return self.handle
+    }
+}
+
+impl Drop for Asn1Integer {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { ffi::ASN1_INTEGER_free(self.handle) };
+        }
+    }
+}
+
+macro_rules! try_opt(
+    ($e:expr) => (match $e { Some(v) => v, None => return None })
+)
+
+fn digits(s: &[u8], start: uint, len: uint) -> Option<i64> {
+    if start + len > s.len() {
+        return None;
+    }
+    let mut res = 0i64;
+    for &b in s.slice(start, start + len).iter() {
+        if b < b'0' || b > b'9' {
+            return None;
+        }
+        res = res * 10 + (b - b'0') as i64;
+    }
+    Some(res)
+}
+
+fn is_leap_year(y: i64) -> bool {
+    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+}
+
+fn days_in_month(y: i64, m: i64) -> i64 {
+    match m {
+        2 => if is_leap_year(y) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+// Number of days between 1970-01-01 and the given proleptic Gregorian date
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Inverse of days_from_civil
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+// Seconds since the epoch of a broken down time, after validating it
+fn to_epoch(y: i64, m: i64, d: i64, h: i64, mi: i64, s: i64) -> Option<i64> {
+    if m < 1 || m > 12 || d < 1 || d > days_in_month(y, m) || h > 23 || mi > 59 || s > 59 {
+        return None;
+    }
+    Some(days_from_civil(y, m, d) * 86400 + h * 3600 + mi * 60 + s)
+}
+
+fn from_epoch(secs: i64) -> (i64, i64, i64, i64, i64, i64) {
+    let days = if secs >= 0 { secs / 86400 } else { (secs - 86399) / 86400 };
+    let rem = secs - days * 86400;
+    let (y, m, d) = civil_from_days(days);
+    (y, m, d, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// Parses the contents of a UTCTime or GeneralizedTime into seconds since the
+// epoch (in UTC) and the digits of any fractional seconds
+fn parse_asn1_time(ty: c_int, s: &[u8]) -> Option<(i64, String)> {
+    let (y, mut pos) = if ty == ffi::V_ASN1_UTCTIME {
+        let yy = try_opt!(digits(s, 0, 2));
+        (if yy >= 50 { 1900 + yy } else { 2000 + yy }, 2)
+    } else if ty == ffi::V_ASN1_GENERALIZEDTIME {
+        (try_opt!(digits(s, 0, 4)), 4)
+    } else {
+        return None;
+    };
+
+    let m = try_opt!(digits(s, pos, 2));
+    let d = try_opt!(digits(s, pos + 2, 2));
+    let h = try_opt!(digits(s, pos + 4, 2));
+    let mi = try_opt!(digits(s, pos + 6, 2));
+    pos += 8;
+
+    // Seconds are optional in UTCTime
+    let sec = match digits(s, pos, 2) {
+        Some(sec) => { pos += 2; sec }
+        None => 0
+    };
+
+    let mut frac = String::new();
+    if ty == ffi::V_ASN1_GENERALIZEDTIME && pos < s.len() && (s[pos] == b'.' || s[pos] == b',') {
+        pos += 1;
+        while pos < s.len() && s[pos] >= b'0' && s[pos] <= b'9' {
+            frac.push(s[pos] as char);
+            pos += 1;
+        }
+        if frac.len() == 0 {
+            return None;
+        }
+    }
+
+    let offset = if pos < s.len() && s[pos] == b'Z' {
+        pos += 1;
+        0
+    } else if pos < s.len() && (s[pos] == b'+' || s[pos] == b'-') {
+        let oh = try_opt!(digits(s, pos + 1, 2));
+        let om = try_opt!(digits(s, pos + 3, 2));
+        if oh > 23 || om > 59 {
+            return None;
+        }
+        let offset = oh * 3600 + om * 60;
+        let offset = if s[pos] == b'-' { -offset } else { offset };
+        pos += 5;
+        offset
+    } else {
+        // Local times without a zone can't be normalized
+        return None;
+    };
+
+    if pos != s.len() {
+        return None;
+    }
+
+    Some((try_opt!(to_epoch(y, m, d, h, mi, sec)) - offset, frac))
+}
+
+fn format_rfc3339(secs: i64, frac: &str) -> String {
+    let (y, m, d, h, mi, s) = from_epoch(secs);
+    let frac = if frac.len() == 0 { String::new() } else { format!(".{}", frac) };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z", y, m, d, h, mi, s, frac)
+}
+
+// Converts an RFC 3339 time into the string form of the ASN.1 time type
+// RFC 5280 mandates for it
+fn rfc3339_to_asn1(s: &str) -> Option<String> {
+    let s = s.as_bytes();
+    if s.len() < 20 || s[4] != b'-' || s[7] != b'-' || s[13] != b':' || s[16] != b':' {
+        return None;
+    }
+    if s[10] != b'T' && s[10] != b't' && s[10] != b' ' {
+        return None;
+    }
+
+    let y = try_opt!(digits(s, 0, 4));
+    let m = try_opt!(digits(s, 5, 2));
+    let d = try_opt!(digits(s, 8, 2));
+    let h = try_opt!(digits(s, 11, 2));
+    let mi = try_opt!(digits(s, 14, 2));
+    let sec = try_opt!(digits(s, 17, 2));
+    let mut pos = 19;
+
+    if s[pos] == b'.' {
+        pos += 1;
+        let start = pos;
+        while pos < s.len() && s[pos] >= b'0' && s[pos] <= b'9' {
+            pos += 1;
+        }
+        if pos == start {
+            return None;
+        }
+    }
+
+    let offset = if pos < s.len() && (s[pos] == b'Z' || s[pos] == b'z') {
+        pos += 1;
+        0
+    } else if pos < s.len() && (s[pos] == b'+' || s[pos] == b'-') {
+        if pos + 6 > s.len() || s[pos + 3] != b':' {
+            return None;
+        }
+        let oh = try_opt!(digits(s, pos + 1, 2));
+        let om = try_opt!(digits(s, pos + 4, 2));
+        if oh > 23 || om > 59 {
+            return None;
+        }
+        let offset = oh * 3600 + om * 60;
+        let offset = if s[pos] == b'-' { -offset } else { offset };
+        pos += 6;
+        offset
+    } else {
+        return None;
+    };
+
+    if pos != s.len() {
+        return None;
+    }
+
+    let (y, m, d, h, mi, sec) = from_epoch(try_opt!(to_epoch(y, m, d, h, mi, sec)) - offset);
+    if y >= 1950 && y < 2050 {
+        Some(format!("{:02}{:02}{:02}{:02}{:02}{:02}Z", y % 100, m, d, h, mi, sec))
+    } else if y >= 0 && y <= 9999 {
+        Some(format!("{:04}{:02}{:02}{:02}{:02}{:02}Z", y, m, d, h, mi, sec))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ffi;
+    use super::{Asn1Time, parse_asn1_time, format_rfc3339, rfc3339_to_asn1};
+
+    fn normalize(ty: i32, s: &str) -> Option<String> {
+        parse_asn1_time(ty, s.as_bytes()).map(|(secs, frac)| {
+            format_rfc3339(secs, frac.as_slice())
+        })
+    }
+
+    #[test]
+    fn test_utctime_pivot() {
+        assert_eq!(normalize(ffi::V_ASN1_UTCTIME, "491231235959Z"),
+                   Some("2049-12-31T23:59:59Z".to_string()));
+        assert_eq!(normalize(ffi::V_ASN1_UTCTIME, "500101000000Z"),
+                   Some("1950-01-01T00:00:00Z".to_string()));
+        assert_eq!(normalize(ffi::V_ASN1_UTCTIME, "7001010000Z"),
+                   Some("1970-01-01T00:00:00Z".to_string()));
+    }
+
+    #[test]
+    fn test_generalizedtime() {
+        assert_eq!(normalize(ffi::V_ASN1_GENERALIZEDTIME, "20500101000000Z"),
+                   Some("2050-01-01T00:00:00Z".to_string()));
+        assert_eq!(normalize(ffi::V_ASN1_GENERALIZEDTIME, "19991231230000.25-0130"),
+                   Some("2000-01-01T00:30:00.25Z".to_string()));
+        assert_eq!(normalize(ffi::V_ASN1_GENERALIZEDTIME, "20000229120000+1200"),
+                   Some("2000-02-29T00:00:00Z".to_string()));
+    }
+
+    #[test]
+    fn test_malformed_asn1() {
+        assert_eq!(normalize(ffi::V_ASN1_UTCTIME, "491231235959"), None);
+        assert_eq!(normalize(ffi::V_ASN1_UTCTIME, "490231235959Z"), None);
+        assert_eq!(normalize(ffi::V_ASN1_GENERALIZEDTIME, "20010229000000Z"), None);
+        assert_eq!(normalize(ffi::V_ASN1_GENERALIZEDTIME, "2001022900000Z"), None);
+    }
+
+    #[test]
+    fn test_rfc3339_to_asn1() {
+        assert_eq!(rfc3339_to_asn1("2049-12-31T23:59:59Z"), Some("491231235959Z".to_string()));
+        assert_eq!(rfc3339_to_asn1("2050-01-01T00:00:00Z"), Some("20500101000000Z".to_string()));
+        assert_eq!(rfc3339_to_asn1("1949-12-31T23:59:59Z"), Some("19491231235959Z".to_string()));
+        assert_eq!(rfc3339_to_asn1("2014-10-15T01:00:00.5+02:00"), Some("141014230000Z".to_string()));
+        assert_eq!(rfc3339_to_asn1("2014-10-15 01:00:00"), None);
+        assert_eq!(rfc3339_to_asn1("2014-13-15T01:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let time = Asn1Time::from_rfc3339("2030-06-15T08:09:10-04:00").unwrap();
+        assert_eq!(time.to_rfc3339().unwrap().as_slice(), "2030-06-15T12:09:10Z");
+    }
+}
@@ -0,0 +1,154 @@
+//! Lookups in OpenSSL's table of known ASN.1 objects, translating between
+//! NIDs, short names, long names and OIDs.
+
+use libc::{c_char, c_int};
+use std::c_str::CString;
+use std::string;
+
+use ffi;
+
+/// An entry of OpenSSL's object table
+#[deriving(Show, Clone, PartialEq, Eq)]
+pub struct Object {
+    /// OpenSSL's numeric identifier for the object
+    pub nid: c_int,
+    /// The short name, e.g. `CN`
+    pub short_name: Option<String>,
+    /// The long name, e.g. `commonName`
+    pub long_name: Option<String>,
+    /// The OID in dotted decimal form, e.g. `2.5.4.3`
+    pub oid: Option<String>
+}
+
+impl Object {
+    /// Looks up an object by its NID
+    pub fn from_nid(nid: c_int) -> Option<Object> {
+        ffi::init();
+        unsafe {
+            if nid == ffi::NID_undef || ffi::OBJ_nid2obj(nid).is_null() {
+                ffi::ERR_clear_error();
+                return None;
+            }
+
+            Some(Object {
+                nid: nid,
+                short_name: to_string(ffi::OBJ_nid2sn(nid)),
+                long_name: to_string(ffi::OBJ_nid2ln(nid)),
+                oid: nid_to_oid(nid)
+            })
+        }
+    }
+
+    /// Looks up an object by its short name
+    pub fn from_short_name(name: &str) -> Option<Object> {
+        ffi::init();
+        Object::from_nid(name.with_c_str(|name| unsafe { ffi::OBJ_sn2nid(name) }))
+    }
+
+    /// Looks up an object by its long name
+    pub fn from_long_name(name: &str) -> Option<Object> {
+        ffi::init();
+        Object::from_nid(name.with_c_str(|name| unsafe { ffi::OBJ_ln2nid(name) }))
+    }
+
+    /// Looks up an object by its OID in dotted decimal form
+    pub fn from_oid(oid: &str) -> Option<Object> {
+        ffi::init();
+        let nid = oid.with_c_str(|oid| unsafe {
+            let obj = ffi::OBJ_txt2obj(oid, 1);
+            if obj.is_null() {
+                ffi::ERR_clear_error();
+                return ffi::NID_undef;
+            }
+            let nid = ffi::OBJ_obj2nid(obj as *const ffi::ASN1_OBJECT);
+            ffi::ASN1_OBJECT_free(obj);
+            nid
+        });
+        Object::from_nid(nid)
+    }
+}
+
+/// Returns an iterator over every object known to OpenSSL, including any
+/// added at runtime, in order of NID
+pub fn objects() -> Objects {
+    ffi::init();
+    Objects {
+        nid: ffi::NID_undef + 1,
+        end: unsafe { ffi::OBJ_new_nid(0) }
+    }
+}
+
+/// An iterator over OpenSSL's object table
+pub struct Objects {
+    nid: c_int,
+    end: c_int
+}
+
+impl Iterator<Object> for Objects {
+    fn next(&mut self) -> Option<Object> {
+        while self.nid < self.end {
+            let nid = self.nid;
+            self.nid += 1;
+            // The table has gaps for NIDs which have been retired
+            match Object::from_nid(nid) {
+                Some(obj) => return Some(obj),
+                None => {}
+            }
+        }
+        None
+    }
+}
+
+unsafe fn to_string(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        None
+    } else {
+        CString::new(s, false).as_str().map(|s| s.to_string())
+    }
+}
+
+unsafe fn nid_to_oid(nid: c_int) -> Option<String> {
+    let obj = ffi::OBJ_nid2obj(nid);
+    let mut buf = Vec::from_elem(128, 0u8);
+    let len = ffi::OBJ_obj2txt(buf.as_mut_ptr() as *mut c_char, buf.len() as c_int,
+                               obj as *const ffi::ASN1_OBJECT, 1);
+
+    if len <= 0 || len as uint >= buf.len() {
+        None
+    } else {
+        buf.truncate(len as uint);
+        Some(string::raw::from_utf8(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Object, objects};
+
+    #[test]
+    fn test_lookups() {
+        let cn = Object::from_nid(13).unwrap();
+        assert_eq!(cn.short_name, Some("CN".to_string()));
+        assert_eq!(cn.long_name, Some("commonName".to_string()));
+        assert_eq!(cn.oid, Some("2.5.4.3".to_string()));
+
+        assert_eq!(Object::from_short_name("CN"), Some(cn.clone()));
+        assert_eq!(Object::from_long_name("commonName"), Some(cn.clone()));
+        assert_eq!(Object::from_oid("2.5.4.3"), Some(cn));
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(Object::from_nid(0), None);
+        assert_eq!(Object::from_short_name("notAnObject"), None);
+        assert_eq!(Object::from_oid("1.2.3.4.5.6.7.8.9"), None);
+        assert_eq!(Object::from_oid("not an oid"), None);
+    }
+
+    #[test]
+    fn test_objects() {
+        let all: Vec<Object> = objects().collect();
+        assert!(all.iter().any(|o| o.oid == Some("1.2.840.113549.1.1.1".to_string())));
+        assert!(all.iter().all(|o| o.nid != 0));
+    }
+}
@@ -0,0 +1,308 @@
+//! A structural parser for DER and BER encoded data, similar to the
+//! `openssl asn1parse` command.
+
+use libc::{c_char, c_int, c_long};
+use std::c_str::CString;
+use std::fmt;
+use std::ptr;
+use std::string;
+
+use ffi;
+use ssl::error::{SslError, InvalidInput};
+
+/// The deepest nesting accepted, so that hostile input can't exhaust the
+/// stack
+static MAX_DEPTH: uint = 64;
+
+/// The class of an ASN.1 tag
+#[deriving(Show, Clone, PartialEq, Eq)]
+pub enum Asn1Class {
+    Universal,
+    Application,
+    ContextSpecific,
+    Private
+}
+
+impl Asn1Class {
+    fn from_raw(class: c_int) -> Asn1Class {
+        match class & 0xc0 {
+            ffi::V_ASN1_UNIVERSAL => Universal,
+            ffi::V_ASN1_APPLICATION => Application,
+            ffi::V_ASN1_CONTEXT_SPECIFIC => ContextSpecific,
+            _ => Private
+        }
+    }
+}
+
+/// A single item of a parsed ASN.1 structure
+#[deriving(Clone)]
+pub struct Asn1Node {
+    /// Offset of the item's header from the start of the input
+    pub offset: uint,
+    /// Nesting depth, 0 for top level items
+    pub depth: uint,
+    /// Class of the item's tag
+    pub class: Asn1Class,
+    /// Tag number
+    pub tag: i32,
+    /// Length of the item's header
+    pub header_len: uint,
+    /// Length of the item's contents, `None` for the indefinite length form
+    pub length: Option<uint>,
+    /// Whether the item is constructed, i.e. contains other items
+    pub constructed: bool,
+    /// A printable rendering of primitive contents
+    pub value: Option<String>,
+    /// Items nested inside a constructed item
+    pub children: Vec<Asn1Node>
+}
+
+impl Asn1Node {
+    /// Returns a human readable name for the item's tag, e.g. `SEQUENCE` or
+    /// `cont [ 0 ]`
+    pub fn tag_name(&self) -> String {
+        match self.class {
+            Universal => unsafe {
+                CString::new(ffi::ASN1_tag2str(self.tag as c_int), false).to_string()
+            },
+            Application => format!("appl [ {} ]", self.tag),
+            ContextSpecific => format!("cont [ {} ]", self.tag),
+            Private => format!("priv [ {} ]", self.tag)
+        }
+    }
+}
+
+impl fmt::Show for Asn1Node {
+    /// Formats the item and its children in the style of `asn1parse`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let length = match self.length {
+            Some(len) => format!("{:4}", len),
+            None => "inf".to_string()
+        };
+        let kind = if self.constructed { "cons" } else { "prim" };
+        try!(write!(f, "{:5}:d={}  hl={} l={} {}: {}", self.offset, self.depth,
+                    self.header_len, length, kind, self.tag_name()));
+        match self.value {
+            Some(ref value) => try!(write!(f, " :{}", value)),
+            None => {}
+        }
+        try!(write!(f, "\n"));
+        for child in self.children.iter() {
+            try!(write!(f, "{}", child));
+        }
+        Ok(())
+    }
+}
+
+/// Parses `data` into a tree of ASN.1 items
+///
+/// Both definite and indefinite length encodings are accepted, so BER
+/// encoded structures such as streamed PKCS#7 can be inspected as well.
+/// Items nested more than 64 levels deep are rejected.
+///
+/// # Example
+///
+/// ```
+/// use openssl::asn1::parse::parse;
+///
+/// let der = [0x30, 0x03, 0x02, 0x01, 0x05];
+/// for node in parse(der).unwrap().iter() {
+///     print!("{}", node);
+/// }
+/// ```
+pub fn parse(data: &[u8]) -> Result<Vec<Asn1Node>, SslError> {
+    let (nodes, _) = try!(parse_items(data, 0, 0, false));
+    Ok(nodes)
+}
+
+fn parse_items(data: &[u8], base: uint, depth: uint, until_eoc: bool)
+               -> Result<(Vec<Asn1Node>, uint), SslError> {
+    if depth > MAX_DEPTH {
+        return Err(InvalidInput(format!("nesting deeper than {} levels", MAX_DEPTH)));
+    }
+
+    let mut nodes = vec!();
+    let mut pos = 0u;
+
+    while pos < data.len() {
+        let rest = data.slice_from(pos);
+        let mut p = rest.as_ptr();
+        let mut len: c_long = 0;
+        let mut tag: c_int = 0;
+        let mut class: c_int = 0;
+
+        let ret = unsafe {
+            ffi::ASN1_get_object(&mut p, &mut len, &mut tag, &mut class,
+                                 rest.len() as c_long)
+        };
+        if ret & 0x80 != 0 {
+            return Err(SslError::get());
+        }
+
+        let header_len = p as uint - rest.as_ptr() as uint;
+        let constructed = ret & ffi::V_ASN1_CONSTRUCTED != 0;
+        let indefinite = ret & 0x01 != 0;
+
+        if until_eoc && tag == 0 && class == 0 && len == 0 && !constructed {
+            return Ok((nodes, pos + header_len));
+        }
+
+        let body = rest.slice_from(header_len);
+        let (children, body_len) = if indefinite {
+            try!(parse_items(body, base + pos + header_len, depth + 1, true))
+        } else {
+            let body = body.slice_to(len as uint);
+            if constructed {
+                let (children, _) = try!(parse_items(body, base + pos + header_len,
+                                                     depth + 1, false));
+                (children, body.len())
+            } else {
+                (vec!(), body.len())
+            }
+        };
+
+        let value = if constructed {
+            None
+        } else {
+            Some(printable_value(class, tag, rest.slice_to(header_len + body_len),
+                                 body.slice_to(body_len)))
+        };
+
+        nodes.push(Asn1Node {
+            offset: base + pos,
+            depth: depth,
+            class: Asn1Class::from_raw(class),
+            tag: tag as i32,
+            header_len: header_len,
+            length: if indefinite { None } else { Some(body_len) },
+            constructed: constructed,
+            value: value,
+            children: children
+        });
+        pos += header_len + body_len;
+    }
+
+    if until_eoc {
+        Err(InvalidInput("missing end-of-contents marker".to_string()))
+    } else {
+        Ok((nodes, pos))
+    }
+}
+
+fn printable_value(class: c_int, tag: c_int, item: &[u8], body: &[u8]) -> String {
+    if class != ffi::V_ASN1_UNIVERSAL {
+        return to_hex(body);
+    }
+
+    match tag {
+        ffi::V_ASN1_BOOLEAN if body.len() == 1 => {
+            if body[0] == 0 { "FALSE".to_string() } else { "TRUE".to_string() }
+        }
+        ffi::V_ASN1_NULL => String::new(),
+        ffi::V_ASN1_OBJECT => object_name(item).unwrap_or_else(|| to_hex(body)),
+        ffi::V_ASN1_UTF8STRING | ffi::V_ASN1_NUMERICSTRING |
+        ffi::V_ASN1_PRINTABLESTRING | ffi::V_ASN1_T61STRING |
+        ffi::V_ASN1_IA5STRING | ffi::V_ASN1_UTCTIME |
+        ffi::V_ASN1_GENERALIZEDTIME | ffi::V_ASN1_VISIBLESTRING => {
+            String::from_utf8_lossy(body).into_string()
+        }
+        _ => to_hex(body)
+    }
+}
+
+fn object_name(item: &[u8]) -> Option<String> {
+    unsafe {
+        let mut p = item.as_ptr();
+        let obj = ffi::d2i_ASN1_OBJECT(ptr::null_mut(), &mut p, item.len() as c_long);
+        if obj.is_null() {
+            ffi::ERR_clear_error();
+            return None;
+        }
+
+        let mut buf = Vec::from_elem(128, 0u8);
+        let len = ffi::OBJ_obj2txt(buf.as_mut_ptr() as *mut c_char, buf.len() as c_int,
+                                   obj as *const ffi::ASN1_OBJECT, 0);
+        ffi::ASN1_OBJECT_free(obj);
+
+        if len <= 0 || len as uint >= buf.len() {
+            None
+        } else {
+            buf.truncate(len as uint);
+            Some(string::raw::from_utf8(buf))
+        }
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut acc, b| {
+        acc.push_str(format!("{:02X}", *b).as_slice());
+        acc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Universal, ContextSpecific};
+
+    #[test]
+    fn test_parse_sequence() {
+        let der = [0x30, 0x14,
+                   0x02, 0x01, 0x05,
+                   0x05, 0x00,
+                   0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01,
+                   0x0c, 0x02, 0x68, 0x69];
+        let nodes = parse(der).unwrap();
+        assert_eq!(nodes.len(), 1);
+
+        let seq = &nodes[0];
+        assert!(seq.constructed);
+        assert_eq!(seq.class, Universal);
+        assert_eq!(seq.tag, 16);
+        assert_eq!(seq.header_len, 2);
+        assert_eq!(seq.length, Some(20));
+        assert_eq!(seq.children.len(), 4);
+
+        let values: Vec<Option<String>> = seq.children.iter().map(|n| n.value.clone()).collect();
+        assert_eq!(values, vec!(Some("05".to_string()),
+                                Some("".to_string()),
+                                Some("rsaEncryption".to_string()),
+                                Some("hi".to_string())));
+        assert_eq!(seq.children[3].offset, 18);
+        assert_eq!(seq.children[3].depth, 1);
+    }
+
+    #[test]
+    fn test_parse_indefinite() {
+        let ber = [0xa0, 0x80, 0x02, 0x01, 0x07, 0x00, 0x00, 0x05, 0x00];
+        let nodes = parse(ber).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].class, ContextSpecific);
+        assert_eq!(nodes[0].length, None);
+        assert_eq!(nodes[0].children.len(), 1);
+        assert_eq!(nodes[1].offset, 7);
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        assert!(parse([0x30, 0x05, 0x02, 0x01]).is_err());
+        assert!(parse([0x30, 0x80, 0x02, 0x01, 0x05]).is_err());
+    }
+
+    #[test]
+    fn test_parse_too_deep() {
+        let mut ber = vec!();
+        for _ in range(0u, 100000) {
+            ber.push_all([0x30, 0x80]);
+        }
+        assert!(parse(ber.as_slice()).is_err());
+
+        let mut ber = vec!();
+        for _ in range(0u, 64) {
+            ber.push_all([0x30, 0x80]);
+        }
+        for _ in range(0u, 64) {
+            ber.push_all([0x00, 0x00]);
+        }
+        assert!(parse(ber.as_slice()).is_ok());
+    }
+}
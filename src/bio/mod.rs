@@ -57,43 +57,75 @@ impl MemBio {
 
 impl Reader for MemBio {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
-        let ret = unsafe {
-            ffi::BIO_read(self.bio, buf.as_ptr() as *mut c_void,
-                          buf.len() as c_int)
-        };
+        bio_read(self.bio, buf, "MemBio EOF", "MemBio read error")
+    }
+}
 
-        if ret <= 0 {
-            let is_eof = unsafe { ffi::BIO_eof(self.bio) };
-            let err = if is_eof {
-                IoError {
-                    kind: EndOfFile,
-                    desc: "MemBio EOF",
-                    detail: None
-                }
-            } else {
-                IoError {
-                    kind: OtherIoError,
-                    desc: "MemBio read error",
-                    detail: Some(format!("{}", SslError::get()))
-                }
-            };
-            Err(err)
-        } else {
-            Ok(ret as uint)
+impl Writer for MemBio {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        bio_write(self.bio, buf, "MemBio write error")
+    }
+}
+
+/// A BIO backed by a file on disk
+pub struct FileBio {
+    bio: *mut ffi::BIO
+}
+
+impl Drop for FileBio {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::BIO_free_all(self.bio);
         }
     }
 }
 
-impl Writer for MemBio {
+impl FileBio {
+    /// Opens an existing file for reading
+    pub fn open(path: &Path) -> Result<FileBio, SslError> {
+        FileBio::new_with_mode(path, "rb")
+    }
+
+    /// Creates a file for writing, truncating it if it already exists
+    pub fn create(path: &Path) -> Result<FileBio, SslError> {
+        FileBio::new_with_mode(path, "wb")
+    }
+
+    fn new_with_mode(path: &Path, mode: &str) -> Result<FileBio, SslError> {
+        // On Windows OpenSSL interprets the file name as UTF-8 and falls back
+        // to `_wfopen`, which matches the representation `Path` uses there,
+        // so the same bytes can be handed over on every platform.
+        let bio = path.with_c_str(|path| {
+            mode.with_c_str(|mode| unsafe { ffi::BIO_new_file(path, mode) })
+        });
+        try_ssl_null!(bio);
+
+        Ok(FileBio { bio: bio })
+    }
+
+    /// Temporarily gets wrapped value
+    pub unsafe fn get_handle(&self) -> *mut ffi::BIO {
+        self.bio
+    }
+}
+
+impl Reader for FileBio {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        bio_read(self.bio, buf, "FileBio EOF", "FileBio read error")
+    }
+}
+
+impl Writer for FileBio {
     fn write(&mut self, buf: &[u8]) -> IoResult<()> {
-        let ret = unsafe {
-            ffi::BIO_write(self.bio, buf.as_ptr() as *const c_void,
-                           buf.len() as c_int)
-        };
-        if buf.len() != ret as uint {
+        bio_write(self.bio, buf, "FileBio write error")
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        let ret = unsafe { ffi::BIO_flush(self.bio) };
+        if ret <= 0 {
             Err(IoError {
                 kind: OtherIoError,
-                desc: "MemBio write error",
+                desc: "FileBio flush error",
                 detail: Some(format!("{}", SslError::get()))
             })
         } else {
@@ -101,3 +133,124 @@ impl Writer for MemBio {
         }
     }
 }
+
+/// A BIO which discards everything written to it
+///
+/// Useful to find out how large the output of a serialization routine is
+/// without having to buffer it.
+pub struct NullBio {
+    bio: *mut ffi::BIO
+}
+
+impl Drop for NullBio {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::BIO_free_all(self.bio);
+        }
+    }
+}
+
+impl NullBio {
+    /// Creates a new null BIO
+    pub fn new() -> Result<NullBio, SslError> {
+        let bio = unsafe { ffi::BIO_new(ffi::BIO_s_null()) };
+        try_ssl_null!(bio);
+
+        Ok(NullBio { bio: bio })
+    }
+
+    /// Returns the number of bytes written to this BIO so far
+    pub fn bytes_written(&self) -> u64 {
+        unsafe { ffi::BIO_number_written(self.bio) as u64 }
+    }
+
+    /// Temporarily gets wrapped value
+    pub unsafe fn get_handle(&self) -> *mut ffi::BIO {
+        self.bio
+    }
+}
+
+impl Writer for NullBio {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        bio_write(self.bio, buf, "NullBio write error")
+    }
+}
+
+fn bio_read(bio: *mut ffi::BIO, buf: &mut [u8], eof_desc: &'static str,
+            err_desc: &'static str) -> IoResult<uint> {
+    let ret = unsafe {
+        ffi::BIO_read(bio, buf.as_ptr() as *mut c_void, buf.len() as c_int)
+    };
+
+    if ret <= 0 {
+        let is_eof = unsafe { ffi::BIO_eof(bio) };
+        let err = if is_eof {
+            IoError {
+                kind: EndOfFile,
+                desc: eof_desc,
+                detail: None
+            }
+        } else {
+            IoError {
+                kind: OtherIoError,
+                desc: err_desc,
+                detail: Some(format!("{}", SslError::get()))
+            }
+        };
+        Err(err)
+    } else {
+        Ok(ret as uint)
+    }
+}
+
+fn bio_write(bio: *mut ffi::BIO, buf: &[u8], err_desc: &'static str) -> IoResult<()> {
+    let ret = unsafe {
+        ffi::BIO_write(bio, buf.as_ptr() as *const c_void, buf.len() as c_int)
+    };
+    if buf.len() != ret as uint {
+        Err(IoError {
+            kind: OtherIoError,
+            desc: err_desc,
+            detail: Some(format!("{}", SslError::get()))
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Reader, Writer};
+    use std::io::fs;
+
+    use super::{FileBio, NullBio};
+
+    #[test]
+    fn test_null_bio_counts() {
+        let mut bio = NullBio::new().unwrap();
+        bio.write(b"hello").unwrap();
+        bio.write(b" there").unwrap();
+        assert_eq!(bio.bytes_written(), 11);
+    }
+
+    #[test]
+    fn test_file_bio_round_trip() {
+        let path = Path::new("file_bio_round_trip.txt");
+        {
+            let mut bio = FileBio::create(&path).unwrap();
+            bio.write(b"hello file bio").unwrap();
+            bio.flush().unwrap();
+        }
+        {
+            let mut bio = FileBio::open(&path).unwrap();
+            let data = bio.read_to_end().unwrap();
+            assert_eq!(data.as_slice(), b"hello file bio");
+        }
+        let _ = fs::unlink(&path);
+    }
+
+    #[test]
+    fn test_file_bio_missing() {
+        assert!(FileBio::open(&Path::new("test/does_not_exist.pem")).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Arithmetic on polynomials over GF(2), as used by binary field elliptic
+//! curves.
+//!
+//! A polynomial is represented by a `BigNum` whose bits are its
+//! coefficients, so that `0b1011` is `x^3 + x + 1`. Operations taking a
+//! modulus `p` reduce their result by that irreducible polynomial, working
+//! in the field GF(2^m) where `m` is the degree of `p`.
+//!
+//! This module requires the `ec2m` feature, as OpenSSL is often built
+//! without support for binary fields.
+
+use bn::{BigNum, BnCtx};
+use ffi;
+use ssl::error::SslError;
+
+/// Returns `a + b`, which is also `a - b`.
+pub fn add(a: &BigNum, b: &BigNum) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, { ffi::BN_GF2m_add(r.raw(), a.raw() as *const _, b.raw() as *const _) == 1 })
+    }
+}
+
+/// Returns `a mod p`.
+pub fn modulo(a: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, { ffi::BN_GF2m_mod(r.raw(), a.raw() as *const _, p.raw() as *const _) == 1 })
+    }
+}
+
+/// Returns `a * b mod p`.
+pub fn mod_mul(a: &BigNum, b: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_mul_with_ctx(a, b, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_mul`, reusing `ctx` as scratch space.
+pub fn mod_mul_with_ctx(a: &BigNum, b: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_mul(r.raw(), a.raw() as *const _, b.raw() as *const _,
+                                 p.raw() as *const _, ctx.raw()) == 1
+        })
+    }
+}
+
+/// Returns `a ^ 2 mod p`.
+pub fn mod_sqr(a: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_sqr_with_ctx(a, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_sqr`, reusing `ctx` as scratch space.
+pub fn mod_sqr_with_ctx(a: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_sqr(r.raw(), a.raw() as *const _, p.raw() as *const _, ctx.raw()) == 1
+        })
+    }
+}
+
+/// Returns the `x` such that `a * x = 1 mod p`.
+pub fn mod_inv(a: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_inv_with_ctx(a, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_inv`, reusing `ctx` as scratch space.
+pub fn mod_inv_with_ctx(a: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_inv(r.raw(), a.raw() as *const _, p.raw() as *const _, ctx.raw()) == 1
+        })
+    }
+}
+
+/// Returns `a / b mod p`.
+pub fn mod_div(a: &BigNum, b: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_div_with_ctx(a, b, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_div`, reusing `ctx` as scratch space.
+pub fn mod_div_with_ctx(a: &BigNum, b: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_div(r.raw(), a.raw() as *const _, b.raw() as *const _,
+                                 p.raw() as *const _, ctx.raw()) == 1
+        })
+    }
+}
+
+/// Returns `a ^ e mod p`.
+pub fn mod_exp(a: &BigNum, e: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_exp_with_ctx(a, e, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_exp`, reusing `ctx` as scratch space.
+pub fn mod_exp_with_ctx(a: &BigNum, e: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_exp(r.raw(), a.raw() as *const _, e.raw() as *const _,
+                                 p.raw() as *const _, ctx.raw()) == 1
+        })
+    }
+}
+
+/// Returns the `x` such that `x ^ 2 = a mod p`.
+pub fn mod_sqrt(a: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_sqrt_with_ctx(a, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_sqrt`, reusing `ctx` as scratch space.
+pub fn mod_sqrt_with_ctx(a: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_sqrt(r.raw(), a.raw() as *const _, p.raw() as *const _,
+                                  ctx.raw()) == 1
+        })
+    }
+}
+
+/// Returns a solution `z` of `z ^ 2 + z = a mod p`, failing if there is
+/// none.
+pub fn mod_solve_quad(a: &BigNum, p: &BigNum) -> Result<BigNum, SslError> {
+    mod_solve_quad_with_ctx(a, p, &mut try!(BnCtx::new()))
+}
+
+/// Like `mod_solve_quad`, reusing `ctx` as scratch space.
+pub fn mod_solve_quad_with_ctx(a: &BigNum, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+    unsafe {
+        with_bn!(r, {
+            ffi::BN_GF2m_mod_solve_quad(r.raw(), a.raw() as *const _, p.raw() as *const _,
+                                        ctx.raw()) == 1
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bn::{BigNum, BnCtx};
+    use super::{add, modulo, mod_mul, mod_sqr, mod_inv, mod_solve_quad};
+    use super::{mod_mul_with_ctx, mod_sqr_with_ctx};
+
+    fn bn(n: u64) -> BigNum {
+        BigNum::new_from(n).unwrap()
+    }
+
+    #[test]
+    fn test_field_ops() {
+        // GF(2^4) with p = x^4 + x + 1
+        let p = bn(0b10011);
+
+        assert!(add(&bn(0b1010), &bn(0b0110)).unwrap() == bn(0b1100));
+        assert!(modulo(&bn(0b110011), &p).unwrap() == bn(0b110));
+        // (x^3 + 1)(x + 1) = x^4 + x^3 + x + 1 = x^3
+        assert!(mod_mul(&bn(0b1001), &bn(0b11), &p).unwrap() == bn(0b1000));
+        assert!(mod_sqr(&bn(0b1001), &p).unwrap() == mod_mul(&bn(0b1001), &bn(0b1001), &p).unwrap());
+
+        let inv = mod_inv(&bn(0b1001), &p).unwrap();
+        assert!(mod_mul(&inv, &bn(0b1001), &p).unwrap() == bn(1));
+    }
+
+    #[test]
+    fn test_with_ctx() {
+        let p = bn(0b10011);
+        let mut ctx = BnCtx::new().unwrap();
+        let sqr = mod_sqr_with_ctx(&bn(0b1001), &p, &mut ctx).unwrap();
+        assert!(mod_mul_with_ctx(&bn(0b1001), &bn(0b1001), &p, &mut ctx).unwrap() == sqr);
+    }
+
+    #[test]
+    fn test_solve_quad() {
+        let p = bn(0b10011);
+        let z = mod_solve_quad(&bn(0b110), &p).unwrap();
+        assert!(add(&mod_sqr(&z, &p).unwrap(), &z).unwrap() == bn(0b110));
+    }
+}
@@ -1,13 +1,160 @@
-use libc::{c_int, c_ulong};
-use std::{fmt, ptr};
+use libc::{c_char, c_int, c_ulong, c_void};
+use std::{fmt, mem, ptr};
+use std::hash::{Hash, Writer};
+use std::kinds::marker;
 use std::c_str::CString;
-use std::num::{One, Zero};
+use std::from_str::FromStr;
+use std::num::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, FromStrRadix, One, Zero};
 
+use asn1::Asn1Integer;
 use ffi;
-use ssl::error::SslError;
+use ssl::error::{SslError, InvalidInput};
 
+/// An owned arbitrary precision integer.
+///
+/// The value's memory is zeroed when it is dropped (with `BN_clear_free`);
+/// use `clear` to scrub a secret earlier.
 pub struct BigNum(*mut ffi::BIGNUM);
 
+/// A `BIGNUM` borrowed from another OpenSSL structure.
+///
+/// Unlike `BigNum`, this does not free the underlying value when dropped.
+/// It only gives read access; use `BigNum::as_mut` to modify a value in
+/// place.
+pub struct BigNumRef<'a> {
+    bn: *mut ffi::BIGNUM,
+    marker: marker::ContravariantLifetime<'a>
+}
+
+/// A mutable view of a `BigNum`, created with `BigNum::as_mut`.
+///
+/// The read-only methods of `BigNumRef` are available through `Deref`.
+pub struct BigNumRefMut<'a> {
+    bn: BigNumRef<'a>,
+    marker: marker::NoCopy
+}
+
+/// A progress callback for prime generation.
+///
+/// It is passed the same `(p, n)` pair as OpenSSL's `BN_GENCB` callbacks:
+/// `p` is 0 when a candidate prime is found, 1 as it is tested, 2 when it is
+/// rejected and 3 when it is accepted. Returning `false` aborts generation.
+pub type GenerateCallback<'a> = |c_int, c_int|: 'a -> bool;
+
+extern "C" fn raw_generate_callback(p: c_int, n: c_int, cb: *mut ffi::BN_GENCB) -> c_int {
+    unsafe {
+        let callback: &mut GenerateCallback = mem::transmute((*cb).arg);
+        (*callback)(p, n) as c_int
+    }
+}
+
+/// Calls `f` with a `BN_GENCB` which forwards to `progress`, or with null if
+/// there is no callback.
+#[doc(hidden)]
+pub fn with_gencb<T>(progress: Option<GenerateCallback>, f: |*mut ffi::BN_GENCB| -> T) -> T {
+    match progress {
+        Some(mut progress) => {
+            let mut cb = ffi::BN_GENCB {
+                ver: 2,
+                arg: &mut progress as *mut GenerateCallback as *mut c_void,
+                cb: Some(raw_generate_callback)
+            };
+            f(&mut cb)
+        }
+        None => f(ptr::null_mut())
+    }
+}
+
+/// A scratch space for temporary values used by `BigNum` operations
+///
+/// The `*_with_ctx` methods take one of these rather than allocating a
+/// context for every call, which is much faster when performing many
+/// operations in a row.
+pub struct BnCtx(*mut ffi::BN_CTX);
+
+impl BnCtx {
+    pub fn new() -> Result<BnCtx, SslError> {
+        ffi::init();
+        unsafe {
+            let ctx = ffi::BN_CTX_new();
+            if ctx.is_null() {
+                Err(SslError::get())
+            } else {
+                Ok(BnCtx(ctx))
+            }
+        }
+    }
+
+    unsafe fn raw(&self) -> *mut ffi::BN_CTX {
+        let BnCtx(ctx) = *self;
+        ctx
+    }
+}
+
+impl Drop for BnCtx {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::BN_CTX_free(self.raw());
+        }
+    }
+}
+
+/// An iterator over the probable primes from a starting value up to a bit
+/// length, created by `BigNum::primes`
+///
+/// Each odd candidate is checked with trial division followed by
+/// Miller-Rabin, sharing one `BnCtx` for the whole search.
+pub struct Primes {
+    next: BigNum,
+    bits: i32,
+    ctx: BnCtx,
+    done: bool,
+}
+
+impl Iterator<BigNum> for Primes {
+    fn next(&mut self) -> Option<BigNum> {
+        while !self.done && self.next.num_bits() <= self.bits {
+            let candidate = match self.next.as_ref().to_owned() {
+                Ok(candidate) => candidate,
+                Err(_) => break
+            };
+            let step = if candidate.is_word(2) { 1 } else { 2 };
+            if self.next.add_word(step).is_err() {
+                self.done = true;
+            }
+
+            match candidate.is_prime_fast_with_ctx(0, true, &mut self.ctx) {
+                Ok(true) => return Some(candidate),
+                Ok(false) => {}
+                Err(_) => break
+            }
+        }
+        self.done = true;
+        None
+    }
+}
+
+/// An iterator over the indices of the set bits of a `BigNum`, from least to
+/// most significant, created by `BigNum::bits`
+pub struct Bits<'a> {
+    bn: BigNumRef<'a>,
+    next: i32,
+    len: i32,
+}
+
+impl<'a> Iterator<i32> for Bits<'a> {
+    fn next(&mut self) -> Option<i32> {
+        while self.next < self.len {
+            let n = self.next;
+            self.next += 1;
+            if self.bn.is_bit_set(n) {
+                return Some(n);
+            }
+        }
+        None
+    }
+}
+
 #[repr(C)]
 pub enum RNGProperty {
     MsbMaybeZero = -1,
@@ -15,18 +162,42 @@ pub enum RNGProperty {
     TwoMsbOne = 1,
 }
 
-macro_rules! with_ctx(
-    ($name:ident, $action:block) => ({
-        let $name = ffi::BN_CTX_new();
-        if ($name).is_null() {
-            Err(SslError::get())
-        } else {
-            let r = $action;
-            ffi::BN_CTX_free($name);
-            r
+/// The primes of the NIST elliptic curve fields, for which OpenSSL has fast
+/// reduction routines
+#[deriving(Show, Clone, PartialEq, Eq)]
+pub enum NistPrime {
+    NistP192,
+    NistP224,
+    NistP256,
+    NistP384,
+    NistP521,
+}
+
+impl NistPrime {
+    /// Returns the value of the prime.
+    ///
+    /// The value is a constant in OpenSSL's read-only data, so it can only
+    /// be borrowed; use `to_owned` for a copy which can be modified.
+    pub fn prime(&self) -> BigNumRef<'static> {
+        unsafe { BigNumRef::from_raw(self.raw() as *mut ffi::BIGNUM) }
+    }
+
+    unsafe fn raw(&self) -> *const ffi::BIGNUM {
+        match *self {
+            NistP192 => ffi::BN_get0_nist_prime_192(),
+            NistP224 => ffi::BN_get0_nist_prime_224(),
+            NistP256 => ffi::BN_get0_nist_prime_256(),
+            NistP384 => ffi::BN_get0_nist_prime_384(),
+            NistP521 => ffi::BN_get0_nist_prime_521(),
         }
-    });
-)
+    }
+}
+
+// Whether `w` fits in a BN_ULONG, which is narrower than 64 bits on some
+// platforms. Word operations on larger values fall back to full BigNums.
+fn fits_word(w: u64) -> bool {
+    w as c_ulong as u64 == w
+}
 
 macro_rules! with_bn(
     ($name:ident, $action:block) => ({
@@ -68,8 +239,13 @@ macro_rules! with_bn_in_ctx(
     });
 )
 
+// Declared after the macros above, which it uses
+#[cfg(feature = "ec2m")]
+pub mod gf2m;
+
 impl BigNum {
     pub fn new() -> Result<BigNum, SslError> {
+        ffi::init();
         unsafe {
             let v = ffi::BN_new();
             if v.is_null() {
@@ -81,6 +257,12 @@ impl BigNum {
     }
 
     pub fn new_from(n: u64) -> Result<BigNum, SslError> {
+        if !fits_word(n) {
+            let bytes: Vec<u8> = range(0u, 8).rev().map(|i| (n >> (i * 8)) as u8).collect();
+            return BigNum::new_from_slice(bytes.as_slice());
+        }
+
+        ffi::init();
         unsafe {
             let bn = ffi::BN_new();
             if bn.is_null() || ffi::BN_set_word(bn, n as c_ulong) == 0 {
@@ -92,6 +274,7 @@ impl BigNum {
     }
 
     pub fn new_from_slice(n: &[u8]) -> Result<BigNum, SslError> {
+        ffi::init();
         unsafe {
             let bn = ffi::BN_new();
             if bn.is_null() || ffi::BN_bin2bn(n.as_ptr(), n.len() as c_int, bn).is_null() {
@@ -102,91 +285,252 @@ impl BigNum {
         }
     }
 
-    pub fn checked_sqr(&self) -> Result<BigNum, SslError> {
+    /// Converts an ASN.1 INTEGER, such as a certificate serial number.
+    pub fn from_asn1_integer(ai: &Asn1Integer) -> Result<BigNum, SslError> {
+        ffi::init();
         unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_sqr(r.raw(), self.raw(), ctx) == 1 })
+            let bn = ffi::ASN1_INTEGER_to_BN(ai.get_handle() as *const ffi::ASN1_INTEGER,
+                                             ptr::null_mut());
+            if bn.is_null() {
+                Err(SslError::get())
+            } else {
+                Ok(BigNum(bn))
+            }
         }
     }
 
-    pub fn checked_nnmod(&self, n: &BigNum) -> Result<BigNum, SslError> {
+    /// Creates a non-negative number from little endian bytes.
+    pub fn new_from_slice_le(n: &[u8]) -> Result<BigNum, SslError> {
+        let mut be = n.to_vec();
+        be.reverse();
+        BigNum::new_from_slice(be.as_slice())
+    }
+
+    /// Parses a number in OpenSSL's MPI format, as produced by `to_mpi`.
+    pub fn new_from_mpi(n: &[u8]) -> Result<BigNum, SslError> {
+        ffi::init();
         unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_nnmod(r.raw(), self.raw(), n.raw(), ctx) == 1 })
+            let bn = ffi::BN_new();
+            if bn.is_null() || ffi::BN_mpi2bn(n.as_ptr(), n.len() as c_int, bn).is_null() {
+                if !bn.is_null() {
+                    ffi::BN_clear_free(bn);
+                }
+                Err(SslError::get())
+            } else {
+                Ok(BigNum(bn))
+            }
         }
     }
 
-    pub fn checked_mod_add(&self, a: &BigNum, n: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_mod_add(r.raw(), self.raw(), a.raw(), n.raw(), ctx) == 1 })
+    /// Parses a decimal number, optionally preceded by `-`.
+    pub fn new_from_dec_str(s: &str) -> Result<BigNum, SslError> {
+        BigNum::new_from_str(s, "decimal", |bn, s| unsafe { ffi::BN_dec2bn(bn, s) })
+    }
+
+    /// Parses a hexadecimal number, optionally preceded by `-`.
+    pub fn new_from_hex_str(s: &str) -> Result<BigNum, SslError> {
+        BigNum::new_from_str(s, "hexadecimal", |bn, s| unsafe { ffi::BN_hex2bn(bn, s) })
+    }
+
+    fn new_from_str(s: &str, radix: &str,
+                    parse: |*mut *mut ffi::BIGNUM, *const c_char| -> c_int)
+                    -> Result<BigNum, SslError> {
+        ffi::init();
+        let mut bn = ptr::null_mut();
+        // The parsers stop at the first invalid character rather than
+        // failing, so check that all of the input was consumed
+        let len = s.with_c_str(|s| parse(&mut bn, s));
+        if bn.is_null() {
+            if len == 0 {
+                return Err(InvalidInput(format!("invalid {} number: {}", radix, s)));
+            }
+            return Err(SslError::get());
         }
+
+        let bn = BigNum(bn);
+        if len as uint != s.len() {
+            Err(InvalidInput(format!("invalid {} number: {}", radix, s)))
+        } else {
+            Ok(bn)
+        }
+    }
+
+    pub fn checked_sqr(&self) -> Result<BigNum, SslError> {
+        self.as_ref().checked_sqr()
+    }
+
+    /// Like `checked_sqr`, reusing `ctx` as scratch space.
+    pub fn checked_sqr_with_ctx(&self, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_sqr_with_ctx(ctx)
+    }
+
+    pub fn checked_nnmod(&self, n: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_nnmod(&n.as_ref())
+    }
+
+    /// Like `checked_nnmod`, reusing `ctx` as scratch space.
+    pub fn checked_nnmod_with_ctx(&self, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_nnmod_with_ctx(&n.as_ref(), ctx)
+    }
+
+    pub fn checked_mod_add(&self, a: &BigNum, n: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_add(&a.as_ref(), &n.as_ref())
+    }
+
+    /// Like `checked_mod_add`, reusing `ctx` as scratch space.
+    pub fn checked_mod_add_with_ctx(&self, a: &BigNum, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_add_with_ctx(&a.as_ref(), &n.as_ref(), ctx)
     }
 
     pub fn checked_mod_sub(&self, a: &BigNum, n: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_mod_sub(r.raw(), self.raw(), a.raw(), n.raw(), ctx) == 1 })
-        }
+        self.as_ref().checked_mod_sub(&a.as_ref(), &n.as_ref())
+    }
+
+    /// Like `checked_mod_sub`, reusing `ctx` as scratch space.
+    pub fn checked_mod_sub_with_ctx(&self, a: &BigNum, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_sub_with_ctx(&a.as_ref(), &n.as_ref(), ctx)
     }
 
     pub fn checked_mod_mul(&self, a: &BigNum, n: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_mod_mul(r.raw(), self.raw(), a.raw(), n.raw(), ctx) == 1 })
-        }
+        self.as_ref().checked_mod_mul(&a.as_ref(), &n.as_ref())
+    }
+
+    /// Like `checked_mod_mul`, reusing `ctx` as scratch space.
+    pub fn checked_mod_mul_with_ctx(&self, a: &BigNum, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_mul_with_ctx(&a.as_ref(), &n.as_ref(), ctx)
     }
 
     pub fn checked_mod_sqr(&self, n: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_mod_sqr(r.raw(), self.raw(), n.raw(), ctx) == 1 })
-        }
+        self.as_ref().checked_mod_sqr(&n.as_ref())
+    }
+
+    /// Like `checked_mod_sqr`, reusing `ctx` as scratch space.
+    pub fn checked_mod_sqr_with_ctx(&self, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_sqr_with_ctx(&n.as_ref(), ctx)
     }
 
     pub fn checked_exp(&self, p: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_exp(r.raw(), self.raw(), p.raw(), ctx) == 1 })
-        }
+        self.as_ref().checked_exp(&p.as_ref())
+    }
+
+    /// Like `checked_exp`, reusing `ctx` as scratch space.
+    pub fn checked_exp_with_ctx(&self, p: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_exp_with_ctx(&p.as_ref(), ctx)
     }
 
     pub fn checked_mod_exp(&self, p: &BigNum, n: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_mod_exp(r.raw(), self.raw(), p.raw(), n.raw(), ctx) == 1 })
-        }
+        self.as_ref().checked_mod_exp(&p.as_ref(), &n.as_ref())
+    }
+
+    /// Like `checked_mod_exp`, reusing `ctx` as scratch space.
+    pub fn checked_mod_exp_with_ctx(&self, p: &BigNum, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_exp_with_ctx(&p.as_ref(), &n.as_ref(), ctx)
+    }
+
+    pub fn checked_mod_exp_consttime(&self, p: &BigNum, n: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_exp_consttime(&p.as_ref(), &n.as_ref())
+    }
+
+    /// Like `checked_mod_exp_consttime`, reusing `ctx` as scratch space.
+    pub fn checked_mod_exp_consttime_with_ctx(&self, p: &BigNum, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_exp_consttime_with_ctx(&p.as_ref(), &n.as_ref(), ctx)
+    }
+
+    pub fn checked_mod_exp2(a1: &BigNum, p1: &BigNum, a2: &BigNum, p2: &BigNum, m: &BigNum) -> Result<BigNum, SslError> {
+        BigNumRef::checked_mod_exp2(&a1.as_ref(), &p1.as_ref(), &a2.as_ref(), &p2.as_ref(), &m.as_ref())
+    }
+
+    /// Like `checked_mod_exp2`, reusing `ctx` as scratch space.
+    pub fn checked_mod_exp2_with_ctx(a1: &BigNum, p1: &BigNum, a2: &BigNum, p2: &BigNum, m: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        BigNumRef::checked_mod_exp2_with_ctx(&a1.as_ref(), &p1.as_ref(), &a2.as_ref(), &p2.as_ref(), &m.as_ref(), ctx)
+    }
+
+    pub fn checked_nist_mod(&self, p: NistPrime) -> Result<BigNum, SslError> {
+        self.as_ref().checked_nist_mod(p)
+    }
+
+    /// Like `checked_nist_mod`, reusing `ctx` as scratch space.
+    pub fn checked_nist_mod_with_ctx(&self, p: NistPrime, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_nist_mod_with_ctx(p, ctx)
     }
 
     pub fn checked_mod_inv(&self, n: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { !ffi::BN_mod_inverse(r.raw(), self.raw(), n.raw(), ctx).is_null() })
-        }
+        self.as_ref().checked_mod_inv(&n.as_ref())
+    }
+
+    /// Like `checked_mod_inv`, reusing `ctx` as scratch space.
+    pub fn checked_mod_inv_with_ctx(&self, n: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_inv_with_ctx(&n.as_ref(), ctx)
     }
 
     pub fn checked_gcd(&self, a: &BigNum) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_gcd(r.raw(), self.raw(), a.raw(), ctx) == 1 })
-        }
+        self.as_ref().checked_gcd(&a.as_ref())
+    }
+
+    /// Like `checked_gcd`, reusing `ctx` as scratch space.
+    pub fn checked_gcd_with_ctx(&self, a: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_gcd_with_ctx(&a.as_ref(), ctx)
     }
 
     pub fn checked_generate_prime(bits: i32, safe: bool, add: Option<&BigNum>, rem: Option<&BigNum>) -> Result<BigNum, SslError> {
+        BigNum::checked_generate_prime_with_progress(bits, safe, add, rem, None)
+    }
+
+    /// Generates a prime as `checked_generate_prime` does, calling
+    /// `progress`, if given, as candidates are found and tested.
+    ///
+    /// Generation is cancelled with an error if `progress` returns `false`.
+    pub fn checked_generate_prime_with_progress(bits: i32, safe: bool, add: Option<&BigNum>, rem: Option<&BigNum>,
+                                                progress: Option<GenerateCallback>) -> Result<BigNum, SslError> {
         unsafe {
             with_bn_in_ctx!(r, ctx, {
                 let add_arg = add.map(|a| a.raw()).unwrap_or(ptr::null_mut());
                 let rem_arg = rem.map(|r| r.raw()).unwrap_or(ptr::null_mut());
 
-                ffi::BN_generate_prime_ex(r.raw(), bits as c_int, safe as c_int, add_arg, rem_arg, ptr::null()) == 1
+                with_gencb(progress, |cb| {
+                    ffi::BN_generate_prime_ex(r.raw(), bits as c_int, safe as c_int, add_arg, rem_arg, cb)
+                }) == 1
             })
         }
     }
 
     pub fn is_prime(&self, checks: i32) -> Result<bool, SslError> {
-        unsafe {
-            with_ctx!(ctx, {
-                Ok(ffi::BN_is_prime_ex(self.raw(), checks as c_int, ctx, ptr::null()) == 1)
-            })
+        self.as_ref().is_prime(checks)
+    }
+
+    /// Returns an iterator over the probable primes greater than or equal to
+    /// `self` which are at most `bits` bits long.
+    pub fn primes(&self, bits: i32) -> Result<Primes, SslError> {
+        let mut next = try!(self.as_ref().to_owned());
+        if next < 2 {
+            try!(next.copy_from(&try!(BigNum::new_from(2))));
+        } else if next > 2 && !next.is_odd() {
+            try!(next.add_word(1));
         }
+        Ok(Primes { next: next, bits: bits, ctx: try!(BnCtx::new()), done: false })
+    }
+
+    /// Like `is_prime`, reusing `ctx` as scratch space.
+    pub fn is_prime_with_ctx(&self, checks: i32, ctx: &mut BnCtx) -> Result<bool, SslError> {
+        self.as_ref().is_prime_with_ctx(checks, ctx)
+    }
+
+    pub fn kronecker(&self, n: &BigNum) -> Result<i32, SslError> {
+        self.as_ref().kronecker(&n.as_ref())
+    }
+
+    /// Like `kronecker`, reusing `ctx` as scratch space.
+    pub fn kronecker_with_ctx(&self, n: &BigNum, ctx: &mut BnCtx) -> Result<i32, SslError> {
+        self.as_ref().kronecker_with_ctx(&n.as_ref(), ctx)
     }
 
     pub fn is_prime_fast(&self, checks: i32, do_trial_division: bool) -> Result<bool, SslError> {
-        unsafe {
-            with_ctx!(ctx, {
-                Ok(ffi::BN_is_prime_fasttest_ex(self.raw(), checks as c_int, ctx, do_trial_division as c_int, ptr::null()) == 1)
-            })
-        }
+        self.as_ref().is_prime_fast(checks, do_trial_division)
+    }
+
+    /// Like `is_prime_fast`, reusing `ctx` as scratch space.
+    pub fn is_prime_fast_with_ctx(&self, checks: i32, do_trial_division: bool, ctx: &mut BnCtx) -> Result<bool, SslError> {
+        self.as_ref().is_prime_fast_with_ctx(checks, do_trial_division, ctx)
     }
 
     pub fn checked_new_random(bits: i32, prop: RNGProperty, odd: bool) -> Result<BigNum, SslError> {
@@ -202,20 +546,937 @@ impl BigNum {
     }
 
     pub fn checked_rand_in_range(&self) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_rand_range(r.raw(), self.raw()) == 1 })
-        }
+        self.as_ref().checked_rand_in_range()
     }
 
     pub fn checked_pseudo_rand_in_range(&self) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_pseudo_rand_range(r.raw(), self.raw()) == 1 })
-        }
+        self.as_ref().checked_pseudo_rand_in_range()
     }
 
     pub fn set_bit(&mut self, n: i32) -> Result<(), SslError> {
-        unsafe {
-            if ffi::BN_set_bit(self.raw(), n as c_int) == 1 {
+        self.as_mut().set_bit(n)
+    }
+
+    pub fn clear_bit(&mut self, n: i32) -> Result<(), SslError> {
+        self.as_mut().clear_bit(n)
+    }
+
+    pub fn is_bit_set(&self, n: i32) -> bool {
+        self.as_ref().is_bit_set(n)
+    }
+
+    pub fn mask_bits(&mut self, n: i32) -> Result<(), SslError> {
+        self.as_mut().mask_bits(n)
+    }
+
+    /// Returns an iterator over the indices of the set bits of the
+    /// magnitude, starting with the least significant.
+    pub fn bits<'a>(&'a self) -> Bits<'a> {
+        let bn = self.as_ref();
+        let len = bn.num_bits();
+        Bits { bn: bn, next: 0, len: len }
+    }
+
+    pub fn add_word(&mut self, w: u64) -> Result<(), SslError> {
+        self.as_mut().add_word(w)
+    }
+
+    pub fn sub_word(&mut self, w: u64) -> Result<(), SslError> {
+        self.as_mut().sub_word(w)
+    }
+
+    pub fn mul_word(&mut self, w: u64) -> Result<(), SslError> {
+        self.as_mut().mul_word(w)
+    }
+
+    pub fn div_word(&mut self, w: u64) -> Result<u64, SslError> {
+        self.as_mut().div_word(w)
+    }
+
+    pub fn mod_word(&self, w: u64) -> Result<u64, SslError> {
+        self.as_ref().mod_word(w)
+    }
+
+    pub fn checked_shl1(&self) -> Result<BigNum, SslError> {
+        self.as_ref().checked_shl1()
+    }
+
+    pub fn checked_shr1(&self) -> Result<BigNum, SslError> {
+        self.as_ref().checked_shr1()
+    }
+
+    pub fn checked_add(&self, a: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_add(&a.as_ref())
+    }
+
+    pub fn checked_sub(&self, a: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_sub(&a.as_ref())
+    }
+
+    pub fn checked_mul(&self, a: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mul(&a.as_ref())
+    }
+
+    /// Like `checked_mul`, reusing `ctx` as scratch space.
+    pub fn checked_mul_with_ctx(&self, a: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mul_with_ctx(&a.as_ref(), ctx)
+    }
+
+    pub fn checked_div(&self, a: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_div(&a.as_ref())
+    }
+
+    /// Like `checked_div`, reusing `ctx` as scratch space.
+    pub fn checked_div_with_ctx(&self, a: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_div_with_ctx(&a.as_ref(), ctx)
+    }
+
+    pub fn checked_mod(&self, a: &BigNum) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod(&a.as_ref())
+    }
+
+    /// Like `checked_mod`, reusing `ctx` as scratch space.
+    pub fn checked_mod_with_ctx(&self, a: &BigNum, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        self.as_ref().checked_mod_with_ctx(&a.as_ref(), ctx)
+    }
+
+    pub fn checked_shl(&self, a: &i32) -> Result<BigNum, SslError> {
+        self.as_ref().checked_shl(a)
+    }
+
+    pub fn checked_shr(&self, a: &i32) -> Result<BigNum, SslError> {
+        self.as_ref().checked_shr(a)
+    }
+
+    pub fn add_assign(&mut self, a: &BigNum) -> Result<(), SslError> {
+        self.as_mut().add_assign(&a.as_ref())
+    }
+
+    pub fn sub_assign(&mut self, a: &BigNum) -> Result<(), SslError> {
+        self.as_mut().sub_assign(&a.as_ref())
+    }
+
+    pub fn mul_assign(&mut self, a: &BigNum) -> Result<(), SslError> {
+        self.as_mut().mul_assign(&a.as_ref())
+    }
+
+    /// Like `mul_assign`, reusing `ctx` as scratch space.
+    pub fn mul_assign_with_ctx(&mut self, a: &BigNum, ctx: &mut BnCtx) -> Result<(), SslError> {
+        self.as_mut().mul_assign_with_ctx(&a.as_ref(), ctx)
+    }
+
+    pub fn div_assign(&mut self, a: &BigNum) -> Result<(), SslError> {
+        self.as_mut().div_assign(&a.as_ref())
+    }
+
+    /// Like `div_assign`, reusing `ctx` as scratch space.
+    pub fn div_assign_with_ctx(&mut self, a: &BigNum, ctx: &mut BnCtx) -> Result<(), SslError> {
+        self.as_mut().div_assign_with_ctx(&a.as_ref(), ctx)
+    }
+
+    pub fn mod_assign(&mut self, a: &BigNum) -> Result<(), SslError> {
+        self.as_mut().mod_assign(&a.as_ref())
+    }
+
+    /// Like `mod_assign`, reusing `ctx` as scratch space.
+    pub fn mod_assign_with_ctx(&mut self, a: &BigNum, ctx: &mut BnCtx) -> Result<(), SslError> {
+        self.as_mut().mod_assign_with_ctx(&a.as_ref(), ctx)
+    }
+
+    pub fn mod_mul_assign(&mut self, a: &BigNum, n: &BigNum) -> Result<(), SslError> {
+        self.as_mut().mod_mul_assign(&a.as_ref(), &n.as_ref())
+    }
+
+    /// Like `mod_mul_assign`, reusing `ctx` as scratch space.
+    pub fn mod_mul_assign_with_ctx(&mut self, a: &BigNum, n: &BigNum, ctx: &mut BnCtx) -> Result<(), SslError> {
+        self.as_mut().mod_mul_assign_with_ctx(&a.as_ref(), &n.as_ref(), ctx)
+    }
+
+    pub fn shl_assign(&mut self, n: i32) -> Result<(), SslError> {
+        self.as_mut().shl_assign(n)
+    }
+
+    pub fn shr_assign(&mut self, n: i32) -> Result<(), SslError> {
+        self.as_mut().shr_assign(n)
+    }
+
+    pub fn shl1_assign(&mut self) -> Result<(), SslError> {
+        self.as_mut().shl1_assign()
+    }
+
+    pub fn shr1_assign(&mut self) -> Result<(), SslError> {
+        self.as_mut().shr1_assign()
+    }
+
+    pub fn copy_from(&mut self, other: &BigNum) -> Result<(), SslError> {
+        self.as_mut().copy_from(&other.as_ref())
+    }
+
+    pub fn swap(&mut self, other: &mut BigNum) {
+        self.as_mut().swap(&mut other.as_mut())
+    }
+
+    pub fn negate(&mut self) {
+        self.as_mut().negate()
+    }
+
+    pub fn set_negative(&mut self, negative: bool) {
+        self.as_mut().set_negative(negative)
+    }
+
+    pub fn abs(&self) -> Result<BigNum, SslError> {
+        self.as_ref().abs()
+    }
+
+    pub fn abs_cmp(&self, oth: BigNum) -> Ordering {
+        self.as_ref().abs_cmp(&oth.as_ref())
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.as_ref().is_negative()
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.as_ref().is_odd()
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.as_ref().is_one()
+    }
+
+    pub fn is_word(&self, w: u64) -> bool {
+        self.as_ref().is_word(w)
+    }
+
+    pub fn set_const_time(&mut self) {
+        self.as_mut().set_const_time()
+    }
+
+    pub fn clear(&mut self) {
+        self.as_mut().clear()
+    }
+
+    pub fn is_const_time(&self) -> bool {
+        self.as_ref().is_const_time()
+    }
+
+    pub fn num_bits(&self) -> i32 {
+        self.as_ref().num_bits()
+    }
+
+    pub fn num_bytes(&self) -> i32 {
+        self.as_ref().num_bytes()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        self.as_ref().to_u64()
+    }
+
+    pub fn to_vec_padded(&self, len: uint) -> Result<Vec<u8>, SslError> {
+        self.as_ref().to_vec_padded(len)
+    }
+
+    pub fn to_vec_le(&self) -> Vec<u8> {
+        self.as_ref().to_vec_le()
+    }
+
+    pub fn to_asn1_integer(&self) -> Result<Asn1Integer, SslError> {
+        self.as_ref().to_asn1_integer()
+    }
+
+    pub fn to_vec_padded_le(&self, len: uint) -> Result<Vec<u8>, SslError> {
+        self.as_ref().to_vec_padded_le(len)
+    }
+
+    pub fn to_mpi(&self) -> Vec<u8> {
+        self.as_ref().to_mpi()
+    }
+
+    pub fn to_dec_str(&self) -> String {
+        self.as_ref().to_dec_str()
+    }
+
+    pub fn to_hex_str(&self) -> String {
+        self.as_ref().to_hex_str()
+    }
+
+    /// Returns a borrowed view of this value.
+    pub fn as_ref<'a>(&'a self) -> BigNumRef<'a> {
+        unsafe { BigNumRef::from_raw(self.raw()) }
+    }
+
+    /// Returns a borrowed view of this value which can modify it.
+    pub fn as_mut<'a>(&'a mut self) -> BigNumRefMut<'a> {
+        unsafe {
+            BigNumRefMut { bn: BigNumRef::from_raw(self.raw()), marker: marker::NoCopy }
+        }
+    }
+
+    unsafe fn raw(&self) -> *mut ffi::BIGNUM {
+        let BigNum(n) = *self;
+        n
+    }
+}
+
+impl<'a> BigNumRef<'a> {
+    /// Wraps a `BIGNUM` owned by some other structure.
+    ///
+    /// The pointer must remain valid for `'a`; it is never freed by the
+    /// returned value.
+    pub unsafe fn from_raw(bn: *mut ffi::BIGNUM) -> BigNumRef<'a> {
+        BigNumRef { bn: bn, marker: marker::ContravariantLifetime }
+    }
+
+    /// Copies the value into a new, owned `BigNum`.
+    pub fn to_owned(&self) -> Result<BigNum, SslError> {
+        unsafe {
+            let r = ffi::BN_dup(self.raw());
+            if r.is_null() {
+                Err(SslError::get())
+            } else {
+                Ok(BigNum(r))
+            }
+        }
+    }
+
+    pub fn checked_sqr(&self) -> Result<BigNum, SslError> {
+        self.checked_sqr_with_ctx(&mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_sqr`, reusing `ctx` as scratch space.
+    pub fn checked_sqr_with_ctx(&self, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_sqr(r.raw(), self.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_nnmod(&self, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_nnmod_with_ctx(n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_nnmod`, reusing `ctx` as scratch space.
+    pub fn checked_nnmod_with_ctx(&self, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_nnmod(r.raw(), self.raw(), n.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod_add(&self, a: &BigNumRef, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_add_with_ctx(a, n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_add`, reusing `ctx` as scratch space.
+    pub fn checked_mod_add_with_ctx(&self, a: &BigNumRef, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_add(r.raw(), self.raw(), a.raw(), n.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod_sub(&self, a: &BigNumRef, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_sub_with_ctx(a, n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_sub`, reusing `ctx` as scratch space.
+    pub fn checked_mod_sub_with_ctx(&self, a: &BigNumRef, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_sub(r.raw(), self.raw(), a.raw(), n.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod_mul(&self, a: &BigNumRef, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_mul_with_ctx(a, n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_mul`, reusing `ctx` as scratch space.
+    pub fn checked_mod_mul_with_ctx(&self, a: &BigNumRef, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_mul(r.raw(), self.raw(), a.raw(), n.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod_sqr(&self, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_sqr_with_ctx(n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_sqr`, reusing `ctx` as scratch space.
+    pub fn checked_mod_sqr_with_ctx(&self, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_sqr(r.raw(), self.raw(), n.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_exp(&self, p: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_exp_with_ctx(p, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_exp`, reusing `ctx` as scratch space.
+    pub fn checked_exp_with_ctx(&self, p: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_exp(r.raw(), self.raw(), p.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod_exp(&self, p: &BigNumRef, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_exp_with_ctx(p, n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_exp`, reusing `ctx` as scratch space.
+    pub fn checked_mod_exp_with_ctx(&self, p: &BigNumRef, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_exp(r.raw(), self.raw(), p.raw(), n.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    /// Computes `self ^ p mod n` in time independent of the secret exponent
+    /// `p`, as is needed for private key operations. `n` must be odd.
+    pub fn checked_mod_exp_consttime(&self, p: &BigNumRef, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_exp_consttime_with_ctx(p, n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_exp_consttime`, reusing `ctx` as scratch space.
+    pub fn checked_mod_exp_consttime_with_ctx(&self, p: &BigNumRef, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_exp_mont_consttime(r.raw(), self.raw(), p.raw(), n.raw(), ctx.raw(), ptr::null_mut()) == 1 })
+        }
+    }
+
+    /// Computes `a1 ^ p1 * a2 ^ p2 mod m` in a single pass, which is much
+    /// faster than exponentiating separately. `m` must be odd.
+    pub fn checked_mod_exp2(a1: &BigNumRef, p1: &BigNumRef, a2: &BigNumRef, p2: &BigNumRef, m: &BigNumRef) -> Result<BigNum, SslError> {
+        BigNumRef::checked_mod_exp2_with_ctx(a1, p1, a2, p2, m, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_exp2`, reusing `ctx` as scratch space.
+    pub fn checked_mod_exp2_with_ctx(a1: &BigNumRef, p1: &BigNumRef, a2: &BigNumRef, p2: &BigNumRef, m: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mod_exp2_mont(r.raw(), a1.raw(), p1.raw(), a2.raw(), p2.raw(), m.raw(), ctx.raw(), ptr::null_mut()) == 1 })
+        }
+    }
+
+    /// Returns `self mod p` using OpenSSL's specialized reduction for `p`.
+    ///
+    /// `self` must be non-negative and less than `p` squared, as is the case
+    /// for the product of two reduced field elements.
+    pub fn checked_nist_mod(&self, p: NistPrime) -> Result<BigNum, SslError> {
+        self.checked_nist_mod_with_ctx(p, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_nist_mod`, reusing `ctx` as scratch space.
+    pub fn checked_nist_mod_with_ctx(&self, p: NistPrime, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            let a = self.raw() as *const ffi::BIGNUM;
+            let f: unsafe extern "C" fn(*mut ffi::BIGNUM, *const ffi::BIGNUM,
+                                        *const ffi::BIGNUM, *mut ffi::BN_CTX) -> c_int = match p {
+                NistP192 => ffi::BN_nist_mod_192,
+                NistP224 => ffi::BN_nist_mod_224,
+                NistP256 => ffi::BN_nist_mod_256,
+                NistP384 => ffi::BN_nist_mod_384,
+                NistP521 => ffi::BN_nist_mod_521,
+            };
+            with_bn!(r, { f(r.raw(), a, p.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod_inv(&self, n: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_inv_with_ctx(n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod_inv`, reusing `ctx` as scratch space.
+    pub fn checked_mod_inv_with_ctx(&self, n: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { !ffi::BN_mod_inverse(r.raw(), self.raw(), n.raw(), ctx.raw()).is_null() })
+        }
+    }
+
+    pub fn checked_gcd(&self, a: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_gcd_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_gcd`, reusing `ctx` as scratch space.
+    pub fn checked_gcd_with_ctx(&self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_gcd(r.raw(), self.raw(), a.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn is_prime(&self, checks: i32) -> Result<bool, SslError> {
+        self.is_prime_with_ctx(checks, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `is_prime`, reusing `ctx` as scratch space.
+    pub fn is_prime_with_ctx(&self, checks: i32, ctx: &mut BnCtx) -> Result<bool, SslError> {
+        unsafe {
+            Ok(ffi::BN_is_prime_ex(self.raw(), checks as c_int, ctx.raw(), ptr::null()) == 1)
+        }
+    }
+
+    /// Returns the Kronecker symbol `(self | n)`, which is the Jacobi
+    /// symbol when `n` is odd and positive: -1, 0 or 1.
+    pub fn kronecker(&self, n: &BigNumRef) -> Result<i32, SslError> {
+        self.kronecker_with_ctx(n, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `kronecker`, reusing `ctx` as scratch space.
+    pub fn kronecker_with_ctx(&self, n: &BigNumRef, ctx: &mut BnCtx) -> Result<i32, SslError> {
+        unsafe {
+            let r = ffi::BN_kronecker(self.raw() as *const ffi::BIGNUM,
+                                      n.raw() as *const ffi::BIGNUM, ctx.raw());
+            if r == -2 {
+                Err(SslError::get())
+            } else {
+                Ok(r as i32)
+            }
+        }
+    }
+
+    pub fn is_prime_fast(&self, checks: i32, do_trial_division: bool) -> Result<bool, SslError> {
+        self.is_prime_fast_with_ctx(checks, do_trial_division, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `is_prime_fast`, reusing `ctx` as scratch space.
+    pub fn is_prime_fast_with_ctx(&self, checks: i32, do_trial_division: bool, ctx: &mut BnCtx) -> Result<bool, SslError> {
+        unsafe {
+            Ok(ffi::BN_is_prime_fasttest_ex(self.raw(), checks as c_int, ctx.raw(), do_trial_division as c_int, ptr::null()) == 1)
+        }
+    }
+
+    pub fn checked_rand_in_range(&self) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn_in_ctx!(r, ctx, { ffi::BN_rand_range(r.raw(), self.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_pseudo_rand_in_range(&self) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn_in_ctx!(r, ctx, { ffi::BN_pseudo_rand_range(r.raw(), self.raw()) == 1 })
+        }
+    }
+
+    pub fn is_bit_set(&self, n: i32) -> bool {
+        unsafe {
+            ffi::BN_is_bit_set(self.raw(), n as c_int) == 1
+        }
+    }
+
+    /// Returns `self` modulo `w`.
+    pub fn mod_word(&self, w: u64) -> Result<u64, SslError> {
+        if !fits_word(w) {
+            let mut r = try!(self.checked_mod(&try!(BigNum::new_from(w)).as_ref()));
+            r.set_negative(false);
+            return Ok(r.to_u64().unwrap());
+        }
+        unsafe {
+            let r = ffi::BN_mod_word(self.raw() as *const ffi::BIGNUM, w as c_ulong);
+            if r == -1 as c_ulong {
+                Err(SslError::get())
+            } else {
+                Ok(r as u64)
+            }
+        }
+    }
+
+    pub fn checked_shl1(&self) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_lshift1(r.raw(), self.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_shr1(&self) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_rshift1(r.raw(), self.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_add(&self, a: &BigNumRef) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_add(r.raw(), self.raw(), a.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_sub(&self, a: &BigNumRef) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_sub(r.raw(), self.raw(), a.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mul(&self, a: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mul_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mul`, reusing `ctx` as scratch space.
+    pub fn checked_mul_with_ctx(&self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_mul(r.raw(), self.raw(), a.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_div(&self, a: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_div_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_div`, reusing `ctx` as scratch space.
+    pub fn checked_div_with_ctx(&self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_div(r.raw(), ptr::null_mut(), self.raw(), a.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_mod(&self, a: &BigNumRef) -> Result<BigNum, SslError> {
+        self.checked_mod_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `checked_mod`, reusing `ctx` as scratch space.
+    pub fn checked_mod_with_ctx(&self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_div(ptr::null_mut(), r.raw(), self.raw(), a.raw(), ctx.raw()) == 1 })
+        }
+    }
+
+    pub fn checked_shl(&self, a: &i32) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_lshift(r.raw(), self.raw(), *a as c_int) == 1 })
+        }
+    }
+
+    pub fn checked_shr(&self, a: &i32) -> Result<BigNum, SslError> {
+        unsafe {
+            with_bn!(r, { ffi::BN_rshift(r.raw(), self.raw(), *a as c_int) == 1 })
+        }
+    }
+
+    pub fn is_const_time(&self) -> bool {
+        unsafe {
+            (*self.raw()).flags & ffi::BN_FLG_CONSTTIME != 0
+        }
+    }
+
+    /// Returns the absolute value.
+    pub fn abs(&self) -> Result<BigNum, SslError> {
+        let mut r = try!(self.to_owned());
+        r.set_negative(false);
+        Ok(r)
+    }
+
+    pub fn abs_cmp(&self, oth: &BigNumRef) -> Ordering {
+        unsafe {
+            let res = ffi::BN_ucmp(self.raw(), oth.raw()) as i32;
+            if res < 0 {
+                Less
+            } else if res > 0 {
+                Greater
+            } else {
+                Equal
+            }
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        unsafe {
+            (*self.raw()).neg == 1
+        }
+    }
+
+    pub fn is_odd(&self) -> bool {
+        unsafe {
+            ffi::BN_is_odd(self.raw()) == 1
+        }
+    }
+
+    pub fn is_one(&self) -> bool {
+        unsafe {
+            ffi::BN_is_one(self.raw()) == 1
+        }
+    }
+
+    /// Returns whether the value is exactly `w`.
+    pub fn is_word(&self, w: u64) -> bool {
+        if !fits_word(w) {
+            return self.to_u64() == Some(w);
+        }
+        unsafe {
+            ffi::BN_is_word(self.raw(), w as c_ulong) == 1
+        }
+    }
+
+    pub fn num_bits(&self) -> i32 {
+        unsafe {
+            ffi::BN_num_bits(self.raw()) as i32
+        }
+    }
+
+    pub fn num_bytes(&self) -> i32 {
+        (self.num_bits() + 7) / 8
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let size = self.num_bytes() as uint;
+        let mut v = Vec::with_capacity(size);
+        unsafe {
+            ffi::BN_bn2bin(self.raw(), v.as_mut_ptr());
+            v.set_len(size);
+        }
+        v
+    }
+
+    /// Returns the value as a `u64`, or `None` if it is negative or too
+    /// large to fit.
+    pub fn to_u64(&self) -> Option<u64> {
+        let bits = self.num_bits() as uint;
+        if self.is_negative() || bits > 64 {
+            return None;
+        }
+
+        if bits <= mem::size_of::<c_ulong>() * 8 {
+            unsafe {
+                Some(ffi::BN_get_word(self.raw()) as u64)
+            }
+        } else {
+            // BN_ULONG is narrower than 64 bits on this platform
+            Some(self.to_vec().iter().fold(0u64, |n, &b| n << 8 | b as u64))
+        }
+    }
+
+    /// Returns the magnitude as big endian bytes, left padded with zeros to
+    /// exactly `len` bytes.
+    pub fn to_vec_padded(&self, len: uint) -> Result<Vec<u8>, SslError> {
+        let size = self.num_bytes() as uint;
+        if size > len {
+            return Err(InvalidInput(format!("{} bytes are needed but only {} are available",
+                                            size, len)));
+        }
+
+        let mut v = Vec::from_elem(len, 0u8);
+        unsafe {
+            ffi::BN_bn2bin(self.raw(), v.as_mut_ptr().offset((len - size) as int));
+        }
+        Ok(v)
+    }
+
+    /// Returns the magnitude as little endian bytes.
+    pub fn to_vec_le(&self) -> Vec<u8> {
+        let mut v = self.to_vec();
+        v.reverse();
+        v
+    }
+
+    /// Returns the magnitude as little endian bytes, right padded with zeros
+    /// to exactly `len` bytes.
+    pub fn to_vec_padded_le(&self, len: uint) -> Result<Vec<u8>, SslError> {
+        let mut v = try!(self.to_vec_padded(len));
+        v.reverse();
+        Ok(v)
+    }
+
+    /// Converts the value to an ASN.1 INTEGER.
+    pub fn to_asn1_integer(&self) -> Result<Asn1Integer, SslError> {
+        unsafe {
+            let ai = try_ssl_null!(ffi::BN_to_ASN1_INTEGER(self.raw() as *const ffi::BIGNUM,
+                                                            ptr::null_mut()));
+            Ok(Asn1Integer::new(ai))
+        }
+    }
+
+    /// Returns the value in OpenSSL's MPI format: a four byte big endian
+    /// length followed by the sign-magnitude value in big endian. The sign
+    /// is the most significant bit of the magnitude, with a leading zero
+    /// byte added if that bit is already in use.
+    pub fn to_mpi(&self) -> Vec<u8> {
+        unsafe {
+            let size = ffi::BN_bn2mpi(self.raw(), ptr::null_mut()) as uint;
+            let mut v = Vec::with_capacity(size);
+            ffi::BN_bn2mpi(self.raw(), v.as_mut_ptr());
+            v.set_len(size);
+            v
+        }
+    }
+
+    pub fn to_dec_str(&self) -> String {
+        unsafe {
+            let buf = ffi::BN_bn2dec(self.raw());
+            assert!(!buf.is_null());
+            let c_str = CString::new(buf, false);
+            let str = c_str.as_str().unwrap().to_string();
+            ffi::CRYPTO_free(buf);
+            str
+        }
+    }
+
+    /// Returns the value in upper case hexadecimal, as formatted by OpenSSL.
+    ///
+    /// The digits are always a whole number of bytes, so there may be a
+    /// leading zero, e.g. `0F`. Use the `LowerHex` or `UpperHex` formatting
+    /// traits for minimal output.
+    pub fn to_hex_str(&self) -> String {
+        unsafe {
+            let buf = ffi::BN_bn2hex(self.raw());
+            assert!(!buf.is_null());
+            let c_str = CString::new(buf, false);
+            let str = c_str.as_str().unwrap().to_string();
+            ffi::CRYPTO_free(buf);
+            str
+        }
+    }
+
+    fn fmt_hex(&self, f: &mut fmt::Formatter, upper: bool) -> fmt::Result {
+        let hex = self.to_hex_str();
+        let digits = hex.as_slice().trim_left_chars('-').trim_left_chars('0');
+        let digits = if digits.is_empty() { "0" } else { digits };
+        let digits = if upper {
+            digits.to_string()
+        } else {
+            digits.chars().map(|c| c.to_lowercase()).collect()
+        };
+        f.pad_integral(!self.is_negative(), "0x", digits.as_bytes())
+    }
+
+    unsafe fn raw(&self) -> *mut ffi::BIGNUM {
+        self.bn
+    }
+}
+
+impl<'a> BigNumRefMut<'a> {
+    pub fn set_bit(&mut self, n: i32) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_set_bit(self.raw(), n as c_int) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    pub fn clear_bit(&mut self, n: i32) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_clear_bit(self.raw(), n as c_int) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    pub fn mask_bits(&mut self, n: i32) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_mask_bits(self.raw(), n as c_int) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Adds `w` to `self` in place.
+    pub fn add_word(&mut self, w: u64) -> Result<(), SslError> {
+        if !fits_word(w) {
+            return self.add_assign(&try!(BigNum::new_from(w)).as_ref());
+        }
+        unsafe {
+            if ffi::BN_add_word(self.raw(), w as c_ulong) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Subtracts `w` from `self` in place.
+    pub fn sub_word(&mut self, w: u64) -> Result<(), SslError> {
+        if !fits_word(w) {
+            return self.sub_assign(&try!(BigNum::new_from(w)).as_ref());
+        }
+        unsafe {
+            if ffi::BN_sub_word(self.raw(), w as c_ulong) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Multiplies `self` by `w` in place.
+    pub fn mul_word(&mut self, w: u64) -> Result<(), SslError> {
+        if !fits_word(w) {
+            return self.mul_assign(&try!(BigNum::new_from(w)).as_ref());
+        }
+        unsafe {
+            if ffi::BN_mul_word(self.raw(), w as c_ulong) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Divides `self` by `w` in place, returning the remainder.
+    pub fn div_word(&mut self, w: u64) -> Result<u64, SslError> {
+        if !fits_word(w) {
+            let w = try!(BigNum::new_from(w));
+            let mut r = try!(self.checked_mod(&w.as_ref()));
+            try!(self.div_assign(&w.as_ref()));
+            r.set_negative(false);
+            return Ok(r.to_u64().unwrap());
+        }
+        unsafe {
+            let r = ffi::BN_div_word(self.raw(), w as c_ulong);
+            if r == -1 as c_ulong {
+                Err(SslError::get())
+            } else {
+                Ok(r as u64)
+            }
+        }
+    }
+
+    /// Adds `a` to `self` in place.
+    pub fn add_assign(&mut self, a: &BigNumRef) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_add(self.raw(), self.raw(), a.raw()) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Subtracts `a` from `self` in place.
+    pub fn sub_assign(&mut self, a: &BigNumRef) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_sub(self.raw(), self.raw(), a.raw()) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Multiplies `self` by `a` in place.
+    pub fn mul_assign(&mut self, a: &BigNumRef) -> Result<(), SslError> {
+        self.mul_assign_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `mul_assign`, reusing `ctx` as scratch space.
+    pub fn mul_assign_with_ctx(&mut self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_mul(self.raw(), self.raw(), a.raw(), ctx.raw()) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
+        }
+    }
+
+    /// Divides `self` by `a` in place, rounding towards zero.
+    pub fn div_assign(&mut self, a: &BigNumRef) -> Result<(), SslError> {
+        self.div_assign_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `div_assign`, reusing `ctx` as scratch space.
+    pub fn div_assign_with_ctx(&mut self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<(), SslError> {
+        unsafe {
+            if ffi::BN_div(self.raw(), ptr::null_mut(), self.raw(), a.raw(), ctx.raw()) == 1 {
                 Ok(())
             } else {
                 Err(SslError::get())
@@ -223,9 +1484,15 @@ impl BigNum {
         }
     }
 
-    pub fn clear_bit(&mut self, n: i32) -> Result<(), SslError> {
+    /// Replaces `self` with the remainder of dividing it by `a`.
+    pub fn mod_assign(&mut self, a: &BigNumRef) -> Result<(), SslError> {
+        self.mod_assign_with_ctx(a, &mut try!(BnCtx::new()))
+    }
+
+    /// Like `mod_assign`, reusing `ctx` as scratch space.
+    pub fn mod_assign_with_ctx(&mut self, a: &BigNumRef, ctx: &mut BnCtx) -> Result<(), SslError> {
         unsafe {
-            if ffi::BN_clear_bit(self.raw(), n as c_int) == 1 {
+            if ffi::BN_div(ptr::null_mut(), self.raw(), self.raw(), a.raw(), ctx.raw()) == 1 {
                 Ok(())
             } else {
                 Err(SslError::get())
@@ -233,15 +1500,15 @@ impl BigNum {
         }
     }
 
-    pub fn is_bit_set(&self, n: i32) -> bool {
-        unsafe {
-            ffi::BN_is_bit_set(self.raw(), n as c_int) == 1
-        }
+    /// Multiplies `self` by `a` modulo `n` in place.
+    pub fn mod_mul_assign(&mut self, a: &BigNumRef, n: &BigNumRef) -> Result<(), SslError> {
+        self.mod_mul_assign_with_ctx(a, n, &mut try!(BnCtx::new()))
     }
 
-    pub fn mask_bits(&mut self, n: i32) -> Result<(), SslError> {
+    /// Like `mod_mul_assign`, reusing `ctx` as scratch space.
+    pub fn mod_mul_assign_with_ctx(&mut self, a: &BigNumRef, n: &BigNumRef, ctx: &mut BnCtx) -> Result<(), SslError> {
         unsafe {
-            if ffi::BN_mask_bits(self.raw(), n as c_int) == 1 {
+            if ffi::BN_mod_mul(self.raw(), self.raw(), a.raw(), n.raw(), ctx.raw()) == 1 {
                 Ok(())
             } else {
                 Err(SslError::get())
@@ -249,57 +1516,83 @@ impl BigNum {
         }
     }
 
-    pub fn checked_shl1(&self) -> Result<BigNum, SslError> {
-        unsafe {
-            with_bn!(r, { ffi::BN_lshift1(r.raw(), self.raw()) == 1 })
-        }
-    }
-
-    pub fn checked_shr1(&self) -> Result<BigNum, SslError> {
+    /// Shifts `self` left by `n` bits in place.
+    pub fn shl_assign(&mut self, n: i32) -> Result<(), SslError> {
         unsafe {
-            with_bn!(r, { ffi::BN_rshift1(r.raw(), self.raw()) == 1 })
+            if ffi::BN_lshift(self.raw(), self.raw(), n as c_int) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
         }
     }
 
-    pub fn checked_add(&self, a: &BigNum) -> Result<BigNum, SslError> {
+    /// Shifts `self` right by `n` bits in place.
+    pub fn shr_assign(&mut self, n: i32) -> Result<(), SslError> {
         unsafe {
-            with_bn!(r, { ffi::BN_add(r.raw(), self.raw(), a.raw()) == 1 })
+            if ffi::BN_rshift(self.raw(), self.raw(), n as c_int) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
         }
     }
 
-    pub fn checked_sub(&self, a: &BigNum) -> Result<BigNum, SslError> {
+    /// Shifts `self` left by one bit in place.
+    pub fn shl1_assign(&mut self) -> Result<(), SslError> {
         unsafe {
-            with_bn!(r, { ffi::BN_sub(r.raw(), self.raw(), a.raw()) == 1 })
+            if ffi::BN_lshift1(self.raw(), self.raw()) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
         }
     }
 
-    pub fn checked_mul(&self, a: &BigNum) -> Result<BigNum, SslError> {
+    /// Shifts `self` right by one bit in place.
+    pub fn shr1_assign(&mut self) -> Result<(), SslError> {
         unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_mul(r.raw(), self.raw(), a.raw(), ctx) == 1 })
+            if ffi::BN_rshift1(self.raw(), self.raw()) == 1 {
+                Ok(())
+            } else {
+                Err(SslError::get())
+            }
         }
     }
 
-    pub fn checked_div(&self, a: &BigNum) -> Result<BigNum, SslError> {
+    /// Sets `self` to the value of `other`, reusing `self`'s storage where
+    /// possible.
+    pub fn copy_from(&mut self, other: &BigNumRef) -> Result<(), SslError> {
         unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_div(r.raw(), ptr::null_mut(), self.raw(), a.raw(), ctx) == 1 })
+            if ffi::BN_copy(self.raw(), other.raw() as *const ffi::BIGNUM).is_null() {
+                Err(SslError::get())
+            } else {
+                Ok(())
+            }
         }
     }
 
-    pub fn checked_mod(&self, a: &BigNum) -> Result<BigNum, SslError> {
+    /// Exchanges the values of `self` and `other` without allocating.
+    pub fn swap(&mut self, other: &mut BigNumRefMut) {
         unsafe {
-            with_bn_in_ctx!(r, ctx, { ffi::BN_div(ptr::null_mut(), r.raw(), self.raw(), a.raw(), ctx) == 1 })
+            ffi::BN_swap(self.raw(), other.raw())
         }
     }
 
-    pub fn checked_shl(&self, a: &i32) -> Result<BigNum, SslError> {
+    /// Marks the value as secret, so that OpenSSL processes it with its
+    /// constant-time algorithms (`BN_FLG_CONSTTIME`).
+    ///
+    /// This should be set on private exponents and other key material.
+    pub fn set_const_time(&mut self) {
         unsafe {
-            with_bn!(r, { ffi::BN_lshift(r.raw(), self.raw(), *a as c_int) == 1 })
+            (*self.raw()).flags |= ffi::BN_FLG_CONSTTIME;
         }
     }
 
-    pub fn checked_shr(&self, a: &i32) -> Result<BigNum, SslError> {
+    /// Zeroes the value's memory and sets it to 0.
+    pub fn clear(&mut self) {
         unsafe {
-            with_bn!(r, { ffi::BN_rshift(r.raw(), self.raw(), *a as c_int) == 1 })
+            ffi::BN_clear(self.raw())
         }
     }
 
@@ -309,58 +1602,42 @@ impl BigNum {
         }
     }
 
-    pub fn abs_cmp(&self, oth: BigNum) -> Ordering {
-        unsafe {
-            let res = ffi::BN_ucmp(self.raw(), oth.raw()) as i32;
-            if res < 0 {
-                Less
-            } else if res > 0 {
-                Greater
-            } else {
-                Equal
-            }
-        }
-    }
-
-    pub fn is_negative(&self) -> bool {
+    /// Sets the sign of the value. Zero is never negative.
+    pub fn set_negative(&mut self, negative: bool) {
         unsafe {
-            (*self.raw()).neg == 1
+            ffi::BN_set_negative(self.raw(), negative as c_int)
         }
     }
+}
 
-    pub fn num_bits(&self) -> i32 {
-        unsafe {
-            ffi::BN_num_bits(self.raw()) as i32
-        }
+impl<'a> Deref<BigNumRef<'a>> for BigNumRefMut<'a> {
+    fn deref<'b>(&'b self) -> &'b BigNumRef<'a> {
+        &self.bn
     }
+}
 
-    pub fn num_bytes(&self) -> i32 {
-        (self.num_bits() + 7) / 8
+impl<'a> fmt::Show for BigNumRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_dec_str())
     }
+}
 
-    unsafe fn raw(&self) -> *mut ffi::BIGNUM {
-        let BigNum(n) = *self;
-        n
+impl<'a> fmt::LowerHex for BigNumRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_hex(f, false)
     }
+}
 
-    pub fn to_vec(&self) -> Vec<u8> {
-        let size = self.num_bytes() as uint;
-        let mut v = Vec::with_capacity(size);
-        unsafe {
-            ffi::BN_bn2bin(self.raw(), v.as_mut_ptr());
-            v.set_len(size);
-        }
-        v
+impl<'a> fmt::UpperHex for BigNumRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_hex(f, true)
     }
+}
 
-    pub fn to_dec_str(&self) -> String {
+impl<'a> PartialEq for BigNumRef<'a> {
+    fn eq(&self, oth: &BigNumRef<'a>) -> bool {
         unsafe {
-            let buf = ffi::BN_bn2dec(self.raw());
-            assert!(!buf.is_null());
-            let c_str = CString::new(buf, false);
-            let str = c_str.as_str().unwrap().to_string();
-            ffi::CRYPTO_free(buf);
-            str
+            ffi::BN_cmp(self.raw(), oth.raw()) == 0
         }
     }
 }
@@ -371,12 +1648,41 @@ impl fmt::Show for BigNum {
     }
 }
 
+impl fmt::LowerHex for BigNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_ref().fmt_hex(f, false)
+    }
+}
+
+impl fmt::UpperHex for BigNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_ref().fmt_hex(f, true)
+    }
+}
+
 impl One for BigNum {
     fn one() -> BigNum {
         BigNum::new_from(1).unwrap()
     }
 }
 
+impl FromStr for BigNum {
+    fn from_str(s: &str) -> Option<BigNum> {
+        BigNum::new_from_dec_str(s).ok()
+    }
+}
+
+/// Only radixes 10 and 16 are supported.
+impl FromStrRadix for BigNum {
+    fn from_str_radix(s: &str, radix: uint) -> Option<BigNum> {
+        match radix {
+            10 => BigNum::new_from_dec_str(s).ok(),
+            16 => BigNum::new_from_hex_str(s).ok(),
+            _ => None
+        }
+    }
+}
+
 impl Zero for BigNum {
     fn zero() -> BigNum {
         BigNum::new_from(0).unwrap()
@@ -388,6 +1694,30 @@ impl Zero for BigNum {
     }
 }
 
+impl CheckedAdd for BigNum {
+    fn checked_add(&self, oth: &BigNum) -> Option<BigNum> {
+        self.as_ref().checked_add(&oth.as_ref()).ok()
+    }
+}
+
+impl CheckedSub for BigNum {
+    fn checked_sub(&self, oth: &BigNum) -> Option<BigNum> {
+        self.as_ref().checked_sub(&oth.as_ref()).ok()
+    }
+}
+
+impl CheckedMul for BigNum {
+    fn checked_mul(&self, oth: &BigNum) -> Option<BigNum> {
+        self.as_ref().checked_mul(&oth.as_ref()).ok()
+    }
+}
+
+impl CheckedDiv for BigNum {
+    fn checked_div(&self, oth: &BigNum) -> Option<BigNum> {
+        self.as_ref().checked_div(&oth.as_ref()).ok()
+    }
+}
+
 impl Eq for BigNum { }
 impl PartialEq for BigNum {
     fn eq(&self, oth: &BigNum) -> bool {
@@ -397,6 +1727,31 @@ impl PartialEq for BigNum {
     }
 }
 
+impl PartialEq<u64> for BigNum {
+    fn eq(&self, oth: &u64) -> bool {
+        self.is_word(*oth)
+    }
+}
+
+impl PartialOrd<u64> for BigNum {
+    fn partial_cmp(&self, oth: &u64) -> Option<Ordering> {
+        if self.is_negative() {
+            return Some(Less);
+        }
+        match self.to_u64() {
+            Some(n) => n.partial_cmp(oth),
+            None => Some(Greater)
+        }
+    }
+}
+
+impl<S: Writer> Hash<S> for BigNum {
+    fn hash(&self, state: &mut S) {
+        self.is_negative().hash(state);
+        self.to_vec().hash(state);
+    }
+}
+
 impl Ord for BigNum {
     fn cmp(&self, oth: &BigNum) -> Ordering {
         self.partial_cmp(oth).unwrap()
@@ -452,6 +1807,30 @@ pub mod unchecked {
         }
     }
 
+    impl Add<u64, BigNum> for BigNum {
+        fn add(&self, oth: &u64) -> BigNum {
+            let mut n = self.clone();
+            n.add_word(*oth).unwrap();
+            n
+        }
+    }
+
+    impl Sub<u64, BigNum> for BigNum {
+        fn sub(&self, oth: &u64) -> BigNum {
+            let mut n = self.clone();
+            n.sub_word(*oth).unwrap();
+            n
+        }
+    }
+
+    impl Mul<u64, BigNum> for BigNum {
+        fn mul(&self, oth: &u64) -> BigNum {
+            let mut n = self.clone();
+            n.mul_word(*oth).unwrap();
+            n
+        }
+    }
+
     impl Div<BigNum, BigNum> for BigNum {
         fn div(&self, oth: &BigNum) -> BigNum {
             self.checked_div(oth).unwrap()
@@ -500,7 +1879,7 @@ pub mod unchecked {
 
 #[cfg(test)]
 mod tests {
-    use bn::BigNum;
+    use bn::{BigNum, BnCtx, NistP256, NistP384};
 
     #[test]
     fn test_to_from_slice() {
@@ -519,6 +1898,394 @@ mod tests {
         assert!((-a).is_negative());
     }
 
+    #[test]
+    fn test_sign() {
+        let mut a = BigNum::new_from(5_u64).unwrap();
+        a.set_negative(true);
+        assert!(a.is_negative());
+        a.set_negative(true);
+        assert!(a.is_negative());
+        assert!(a.abs().unwrap() == BigNum::new_from(5_u64).unwrap());
+        assert!(a.is_negative());
+        a.set_negative(false);
+        assert!(!a.is_negative());
+
+        let mut zero = BigNum::new().unwrap();
+        zero.set_negative(true);
+        assert!(!zero.is_negative());
+    }
+
+    #[test]
+    fn test_checked_traits() {
+        use std::num::{CheckedAdd, CheckedDiv, Zero};
+
+        fn sum_then_div<T: CheckedAdd + CheckedDiv>(a: &T, b: &T, c: &T) -> Option<T> {
+            a.checked_add(b).and_then(|s| s.checked_div(c))
+        }
+
+        let a = BigNum::new_from(10_u64).unwrap();
+        let b = BigNum::new_from(4_u64).unwrap();
+        let c = BigNum::new_from(7_u64).unwrap();
+        assert!(sum_then_div(&a, &b, &c).unwrap() == BigNum::new_from(2_u64).unwrap());
+        assert!(sum_then_div(&a, &b, &Zero::zero()).is_none());
+    }
+
+    #[test]
+    fn test_ref_arithmetic() {
+        let a = BigNum::new_from(1234_u64).unwrap();
+        let b = BigNum::new_from(5678_u64).unwrap();
+
+        let r = a.as_ref();
+        assert!(r.checked_add(&b.as_ref()).unwrap() == a.checked_add(&b).unwrap());
+        assert!(r.checked_mod_exp(&b.as_ref(), &b.as_ref()).unwrap()
+                == a.checked_mod_exp(&b, &b).unwrap());
+        assert!(r.to_owned().unwrap() == a);
+        assert!(r == a.as_ref());
+    }
+
+    #[test]
+    fn test_mut_ref() {
+        let mut a = BigNum::new_from(1234_u64).unwrap();
+        let b = BigNum::new_from(5678_u64).unwrap();
+
+        {
+            let mut r = a.as_mut();
+            r.add_assign(&b.as_ref()).unwrap();
+            assert!(r.is_word(6912));
+            r.negate();
+        }
+        assert!(a == BigNum::new_from_dec_str("-6912").unwrap());
+    }
+
+
+    #[test]
+    fn test_with_ctx() {
+        let mut ctx = BnCtx::new().unwrap();
+        let a = BigNum::new_from(3_u64).unwrap();
+        let n = BigNum::new_from(1000000007_u64).unwrap();
+
+        let mut r = BigNum::new_from(1_u64).unwrap();
+        for _ in range(0u, 100) {
+            r = r.checked_mod_mul_with_ctx(&a, &n, &mut ctx).unwrap();
+        }
+        let p = BigNum::new_from(100_u64).unwrap();
+        assert!(r == a.checked_mod_exp_with_ctx(&p, &n, &mut ctx).unwrap());
+        assert!(r == a.checked_mod_exp(&p, &n).unwrap());
+        assert!(n.is_prime_with_ctx(50, &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn test_mod_exp_consttime() {
+        let a = BigNum::new_from(4_u64).unwrap();
+        let p = BigNum::new_from(13_u64).unwrap();
+        let n = BigNum::new_from(497_u64).unwrap();
+
+        let r = a.checked_mod_exp_consttime(&p, &n).unwrap();
+        assert!(r == BigNum::new_from(445_u64).unwrap());
+        assert!(r == a.checked_mod_exp(&p, &n).unwrap());
+        assert!(a.checked_mod_exp_consttime(&p, &BigNum::new_from(498_u64).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_mod_exp2() {
+        let a1 = BigNum::new_from(4_u64).unwrap();
+        let p1 = BigNum::new_from(13_u64).unwrap();
+        let a2 = BigNum::new_from(7_u64).unwrap();
+        let p2 = BigNum::new_from(21_u64).unwrap();
+        let m = BigNum::new_from(497_u64).unwrap();
+
+        let r = BigNum::checked_mod_exp2(&a1, &p1, &a2, &p2, &m).unwrap();
+        let expected = a1.checked_mod_exp(&p1, &m).unwrap()
+                         .checked_mod_mul(&a2.checked_mod_exp(&p2, &m).unwrap(), &m).unwrap();
+        assert!(r == expected);
+    }
+
+    #[test]
+    fn test_assign_ops() {
+        let mut ctx = BnCtx::new().unwrap();
+        let mut a = BigNum::new_from(10_u64).unwrap();
+        let b = BigNum::new_from(3_u64).unwrap();
+
+        a.add_assign(&b).unwrap();
+        a.mul_assign_with_ctx(&b, &mut ctx).unwrap();
+        a.sub_assign(&b).unwrap();
+        assert!(a == BigNum::new_from(36_u64).unwrap());
+
+        a.shl_assign(4).unwrap();
+        a.div_assign(&b).unwrap();
+        a.shr_assign(1).unwrap();
+        assert!(a == BigNum::new_from(96_u64).unwrap());
+
+        a.shl1_assign().unwrap();
+        a.shr1_assign().unwrap();
+        a.shr1_assign().unwrap();
+        assert!(a == BigNum::new_from(48_u64).unwrap());
+        a.shl1_assign().unwrap();
+
+        a.mod_mul_assign(&b, &BigNum::new_from(50_u64).unwrap()).unwrap();
+        a.mod_assign(&BigNum::new_from(7_u64).unwrap()).unwrap();
+        assert!(a == BigNum::new_from(3_u64).unwrap());
+    }
+
+    #[test]
+    fn test_bits() {
+        let a = BigNum::new_from(0b1000101_u64).unwrap();
+        assert_eq!(a.bits().collect::<Vec<i32>>(), vec![0, 2, 6]);
+        assert_eq!(BigNum::new().unwrap().bits().count(), 0);
+
+        let mut b = BigNum::new().unwrap();
+        b.set_bit(200).unwrap();
+        assert_eq!(b.bits().collect::<Vec<i32>>(), vec![200]);
+    }
+
+    #[test]
+    fn test_word_ops() {
+        let mut a = BigNum::new_from(1000_u64).unwrap();
+        a.add_word(24).unwrap();
+        a.mul_word(3).unwrap();
+        a.sub_word(2).unwrap();
+        assert!(a == BigNum::new_from(3070_u64).unwrap());
+
+        assert_eq!(a.mod_word(7).unwrap(), 4);
+        assert_eq!(a.div_word(7).unwrap(), 4);
+        assert!(a == BigNum::new_from(438_u64).unwrap());
+        assert!(a.div_word(0).is_err());
+    }
+
+    #[test]
+    fn test_wide_words() {
+        // Larger than a BN_ULONG where it is 32 bits wide
+        let w = 0x100000003_u64;
+        let mut a = BigNum::new_from(w).unwrap();
+        assert_eq!(a.to_u64(), Some(w));
+        a.add_word(w).unwrap();
+        assert!(a.is_word(2 * w));
+        a.mul_word(w).unwrap();
+        let wide = BigNum::new_from(w).unwrap();
+        assert!(a == BigNum::new_from(2 * w).unwrap().checked_mul(&wide).unwrap());
+        a.add_word(5).unwrap();
+        assert_eq!(a.mod_word(w).unwrap(), 5);
+        assert_eq!(a.div_word(w).unwrap(), 5);
+        assert!(a.is_word(2 * w));
+        a.sub_word(w).unwrap();
+        assert!(a.is_word(w));
+    }
+
+    #[test]
+    fn test_to_u64() {
+        assert_eq!(BigNum::new().unwrap().to_u64(), Some(0));
+        assert_eq!(BigNum::new_from(438_u64).unwrap().to_u64(), Some(438));
+        assert_eq!(BigNum::new_from(0xffffffffffffffff_u64).unwrap().to_u64(),
+                   Some(0xffffffffffffffff));
+
+        let mut a = BigNum::new_from(0xffffffffffffffff_u64).unwrap();
+        a.add_word(1).unwrap();
+        assert_eq!(a.to_u64(), None);
+
+        let mut b = BigNum::new_from(5_u64).unwrap();
+        b.negate();
+        assert_eq!(b.to_u64(), None);
+    }
+
+    #[test]
+    fn test_predicates() {
+        let one = BigNum::new_from(1_u64).unwrap();
+        assert!(one.is_one());
+        assert!(one.is_odd());
+        assert!(one.is_word(1));
+
+        let mut a = BigNum::new_from(438_u64).unwrap();
+        assert!(!a.is_one());
+        assert!(!a.is_odd());
+        assert!(a.is_word(438));
+        assert!(!a.is_word(439));
+
+        a.negate();
+        assert!(!a.is_word(438));
+
+        let mut minus_one = BigNum::new_from(1_u64).unwrap();
+        minus_one.negate();
+        assert!(!minus_one.is_one());
+        assert!(minus_one.is_odd());
+    }
+
+    #[test]
+    fn test_const_time() {
+        let mut p = BigNum::new_from(65537_u64).unwrap();
+        assert!(!p.is_const_time());
+        p.set_const_time();
+        assert!(p.is_const_time());
+
+        let a = BigNum::new_from(3_u64).unwrap();
+        let m = BigNum::new_from(1001_u64).unwrap();
+        assert!(a.checked_mod_exp(&p, &m).unwrap() == a.checked_mod_exp_consttime(&p, &m).unwrap());
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut a = BigNum::new_from(0xdeadbeef_u64).unwrap();
+        a.clear();
+        assert!(a.is_word(0));
+        a.add_word(1).unwrap();
+        assert!(a.is_one());
+    }
+
+    #[test]
+    fn test_hash() {
+        use std::collections::HashSet;
+
+        let mut set = HashSet::new();
+        set.insert(BigNum::new_from(438_u64).unwrap());
+        assert!(set.contains(&BigNum::new_from_dec_str("438").unwrap()));
+        assert!(!set.contains(&BigNum::new_from(439_u64).unwrap()));
+
+        let mut neg = BigNum::new_from(438_u64).unwrap();
+        neg.negate();
+        assert!(!set.contains(&neg));
+    }
+
+    #[test]
+    fn test_from_str() {
+        let a = BigNum::new_from_dec_str("-123456789012345678901234567890").unwrap();
+        assert_eq!(a.to_dec_str().as_slice(), "-123456789012345678901234567890");
+
+        let b = BigNum::new_from_hex_str("fF00").unwrap();
+        assert!(b == BigNum::new_from(0xff00_u64).unwrap());
+
+        assert!(BigNum::new_from_dec_str("").is_err());
+        assert!(BigNum::new_from_dec_str("12a").is_err());
+        assert!(BigNum::new_from_hex_str("0x12").is_err());
+        assert!(BigNum::new_from_hex_str("-").is_err());
+    }
+
+    #[test]
+    fn test_hex() {
+        let a = BigNum::new_from(0xabc_u64).unwrap();
+        assert_eq!(a.to_hex_str().as_slice(), "0ABC");
+        assert_eq!(format!("{:x}", a).as_slice(), "abc");
+        assert_eq!(format!("{:X}", a).as_slice(), "ABC");
+        assert_eq!(format!("{:#08x}", a).as_slice(), "0x000abc");
+
+        assert_eq!(format!("{:x}", -a).as_slice(), "-abc");
+        assert_eq!(format!("{:x}", BigNum::new_from(0_u64).unwrap()).as_slice(), "0");
+    }
+
+    #[test]
+    fn test_from_str_traits() {
+        use std::num::FromStrRadix;
+
+        let a: BigNum = from_str("1234").unwrap();
+        assert!(a == BigNum::new_from(1234_u64).unwrap());
+        let b: BigNum = FromStrRadix::from_str_radix("4d2", 16).unwrap();
+        assert!(a == b);
+
+        assert!(from_str::<BigNum>("12 34").is_none());
+        let c: Option<BigNum> = FromStrRadix::from_str_radix("1234", 8);
+        assert!(c.is_none());
+    }
+
+    #[test]
+    fn test_to_vec_padded() {
+        let a = BigNum::new_from(0x1234_u64).unwrap();
+        assert_eq!(a.to_vec_padded(4).unwrap(), vec![0, 0, 0x12, 0x34]);
+        assert_eq!(a.to_vec_padded(2).unwrap(), vec![0x12, 0x34]);
+        assert!(a.to_vec_padded(1).is_err());
+        assert_eq!(BigNum::new_from(0_u64).unwrap().to_vec_padded(2).unwrap(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_little_endian() {
+        let a = BigNum::new_from(0x123456_u64).unwrap();
+        assert_eq!(a.to_vec_le(), vec![0x56, 0x34, 0x12]);
+        assert_eq!(a.to_vec_padded_le(5).unwrap(), vec![0x56, 0x34, 0x12, 0, 0]);
+        assert!(a.to_vec_padded_le(2).is_err());
+
+        let b = BigNum::new_from_slice_le(&[0x56, 0x34, 0x12, 0]).unwrap();
+        assert!(a == b);
+    }
+
+    #[test]
+    fn test_word_operators() {
+        let a = BigNum::new_from(1000_u64).unwrap();
+        assert!(a + 24 == 1024);
+        assert!(a - 1 == 999);
+        assert!(a * 3 == 3000);
+
+        assert!(a > 999);
+        assert!(a < 1001);
+        assert!(-a < 0);
+        assert!(a * 0xffffffffffffffff > 0xffffffffffffffff);
+
+        // Above u32::MAX, which doesn't fit a 32-bit BN_ULONG
+        let w = 0x100000000_u64;
+        assert!(a + w == w + 1000);
+        assert!(a - w == -(BigNum::new_from(w - 1000).unwrap()));
+        assert!(a * w == 1000 * w);
+        assert!(a < w);
+        assert!(a + w > w);
+        assert!(a * w != 0);
+    }
+
+    #[test]
+    fn test_asn1_integer() {
+        let a = BigNum::new_from_hex_str("123456789abcdef0123456789abcdef").unwrap();
+        let ai = a.to_asn1_integer().unwrap();
+        assert!(BigNum::from_asn1_integer(&ai).unwrap() == a);
+
+        let mut b = BigNum::new_from(42_u64).unwrap();
+        b.negate();
+        let ai = b.to_asn1_integer().unwrap();
+        assert!(BigNum::from_asn1_integer(&ai).unwrap() == b);
+    }
+
+    #[test]
+    fn test_mpi() {
+        let a = BigNum::new_from(0x80_u64).unwrap();
+        assert_eq!(a.to_mpi(), vec![0, 0, 0, 2, 0, 0x80]);
+        assert_eq!((-a).to_mpi(), vec![0, 0, 0, 2, 0x80, 0x80]);
+        assert!(BigNum::new_from_mpi((-a).to_mpi().as_slice()).unwrap() == -a);
+
+        assert!(BigNum::new_from_mpi([0, 0, 0, 3, 1]).is_err());
+    }
+
+    #[test]
+    fn test_kronecker() {
+        let n = BigNum::new_from(11_u64).unwrap();
+        // 3 = 5^2 mod 11 is a square, 2 is not
+        assert_eq!(BigNum::new_from(3_u64).unwrap().kronecker(&n).unwrap(), 1);
+        assert_eq!(BigNum::new_from(2_u64).unwrap().kronecker(&n).unwrap(), -1);
+        assert_eq!(BigNum::new_from(22_u64).unwrap().kronecker(&n).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_nist_mod() {
+        let p = NistP256.prime();
+        assert_eq!(p.num_bits(), 256);
+
+        let a = BigNum::new_from_hex_str("fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210").unwrap();
+        let expected = a.as_ref().checked_nnmod(&p).unwrap();
+        assert!(a.checked_nist_mod(NistP256).unwrap() == expected);
+        assert!(a.checked_nist_mod(NistP384).unwrap() == a);
+        let mut ctx = BnCtx::new().unwrap();
+        assert!(a.checked_nist_mod_with_ctx(NistP256, &mut ctx).unwrap() == expected);
+
+        let mut q = p.to_owned().unwrap();
+        q.clear();
+        assert_eq!(NistP256.prime().num_bits(), 256);
+    }
+
+    #[test]
+    fn test_copy_swap() {
+        let mut a = BigNum::new_from(1_u64).unwrap();
+        let mut b = BigNum::new_from(2_u64).unwrap();
+
+        a.swap(&mut b);
+        assert!(a == BigNum::new_from(2_u64).unwrap());
+        assert!(b == BigNum::new_from(1_u64).unwrap());
+
+        a.copy_from(&b).unwrap();
+        assert!(a == b);
+    }
 
     #[test]
     fn test_prime_numbers() {
@@ -528,4 +2295,29 @@ mod tests {
         assert!(p.is_prime(100).unwrap());
         assert!(p.is_prime_fast(100, true).unwrap());
     }
+
+    #[test]
+    fn test_primes() {
+        let start = BigNum::new_from(0_u64).unwrap();
+        let primes: Vec<u64> = start.primes(5).unwrap().map(|p| p.to_u64().unwrap()).collect();
+        assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31]);
+
+        let start = BigNum::new_from(90_u64).unwrap();
+        let primes: Vec<u64> = start.primes(32).unwrap().take(3).map(|p| p.to_u64().unwrap()).collect();
+        assert_eq!(primes, vec![97, 101, 103]);
+    }
+
+    #[test]
+    fn test_generate_prime_progress() {
+        let mut found = 0u;
+        let p = BigNum::checked_generate_prime_with_progress(128, false, None, None, Some(|p, _| {
+            if p == 0 { found += 1; }
+            true
+        })).unwrap();
+        assert!(p.is_prime(100).unwrap());
+        assert!(found > 0);
+
+        assert!(BigNum::checked_generate_prime_with_progress(128, false, None, None,
+                                                             Some(|_, _| false)).is_err());
+    }
 }
@@ -0,0 +1,156 @@
+use libc::{c_char, c_long, c_ulong};
+use std::c_str::CString;
+use std::ptr;
+
+use ffi;
+use ssl::error::{SslError};
+
+/// Makes missing configuration files a no-op rather than an error
+pub static IGNORE_MISSING_FILE: c_ulong = ffi::CONF_MFLAGS_IGNORE_MISSING_FILE;
+
+/// A parsed OpenSSL configuration file, in the `openssl.cnf` format
+pub struct Conf {
+    conf: *mut ffi::CONF
+}
+
+impl Drop for Conf {
+    fn drop(&mut self) {
+        unsafe { ffi::NCONF_free(self.conf) }
+    }
+}
+
+impl Conf {
+    /// Parses the configuration file at `path`
+    pub fn load(path: &Path) -> Result<Conf, SslError> {
+        ffi::init();
+        let conf = unsafe { ffi::NCONF_new(ptr::null_mut()) };
+        try_ssl_null!(conf);
+        let conf = Conf { conf: conf };
+
+        let mut eline: c_long = 0;
+        try_ssl!(path.with_c_str(|path| unsafe {
+            ffi::NCONF_load(conf.conf, path, &mut eline)
+        }));
+
+        Ok(conf)
+    }
+
+    /// Looks up `name` in `section`, or in the default section if `section`
+    /// is `None`
+    pub fn get_string(&self, section: Option<&str>, name: &str) -> Option<String> {
+        let value = name.with_c_str(|name| unsafe {
+            match section {
+                Some(section) => section.with_c_str(|section| {
+                    ffi::NCONF_get_string(self.conf, section, name)
+                }),
+                None => ffi::NCONF_get_string(self.conf, ptr::null(), name)
+            }
+        });
+
+        if value.is_null() {
+            // A missing value leaves an error behind which must not be
+            // attributed to a later, unrelated call
+            unsafe { ffi::ERR_clear_error() };
+            None
+        } else {
+            Some(unsafe { CString::new(value, false) }.to_string())
+        }
+    }
+
+    /// Configures the library's modules (engines, algorithm defaults, ...)
+    /// from this file.
+    ///
+    /// `appname` names the entry in the default section pointing to the
+    /// section holding the module configuration. If it is `None`, the
+    /// standard `openssl_conf` entry is used.
+    pub fn apply(&self, appname: Option<&str>, flags: c_ulong) -> Result<(), SslError> {
+        load_builtin_modules();
+        let ret = with_opt_c_str(appname, |appname| unsafe {
+            ffi::CONF_modules_load(self.conf, appname, flags)
+        });
+        lift_ssl_if!(ret <= 0)
+    }
+}
+
+/// Loads the configuration file at `path` and applies the module
+/// configuration selected by `appname`.
+///
+/// This is the equivalent of what the `openssl` command line tool does at
+/// startup. See `Conf::apply` for the meaning of `appname`.
+pub fn load_file(path: &Path, appname: Option<&str>, flags: c_ulong) -> Result<(), SslError> {
+    ffi::init();
+    load_builtin_modules();
+    let ret = path.with_c_str(|path| {
+        with_opt_c_str(appname, |appname| unsafe {
+            ffi::CONF_modules_load_file(path, appname, flags)
+        })
+    });
+    lift_ssl_if!(ret <= 0)
+}
+
+/// Applies the module configuration in OpenSSL's default configuration
+/// file, doing nothing if the file doesn't exist.
+pub fn load_default_file() -> Result<(), SslError> {
+    ffi::init();
+    load_builtin_modules();
+    let ret = unsafe {
+        ffi::CONF_modules_load_file(ptr::null(), ptr::null(), IGNORE_MISSING_FILE)
+    };
+    lift_ssl_if!(ret <= 0)
+}
+
+fn load_builtin_modules() {
+    unsafe {
+        ffi::OPENSSL_load_builtin_modules();
+        ffi::ENGINE_load_builtin_engines();
+    }
+}
+
+fn with_opt_c_str<T>(s: Option<&str>, f: |*const c_char| -> T) -> T {
+    match s {
+        Some(s) => s.with_c_str(|s| f(s)),
+        None => f(ptr::null())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, Writer, fs};
+
+    use super::{Conf, IGNORE_MISSING_FILE, load_file, load_default_file};
+
+    #[test]
+    fn test_get_string() {
+        let path = Path::new("conf_get_string.cnf");
+        File::create(&path).write(b"top = level\n\n[section]\nkey = value\n").unwrap();
+
+        let conf = Conf::load(&path).unwrap();
+        assert_eq!(conf.get_string(None, "top"), Some("level".to_string()));
+        assert_eq!(conf.get_string(Some("section"), "key"), Some("value".to_string()));
+        assert_eq!(conf.get_string(Some("section"), "missing"), None);
+
+        let _ = fs::unlink(&path);
+    }
+
+    #[test]
+    fn test_apply_empty() {
+        let path = Path::new("conf_apply_empty.cnf");
+        File::create(&path).write(b"app_conf = app_section\n\n[app_section]\n").unwrap();
+
+        let conf = Conf::load(&path).unwrap();
+        conf.apply(Some("app_conf"), 0).unwrap();
+
+        let _ = fs::unlink(&path);
+    }
+
+    #[test]
+    fn test_load_missing() {
+        assert!(Conf::load(&Path::new("test/missing.cnf")).is_err());
+        load_file(&Path::new("test/missing.cnf"), None, IGNORE_MISSING_FILE).unwrap();
+    }
+
+    #[test]
+    fn test_load_default_file() {
+        load_default_file().unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+use ffi;
+use ssl::error::SslError;
+
+/// Enables or disables FIPS mode.
+///
+/// Enabling FIPS mode runs the FIPS module's power-on self tests, and fails if
+/// they do not pass or the library was not built against a FIPS capable
+/// OpenSSL. While enabled, only FIPS approved algorithms are available.
+pub fn enable(enabled: bool) -> Result<(), SslError> {
+    unsafe {
+        try_ssl!(ffi::FIPS_mode_set(enabled as ::libc::c_int));
+    }
+    Ok(())
+}
+
+/// Returns `true` if FIPS mode is currently enabled.
+pub fn is_enabled() -> bool {
+    unsafe { ffi::FIPS_mode() != 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enable, is_enabled};
+
+    #[test]
+    fn test_disable() {
+        enable(false).unwrap();
+        assert!(!is_enabled());
+    }
+}
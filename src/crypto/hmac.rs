@@ -17,6 +17,7 @@
 use libc::{c_int, c_uint};
 
 use crypto::hash;
+use crypto::memcmp;
 use ffi;
 
 pub struct HMAC {
@@ -26,6 +27,7 @@ pub struct HMAC {
 
 #[allow(non_snake_case)]
 pub fn HMAC(ht: hash::HashType, key: &[u8]) -> HMAC {
+    ffi::init();
     unsafe {
         let (evp, mdlen) = hash::evpmd(ht);
 
@@ -57,6 +59,15 @@ impl HMAC {
             res
         }
     }
+
+    /// Finishes the computation and checks the result against `mac`.
+    ///
+    /// The comparison takes constant time, so it does not leak how much of
+    /// `mac` was correct.
+    pub fn verify(&mut self, mac: &[u8]) -> bool {
+        let res = self.final();
+        res.len() == mac.len() && memcmp::eq(res.as_slice(), mac)
+    }
 }
 
 #[cfg(test)]
@@ -131,6 +142,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hmac_verify() {
+        let mac = "9294727a3638bb1c13f48ef8158bfc9d".from_hex().unwrap();
+
+        let mut hmac = HMAC(MD5, Vec::from_elem(16, 0x0b_u8).as_slice());
+        hmac.update(b"Hi There");
+        assert!(hmac.verify(mac.as_slice()));
+
+        let mut hmac = HMAC(MD5, Vec::from_elem(16, 0x0b_u8).as_slice());
+        hmac.update(b"Hi there");
+        assert!(!hmac.verify(mac.as_slice()));
+
+        let mut hmac = HMAC(MD5, Vec::from_elem(16, 0x0b_u8).as_slice());
+        hmac.update(b"Hi There");
+        assert!(!hmac.verify(mac.slice_to(8)));
+    }
+
     fn test_sha2(ty: HashType, results: &[Vec<u8>]) {
         // test vectors from RFC 4231
         let tests: [(Vec<u8>, Vec<u8>), ..6] = [
@@ -0,0 +1,77 @@
+//! OpenSSL memory debugging.
+//!
+//! When enabled, libcrypto records every allocation it makes so that objects
+//! which were never freed can be listed later, e.g. at the end of a test:
+//!
+//! ```no_run
+//! use openssl::crypto::memdbg;
+//!
+//! // Must happen before anything else in the process uses OpenSSL
+//! assert!(memdbg::enable());
+//!
+//! // ... exercise the code under test ...
+//!
+//! match memdbg::leaks().unwrap() {
+//!     None => {}
+//!     Some(report) => fail!("OpenSSL objects leaked:\n{}", report)
+//! }
+//! ```
+
+use bio::MemBio;
+use ffi;
+use ssl::error::{SslError, StreamError};
+
+/// Starts recording OpenSSL allocations.
+///
+/// The debugging allocator can only be installed before OpenSSL has allocated
+/// any memory, so this must be called before any other use of the library.
+/// Returns `false` if it was called too late.
+pub fn enable() -> bool {
+    unsafe {
+        let ok = ffi::CRYPTO_set_mem_debug_functions(Some(ffi::CRYPTO_dbg_malloc),
+                                                     Some(ffi::CRYPTO_dbg_realloc),
+                                                     Some(ffi::CRYPTO_dbg_free),
+                                                     Some(ffi::CRYPTO_dbg_set_options),
+                                                     Some(ffi::CRYPTO_dbg_get_options));
+        if ok == 0 {
+            return false;
+        }
+        ffi::CRYPTO_mem_ctrl(ffi::CRYPTO_MEM_CHECK_ON);
+    }
+    true
+}
+
+/// Stops recording OpenSSL allocations.
+pub fn disable() {
+    unsafe { ffi::CRYPTO_mem_ctrl(ffi::CRYPTO_MEM_CHECK_OFF); }
+}
+
+/// Returns a report of the allocations made since `enable` was called which
+/// have not yet been freed, or `None` if there are none.
+pub fn leaks() -> Result<Option<String>, SslError> {
+    // Keep the report's own buffer out of the report
+    unsafe { ffi::CRYPTO_mem_ctrl(ffi::CRYPTO_MEM_CHECK_DISABLE); }
+    let bio = MemBio::new();
+    unsafe { ffi::CRYPTO_mem_ctrl(ffi::CRYPTO_MEM_CHECK_ENABLE); }
+    let mut bio = try!(bio);
+
+    unsafe { ffi::CRYPTO_mem_leaks(bio.get_handle()); }
+
+    let buf = try!(bio.read_to_end().map_err(StreamError));
+    if buf.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(buf.as_slice()).into_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::leaks;
+
+    #[test]
+    fn test_leaks() {
+        // Other tests have already used OpenSSL, so this can only check that
+        // producing the report works
+        leaks().unwrap();
+    }
+}
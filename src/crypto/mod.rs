@@ -15,10 +15,14 @@
  * limitations under the License.
  */
 
+#[cfg(feature = "fips")]
+pub mod fips;
 pub mod hash;
 pub mod hmac;
 pub mod pkcs5;
 pub mod pkey;
 pub mod rand;
+pub mod secret;
 pub mod symm;
 pub mod memcmp;
+pub mod memdbg;
@@ -3,11 +3,13 @@ use std::mem;
 use std::ptr;
 use bio::{MemBio};
 use crypto::hash::{HashType, MD5, SHA1, SHA224, SHA256, SHA384, SHA512, RIPEMD160};
+use crypto::secret::SecretBytes;
 use ffi;
 use ssl::error::{SslError, StreamError};
 
 
-enum Parts {
+/// The parts of a key pair a `PKey` holds.
+pub enum Parts {
     Neither,
     Public,
     Both
@@ -54,6 +56,7 @@ pub struct PKey {
 /// Represents a public key, optionally with a private key attached.
 impl PKey {
     pub fn new() -> PKey {
+        ffi::init();
         unsafe {
             PKey {
                 evp: ffi::EVP_PKEY_new(),
@@ -62,6 +65,14 @@ impl PKey {
         }
     }
 
+    /// Wraps an existing EVP_PKEY and takes ownership
+    pub fn from_handle(handle: *mut ffi::EVP_PKEY, parts: Parts) -> PKey {
+        PKey {
+            evp: handle,
+            parts: parts
+        }
+    }
+
     fn _tostr(&self, f: unsafe extern "C" fn(*mut ffi::RSA, *const *mut u8) -> c_int) -> Vec<u8> {
         unsafe {
             let rsa = ffi::EVP_PKEY_get1_RSA(self.evp);
@@ -122,8 +133,8 @@ impl PKey {
      * Returns a serialized form of the public and private keys, suitable for
      * load_priv().
      */
-    pub fn save_priv(&self) -> Vec<u8> {
-        self._tostr(ffi::i2d_RSAPrivateKey)
+    pub fn save_priv(&self) -> SecretBytes {
+        SecretBytes::from_vec(self._tostr(ffi::i2d_RSAPrivateKey))
     }
     /**
      * Loads a serialized form of the public and private keys, as produced by
@@ -1,7 +1,16 @@
-use libc::c_int;
+use libc::{c_int, c_long, c_ulong, c_void, c_double};
+use std::kinds::marker;
+use std::mem;
+use std::rand::Rng;
+use std::rt::mutex::{StaticNativeMutex, NATIVE_MUTEX_INIT};
+use std::slice;
+
+use crypto::hash::{hash, SHA256};
 use ffi;
+use ssl::error::SslError;
 
 pub fn rand_bytes(len: uint) -> Vec<u8> {
+    ffi::init();
     unsafe {
         let mut out = Vec::with_capacity(len);
 
@@ -14,13 +23,391 @@ pub fn rand_bytes(len: uint) -> Vec<u8> {
     }
 }
 
+/// A random number generator backed by OpenSSL's PRNG
+///
+/// This allows OpenSSL to be used as the source of randomness for code
+/// written against the standard `Rng` trait.
+///
+/// # Failure
+///
+/// Generating values fails the current task if OpenSSL is unable to produce
+/// random output, e.g. because the PRNG has not been seeded.
+///
+/// # Example
+///
+/// ```
+/// use std::rand::Rng;
+///
+/// use openssl::crypto::rand::OpenSslRng;
+///
+/// let mut rng = OpenSslRng::new();
+/// let mut cards = Vec::from_fn(52, |i| i);
+/// rng.shuffle(cards.as_mut_slice());
+/// let nonce: u64 = rng.gen();
+/// ```
+pub struct OpenSslRng;
+
+impl OpenSslRng {
+    pub fn new() -> OpenSslRng {
+        ffi::init();
+        OpenSslRng
+    }
+}
+
+impl Rng for OpenSslRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8, ..4];
+        self.fill_bytes(buf);
+        unsafe { mem::transmute(buf) }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8, ..8];
+        self.fill_bytes(buf);
+        unsafe { mem::transmute(buf) }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let r = unsafe { ffi::RAND_bytes(dest.as_mut_ptr(), dest.len() as c_int) };
+        if r != 1 { fail!("RAND_bytes failed: {}", SslError::get()) }
+    }
+}
+
+/// Mixes `buf` into the PRNG state, assuming it is entirely random.
+pub fn seed(buf: &[u8]) {
+    ffi::init();
+    unsafe {
+        ffi::RAND_seed(buf.as_ptr() as *const c_void, buf.len() as c_int)
+    }
+}
+
+/// Mixes `buf` into the PRNG state, crediting it with `entropy` bytes of
+/// randomness.
+///
+/// Use this for sources which are only partially unpredictable, such as
+/// timings of external events.
+pub fn add(buf: &[u8], entropy: f64) {
+    ffi::init();
+    unsafe {
+        ffi::RAND_add(buf.as_ptr() as *const c_void, buf.len() as c_int,
+                      entropy as c_double)
+    }
+}
+
+/// Mixes up to `max_bytes` bytes read from the file at `path` into the PRNG
+/// state, returning the number of bytes read.
+///
+/// If `max_bytes` is `None` the whole file is read, which must not be done
+/// for devices such as `/dev/hwrng` that never reach end of file.
+pub fn load_file(path: &Path, max_bytes: Option<uint>) -> Result<uint, SslError> {
+    ffi::init();
+    let max_bytes = match max_bytes {
+        Some(max_bytes) => max_bytes as c_long,
+        None => -1
+    };
+    let ret = path.with_c_str(|path| unsafe { ffi::RAND_load_file(path, max_bytes) });
+    if ret < 0 {
+        Err(SslError::get())
+    } else {
+        Ok(ret as uint)
+    }
+}
+
+/// Returns `true` if the PRNG has been seeded with enough entropy to produce
+/// unpredictable output.
+///
+/// Keys should not be generated while this returns `false`.
+pub fn status() -> bool {
+    ffi::init();
+    unsafe { ffi::RAND_status() == 1 }
+}
+
+/// Replaces OpenSSL's PRNG with a deterministic generator seeded from `seed`
+/// until the returned guard is dropped.
+///
+/// Everything on the current thread which draws randomness from OpenSSL,
+/// including key generation and the choice of signature nonces, produces the
+/// same output for the same seed while the guard is alive. Calls from other
+/// threads are forwarded to the previous generator. This is intended for
+/// reproducible tests only: the output is completely predictable.
+///
+/// Guards can be nested on the same thread, with the innermost one in
+/// effect. Only one thread can install deterministic generators at a time;
+/// this blocks until every guard held by another thread has been dropped.
+///
+/// # Example
+///
+/// ```
+/// use openssl::crypto::rand::{deterministic, rand_bytes};
+///
+/// let a = {
+///     let _rng = deterministic(b"test seed");
+///     rand_bytes(16)
+/// };
+/// let b = {
+///     let _rng = deterministic(b"test seed");
+///     rand_bytes(16)
+/// };
+/// assert_eq!(a, b);
+/// ```
+pub fn deterministic(seed: &[u8]) -> DeterministicRand {
+    ffi::init();
+    unsafe {
+        let owner = ffi::current_thread_id();
+
+        STATE_LOCK.lock_noguard();
+        while !STATE.is_null() && (*STATE).owner != owner {
+            STATE_LOCK.wait_noguard();
+        }
+
+        let outer = STATE;
+        if outer.is_null() {
+            PREV = ffi::RAND_get_rand_method();
+            ffi::RAND_set_rand_method(&DETERMINISTIC_METHOD);
+        }
+        let state = box DeterministicState {
+            owner: owner,
+            outer: outer,
+            seed: seed.to_vec(),
+            counter: 0,
+            buf: vec![]
+        };
+        STATE = mem::transmute(state);
+        let guard = DeterministicRand { state: STATE, marker: marker::NoSend };
+        STATE_LOCK.unlock_noguard();
+        guard
+    }
+}
+
+/// Removes a deterministic generator when dropped, restoring OpenSSL's
+/// previous PRNG once the outermost one is gone
+///
+/// The guard must be dropped on the thread which created it.
+pub struct DeterministicRand {
+    state: *mut DeterministicState,
+    marker: marker::NoSend
+}
+
+impl Drop for DeterministicRand {
+    fn drop(&mut self) {
+        unsafe {
+            STATE_LOCK.lock_noguard();
+
+            // Nested guards are normally dropped innermost first, but unlink
+            // this one from wherever it is in the chain in case they weren't
+            let mut link: *mut *mut DeterministicState = &mut STATE;
+            while *link != self.state {
+                link = &mut (**link).outer;
+            }
+            *link = (*self.state).outer;
+            let _: Box<DeterministicState> = mem::transmute(self.state);
+
+            if STATE.is_null() {
+                ffi::RAND_set_rand_method(PREV);
+                STATE_LOCK.signal_noguard();
+            }
+            STATE_LOCK.unlock_noguard();
+        }
+    }
+}
+
+// Output is SHA-256(seed || counter) for successive big endian counters
+struct DeterministicState {
+    owner: c_ulong,
+    // The generator this one is nested inside, if any
+    outer: *mut DeterministicState,
+    seed: Vec<u8>,
+    counter: u64,
+    buf: Vec<u8>
+}
+
+impl DeterministicState {
+    fn fill(&mut self, out: &mut [u8]) {
+        for b in out.iter_mut() {
+            if self.buf.is_empty() {
+                let mut block = self.seed.clone();
+                for i in range(0u, 8) {
+                    block.push((self.counter >> (56 - 8 * i)) as u8);
+                }
+                self.buf = hash(SHA256, block.as_slice());
+                self.buf.reverse();
+                self.counter += 1;
+            }
+            *b = self.buf.pop().unwrap();
+        }
+    }
+}
+
+// Protects STATE and PREV, and is signalled when the last guard is dropped
+static mut STATE_LOCK: StaticNativeMutex = NATIVE_MUTEX_INIT;
+// The innermost generator, or null if none is installed
+static mut STATE: *mut DeterministicState = 0 as *mut DeterministicState;
+// The method in use before the outermost generator was installed
+static mut PREV: *const ffi::RAND_METHOD = 0 as *const ffi::RAND_METHOD;
+
+static DETERMINISTIC_METHOD: ffi::RAND_METHOD = ffi::RAND_METHOD {
+    seed: Some(deterministic_seed),
+    bytes: Some(deterministic_bytes),
+    cleanup: Some(deterministic_cleanup),
+    add: Some(deterministic_add),
+    pseudorand: Some(deterministic_bytes),
+    status: Some(deterministic_status)
+};
+
+// Calls `f` with the innermost generator if it belongs to the calling
+// thread, or returns the method which other threads' calls should be
+// forwarded to
+unsafe fn with_state<T>(f: |&mut DeterministicState| -> T)
+                        -> Result<T, *const ffi::RAND_METHOD> {
+    STATE_LOCK.lock_noguard();
+    let r = if !STATE.is_null() && (*STATE).owner == ffi::current_thread_id() {
+        Ok(f(&mut *STATE))
+    } else {
+        Err(PREV)
+    };
+    STATE_LOCK.unlock_noguard();
+    r
+}
+
+// Seeding material is ignored so output depends only on the initial seed
+extern fn deterministic_seed(buf: *const c_void, num: c_int) {
+    unsafe {
+        match with_state(|_| ()) {
+            Ok(()) => {}
+            Err(prev) => match (*prev).seed {
+                Some(seed) => seed(buf, num),
+                None => {}
+            }
+        }
+    }
+}
+
+extern fn deterministic_add(buf: *const c_void, num: c_int, entropy: c_double) {
+    unsafe {
+        match with_state(|_| ()) {
+            Ok(()) => {}
+            Err(prev) => match (*prev).add {
+                Some(add) => add(buf, num, entropy),
+                None => {}
+            }
+        }
+    }
+}
+
+extern fn deterministic_cleanup() {}
+
+extern fn deterministic_status() -> c_int {
+    unsafe {
+        match with_state(|_| ()) {
+            Ok(()) => 1,
+            Err(prev) => match (*prev).status {
+                Some(status) => status(),
+                None => 0
+            }
+        }
+    }
+}
+
+extern fn deterministic_bytes(buf: *mut u8, num: c_int) -> c_int {
+    unsafe {
+        let res = with_state(|state| {
+            slice::raw::mut_buf_as_slice(buf, num as uint, |out| state.fill(out))
+        });
+        match res {
+            Ok(()) => 1,
+            Err(prev) => match (*prev).bytes {
+                Some(bytes) => bytes(buf, num),
+                None => 0
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::rand_bytes;
+    use std::rand::Rng;
+
+    use super::{rand_bytes, seed, add, status, deterministic, OpenSslRng};
 
     #[test]
     fn test_rand_bytes() {
         let bytes = rand_bytes(32u);
         println!("{}", bytes);
     }
+
+    #[test]
+    fn test_seed() {
+        seed(b"some application entropy");
+        add(b"a less random event", 1.5);
+        assert!(status());
+    }
+
+    #[test]
+    fn test_rng() {
+        let mut rng = OpenSslRng::new();
+        let mut values = Vec::from_fn(32, |i| i);
+        rng.shuffle(values.as_mut_slice());
+        values.sort();
+        assert_eq!(values, Vec::from_fn(32, |i| i));
+
+        let a: u64 = rng.gen();
+        let b: u64 = rng.gen();
+        assert!(a != b);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let (a, b) = {
+            let _rng = deterministic(b"seed");
+            (rand_bytes(40), rand_bytes(8))
+        };
+        let c = {
+            let _rng = deterministic(b"seed");
+            rand_bytes(48)
+        };
+        assert_eq!(a.as_slice(), c.slice_to(40));
+        assert_eq!(b.as_slice(), c.slice_from(40));
+
+        let d = {
+            let _rng = deterministic(b"other seed");
+            rand_bytes(48)
+        };
+        assert!(c != d);
+    }
+
+    #[test]
+    fn test_deterministic_nested() {
+        let outer = {
+            let _rng = deterministic(b"outer");
+            rand_bytes(32)
+        };
+        let inner = {
+            let _rng = deterministic(b"inner");
+            rand_bytes(16)
+        };
+
+        let _outer = deterministic(b"outer");
+        assert_eq!(rand_bytes(16).as_slice(), outer.slice_to(16));
+        {
+            let _inner = deterministic(b"inner");
+            assert_eq!(rand_bytes(16), inner);
+        }
+        assert_eq!(rand_bytes(16).as_slice(), outer.slice_from(16));
+    }
+
+    #[test]
+    fn test_deterministic_other_thread() {
+        let expected = {
+            let _rng = deterministic(b"seed");
+            rand_bytes(16)
+        };
+
+        let _rng = deterministic(b"seed");
+        let (tx, rx) = channel();
+        spawn(proc() {
+            tx.send(rand_bytes(16));
+        });
+        assert!(rx.recv() != expected);
+        assert_eq!(rand_bytes(16), expected);
+    }
 }
@@ -0,0 +1,118 @@
+use libc::{c_void, size_t};
+use std::fmt;
+
+use crypto::memcmp;
+use ffi;
+
+/// A buffer holding secret data, such as private key material, passwords or
+/// derived keys.
+///
+/// The contents are overwritten with `OPENSSL_cleanse` when the buffer is
+/// dropped, comparisons take constant time, and the contents are never
+/// printed.
+pub struct SecretBytes {
+    buf: Vec<u8>
+}
+
+impl SecretBytes {
+    /// Creates a zero-filled buffer of `len` bytes
+    pub fn new(len: uint) -> SecretBytes {
+        SecretBytes::from_vec(Vec::from_elem(len, 0u8))
+    }
+
+    /// Takes ownership of an existing buffer.
+    ///
+    /// Copies of the data made before this call are not affected.
+    pub fn from_vec(buf: Vec<u8>) -> SecretBytes {
+        SecretBytes { buf: buf }
+    }
+
+    /// Returns the number of bytes held
+    pub fn len(&self) -> uint {
+        self.buf.len()
+    }
+
+    /// Returns the secret as a slice
+    pub fn as_slice<'a>(&'a self) -> &'a [u8] {
+        self.buf.as_slice()
+    }
+
+    /// Returns the secret as a mutable slice
+    pub fn as_mut_slice<'a>(&'a mut self) -> &'a mut [u8] {
+        self.buf.as_mut_slice()
+    }
+
+    /// Shortens the buffer to `len` bytes, wiping the discarded tail.
+    pub fn truncate(&mut self, len: uint) {
+        if len < self.buf.len() {
+            cleanse(self.buf.slice_from_mut(len));
+            self.buf.truncate(len);
+        }
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        cleanse(self.buf.as_mut_slice());
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> SecretBytes {
+        SecretBytes::from_vec(self.buf.clone())
+    }
+}
+
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &SecretBytes) -> bool {
+        self.len() == other.len() && memcmp::eq(self.as_slice(), other.as_slice())
+    }
+}
+
+impl Eq for SecretBytes {}
+
+impl fmt::Show for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretBytes({} bytes)", self.len())
+    }
+}
+
+/// Overwrites `buf` with zeroes in a way the compiler will not optimize away.
+pub fn cleanse(buf: &mut [u8]) {
+    unsafe {
+        ffi::OPENSSL_cleanse(buf.as_mut_ptr() as *mut c_void, buf.len() as size_t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SecretBytes, cleanse};
+
+    #[test]
+    fn test_cleanse() {
+        let mut buf = [1u8, 2, 3, 4];
+        cleanse(buf);
+        assert_eq!(buf.as_slice(), [0u8, 0, 0, 0].as_slice());
+    }
+
+    #[test]
+    fn test_eq() {
+        let a = SecretBytes::from_vec(vec!(1u8, 2, 3));
+        assert_eq!(a, a.clone());
+        assert!(a != SecretBytes::from_vec(vec!(1u8, 2, 4)));
+        assert!(a != SecretBytes::from_vec(vec!(1u8, 2)));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut a = SecretBytes::from_vec(vec!(1u8, 2, 3));
+        a.truncate(1);
+        assert_eq!(a.as_slice(), [1u8].as_slice());
+    }
+
+    #[test]
+    fn test_show_redacts() {
+        let a = SecretBytes::from_vec(vec!(0xaau8, 0xbb));
+        assert_eq!(format!("{}", a).as_slice(), "SecretBytes(2 bytes)");
+    }
+}
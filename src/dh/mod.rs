@@ -0,0 +1,163 @@
+use libc::{c_int, c_long};
+use std::io::{Reader, Writer};
+use std::ptr;
+
+use bio::{MemBio};
+use bn::with_gencb;
+use ffi;
+use ssl::error::{SslError, StreamError};
+
+pub use bn::GenerateCallback;
+
+/// Diffie-Hellman parameters, as produced by `openssl dhparam`
+pub struct Dh {
+    dh: *mut ffi::DH
+}
+
+impl Drop for Dh {
+    fn drop(&mut self) {
+        unsafe { ffi::DH_free(self.dh) }
+    }
+}
+
+impl Dh {
+    /// Generates new parameters with a safe prime of `prime_len` bits.
+    ///
+    /// `generator` is usually 2 or 5. This can take a long time for large
+    /// primes; `progress`, if given, is called periodically and may cancel
+    /// generation.
+    pub fn generate(prime_len: uint, generator: uint,
+                    progress: Option<GenerateCallback>) -> Result<Dh, SslError> {
+        ffi::init();
+
+        let dh = unsafe { ffi::DH_new() };
+        try_ssl_null!(dh);
+        let dh = Dh { dh: dh };
+
+        let ret = with_gencb(progress, |cb| unsafe {
+            ffi::DH_generate_parameters_ex(dh.dh, prime_len as c_int,
+                                           generator as c_int, cb)
+        });
+        try_ssl!(ret);
+
+        Ok(dh)
+    }
+
+    /// Reads PEM encoded parameters (`BEGIN DH PARAMETERS`)
+    pub fn from_pem(reader: &mut Reader) -> Result<Dh, SslError> {
+        ffi::init();
+
+        let mut mem_bio = try!(MemBio::new());
+        let buf = try!(reader.read_to_end().map_err(StreamError));
+        try!(mem_bio.write(buf.as_slice()).map_err(StreamError));
+
+        let dh = unsafe {
+            ffi::PEM_read_bio_DHparams(mem_bio.get_handle(), ptr::null_mut(),
+                                       None, ptr::null_mut())
+        };
+        try_ssl_null!(dh);
+
+        Ok(Dh { dh: dh })
+    }
+
+    /// Reads DER encoded parameters
+    pub fn from_der(der: &[u8]) -> Result<Dh, SslError> {
+        ffi::init();
+
+        let mut ptr = der.as_ptr();
+        let dh = unsafe {
+            ffi::d2i_DHparams(ptr::null_mut(), &mut ptr, der.len() as c_long)
+        };
+        try_ssl_null!(dh);
+
+        Ok(Dh { dh: dh })
+    }
+
+    /// Writes the parameters in PEM format
+    pub fn write_pem(&self, writer: &mut Writer) -> Result<(), SslError> {
+        let mut mem_bio = try!(MemBio::new());
+        unsafe {
+            try_ssl!(ffi::PEM_write_bio_DHparams(mem_bio.get_handle(), self.dh));
+        }
+        let buf = try!(mem_bio.read_to_end().map_err(StreamError));
+        writer.write(buf.as_slice()).map_err(StreamError)
+    }
+
+    /// Returns the parameters in DER format
+    pub fn to_der(&self) -> Result<Vec<u8>, SslError> {
+        unsafe {
+            let len = ffi::i2d_DHparams(self.dh, ptr::null_mut());
+            try_ssl_if!(len < 0);
+
+            let mut buf = Vec::from_elem(len as uint, 0u8);
+            let mut ptr = buf.as_mut_ptr();
+            try_ssl_if!(ffi::i2d_DHparams(self.dh, &mut ptr) != len);
+            Ok(buf)
+        }
+    }
+
+    /// Returns the size of the prime in bits
+    pub fn bits(&self) -> uint {
+        unsafe { ffi::DH_size(self.dh) as uint * 8 }
+    }
+
+    /// Checks that the prime is safe and the generator suitable
+    pub fn check(&self) -> Result<bool, SslError> {
+        let mut codes = 0;
+        unsafe {
+            try_ssl!(ffi::DH_check(self.dh, &mut codes));
+        }
+        Ok(codes == 0)
+    }
+
+    /// Returns raw handle
+    pub unsafe fn get_handle(&self) -> *mut ffi::DH {
+        self.dh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, MemReader};
+
+    use super::Dh;
+
+    #[test]
+    fn test_pem_roundtrip() {
+        let mut file = File::open(&Path::new("test/dhparams.pem")).unwrap();
+        let dh = Dh::from_pem(&mut file).unwrap();
+        assert_eq!(dh.bits(), 512);
+
+        let mut pem = vec!();
+        dh.write_pem(&mut pem).unwrap();
+        let dh2 = Dh::from_pem(&mut MemReader::new(pem)).unwrap();
+        assert_eq!(dh.to_der().unwrap(), dh2.to_der().unwrap());
+    }
+
+    #[test]
+    fn test_der_roundtrip() {
+        let mut file = File::open(&Path::new("test/dhparams.pem")).unwrap();
+        let dh = Dh::from_pem(&mut file).unwrap();
+        let der = dh.to_der().unwrap();
+        assert_eq!(Dh::from_der(der.as_slice()).unwrap().to_der().unwrap(), der);
+    }
+
+    #[test]
+    fn test_bad_pem() {
+        let mut reader = MemReader::new(b"not a pem file".to_vec());
+        assert!(Dh::from_pem(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut calls = 0u;
+        let dh = Dh::generate(128, 2, Some(|_, _| { calls += 1; true })).unwrap();
+        assert!(calls > 0);
+        assert!(dh.check().unwrap());
+    }
+
+    #[test]
+    fn test_generate_cancel() {
+        assert!(Dh::generate(128, 2, Some(|_, _| false)).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use libc::{c_uint};
+use std::c_str::CString;
+use std::ptr;
+
+use crypto::pkey::{PKey, Public, Both};
+use ffi;
+use ssl::error::{SslError};
+
+bitflags! {
+    #[doc="The algorithm classes an engine can be made the default for"]
+    flags EngineMethods: c_uint {
+        static ENGINE_METHOD_RSA = 0x0001,
+        static ENGINE_METHOD_DSA = 0x0002,
+        static ENGINE_METHOD_DH = 0x0004,
+        static ENGINE_METHOD_RAND = 0x0008,
+        static ENGINE_METHOD_ECDH = 0x0010,
+        static ENGINE_METHOD_ECDSA = 0x0020,
+        static ENGINE_METHOD_CIPHERS = 0x0040,
+        static ENGINE_METHOD_DIGESTS = 0x0080,
+        static ENGINE_METHOD_PKEY_METHS = 0x0200,
+        static ENGINE_METHOD_PKEY_ASN1_METHS = 0x0400,
+        static ENGINE_METHOD_ALL = 0xFFFF
+    }
+}
+
+/// A handle to an OpenSSL engine, such as a PKCS#11 module or other
+/// hardware accelerator
+///
+/// # Example
+///
+/// ```no_run
+/// use openssl::engine::{Engine, ENGINE_METHOD_RSA};
+///
+/// let mut engine = Engine::by_id("pkcs11").unwrap();
+/// engine.ctrl_cmd_string("PIN", Some("1234")).unwrap();
+/// engine.init().unwrap();
+/// engine.set_default(ENGINE_METHOD_RSA).unwrap();
+/// let key = engine.load_private_key("pkcs11:object=server-key").unwrap();
+/// ```
+pub struct Engine {
+    e: *mut ffi::ENGINE,
+    initialized: bool
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        unsafe {
+            if self.initialized {
+                ffi::ENGINE_finish(self.e);
+            }
+            ffi::ENGINE_free(self.e);
+        }
+    }
+}
+
+impl Engine {
+    /// Looks up an engine by its id, loading it dynamically if necessary.
+    ///
+    /// The engine must be initialized with `init` before it can be used, but
+    /// commands such as module paths may need to be sent with
+    /// `ctrl_cmd_string` first.
+    pub fn by_id(id: &str) -> Result<Engine, SslError> {
+        ffi::init();
+        unsafe { ffi::ENGINE_load_builtin_engines() };
+
+        let e = id.with_c_str(|id| unsafe { ffi::ENGINE_by_id(id) });
+        try_ssl_null!(e);
+
+        Ok(Engine {
+            e: e,
+            initialized: false
+        })
+    }
+
+    /// Sends an engine specific command, e.g. `SO_PATH` or `PIN`.
+    pub fn ctrl_cmd_string(&mut self, cmd: &str, arg: Option<&str>) -> Result<(), SslError> {
+        lift_ssl!(cmd.with_c_str(|cmd| {
+            match arg {
+                Some(arg) => arg.with_c_str(|arg| unsafe {
+                    ffi::ENGINE_ctrl_cmd_string(self.e, cmd, arg, 0)
+                }),
+                None => unsafe {
+                    ffi::ENGINE_ctrl_cmd_string(self.e, cmd, ptr::null(), 0)
+                }
+            }
+        }))
+    }
+
+    /// Initializes the engine so that it can be used.
+    pub fn init(&mut self) -> Result<(), SslError> {
+        if !self.initialized {
+            unsafe { try_ssl!(ffi::ENGINE_init(self.e)) };
+            self.initialized = true;
+        }
+        Ok(())
+    }
+
+    /// Makes this engine the default implementation of the given algorithm
+    /// classes.
+    pub fn set_default(&self, methods: EngineMethods) -> Result<(), SslError> {
+        lift_ssl!(unsafe { ffi::ENGINE_set_default(self.e, methods.bits()) })
+    }
+
+    /// Loads a private key held by the engine.
+    ///
+    /// The key material stays inside the engine; the returned key delegates
+    /// all private key operations to it.
+    pub fn load_private_key(&self, key_id: &str) -> Result<PKey, SslError> {
+        let pkey = key_id.with_c_str(|key_id| unsafe {
+            ffi::ENGINE_load_private_key(self.e, key_id, ptr::null_mut(), ptr::null_mut())
+        });
+        try_ssl_null!(pkey);
+        Ok(PKey::from_handle(pkey, Both))
+    }
+
+    /// Loads a public key held by the engine.
+    pub fn load_public_key(&self, key_id: &str) -> Result<PKey, SslError> {
+        let pkey = key_id.with_c_str(|key_id| unsafe {
+            ffi::ENGINE_load_public_key(self.e, key_id, ptr::null_mut(), ptr::null_mut())
+        });
+        try_ssl_null!(pkey);
+        Ok(PKey::from_handle(pkey, Public))
+    }
+
+    /// Returns the engine's id
+    pub fn id(&self) -> String {
+        unsafe { CString::new(ffi::ENGINE_get_id(self.e), false).to_string() }
+    }
+
+    /// Returns the engine's descriptive name
+    pub fn name(&self) -> String {
+        unsafe { CString::new(ffi::ENGINE_get_name(self.e), false).to_string() }
+    }
+
+    /// Returns raw handle
+    pub unsafe fn get_handle(&self) -> *mut ffi::ENGINE {
+        self.e
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Engine;
+
+    #[test]
+    fn test_by_id() {
+        let engine = Engine::by_id("dynamic").unwrap();
+        assert_eq!(engine.id().as_slice(), "dynamic");
+    }
+
+    #[test]
+    fn test_unknown_engine() {
+        assert!(Engine::by_id("no-such-engine").is_err());
+    }
+}
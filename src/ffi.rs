@@ -1,16 +1,28 @@
 #![allow(non_camel_case_types, non_uppercase_statics, non_snake_case)]
 #![allow(dead_code)]
-use libc::{c_void, c_int, c_char, c_ulong, c_long, c_uint, c_uchar, size_t};
+use libc::{c_void, c_int, c_char, c_ulong, c_long, c_uint, c_uchar, c_double, size_t};
+pub use libc::time_t;
+#[cfg(unix)]
+use libc::pthread_t;
+use std::mem;
 use std::ptr;
+use std::rt::mutex::NativeMutex;
+use sync::one::{Once, ONCE_INIT};
 
 pub type ASN1_INTEGER = c_void;
+pub type ASN1_OBJECT = c_void;
 pub type ASN1_STRING = c_void;
 pub type ASN1_TIME = c_void;
 pub type BIO = c_void;
 pub type BIO_METHOD = c_void;
 pub type BN_CTX = c_void;
 pub type COMP_METHOD = c_void;
+pub type CONF = c_void;
+pub type CONF_METHOD = c_void;
 pub type CRYPTO_EX_DATA = c_void;
+pub type CRYPTO_THREADID = c_void;
+pub type DH = c_void;
+pub type EC_KEY = c_void;
 pub type ENGINE = c_void;
 pub type EVP_CIPHER = c_void;
 pub type EVP_CIPHER_CTX = c_void;
@@ -19,14 +31,21 @@ pub type EVP_PKEY = c_void;
 pub type EVP_PKEY_CTX = c_void;
 pub type RSA = c_void;
 pub type SSL = c_void;
+pub type SSL_CIPHER = c_void;
+pub type SSL_SESSION = c_void;
 pub type SSL_CTX = c_void;
 pub type SSL_METHOD = c_void;
+pub type UI_METHOD = c_void;
 pub type X509 = c_void;
 pub type X509_CRL = c_void;
 pub type X509_EXTENSION = c_void;
 pub type X509_NAME = c_void;
+pub type X509_NAME_ENTRY = c_void;
 pub type X509_REQ = c_void;
+pub type X509_STORE = c_void;
 pub type X509_STORE_CTX = c_void;
+pub type X509_VERIFY_PARAM = c_void;
+pub type _STACK = c_void;
 
 #[allow(dead_code)]
 #[repr(C)]
@@ -35,7 +54,14 @@ pub struct BIGNUM {
     top: c_int,
     dmax: c_int,
     pub neg: c_int,
-    flags: c_int,
+    pub flags: c_int,
+}
+
+#[repr(C)]
+pub struct BN_GENCB {
+    pub ver: c_uint,
+    pub arg: *mut c_void,
+    pub cb: Option<extern "C" fn(p: c_int, n: c_int, cb: *mut BN_GENCB) -> c_int>
 }
 
 #[repr(C)]
@@ -48,6 +74,12 @@ pub struct EVP_MD_CTX {
     update: *mut c_void
 }
 
+#[repr(C)]
+pub struct GENERAL_NAME {
+    pub type_: c_int,
+    pub d: *mut c_void
+}
+
 #[repr(C)]
 pub struct HMAC_CTX {
     md: *mut EVP_MD,
@@ -58,6 +90,29 @@ pub struct HMAC_CTX {
     key: [c_uchar, ..128]
 }
 
+#[repr(C)]
+pub struct NETSCAPE_SPKAC {
+    pub pubkey: *mut c_void,
+    pub challenge: *mut ASN1_STRING
+}
+
+#[repr(C)]
+pub struct NETSCAPE_SPKI {
+    pub spkac: *mut NETSCAPE_SPKAC,
+    sig_algor: *mut c_void,
+    signature: *mut ASN1_STRING
+}
+
+#[repr(C)]
+pub struct RAND_METHOD {
+    pub seed: Option<extern "C" fn(buf: *const c_void, num: c_int)>,
+    pub bytes: Option<extern "C" fn(buf: *mut u8, num: c_int) -> c_int>,
+    pub cleanup: Option<extern "C" fn()>,
+    pub add: Option<extern "C" fn(buf: *const c_void, num: c_int, entropy: c_double)>,
+    pub pseudorand: Option<extern "C" fn(buf: *mut u8, num: c_int) -> c_int>,
+    pub status: Option<extern "C" fn() -> c_int>
+}
+
 #[repr(C)]
 pub struct X509V3_CTX {
     flags: c_int,
@@ -71,6 +126,12 @@ pub struct X509V3_CTX {
     // Maybe more here
 }
 
+#[repr(C)]
+pub struct timeval {
+    pub tv_sec: c_long,
+    pub tv_usec: c_long
+}
+
 pub type CRYPTO_EX_new = extern "C" fn(parent: *mut c_void, ptr: *mut c_void,
                                        ad: *const CRYPTO_EX_DATA, idx: c_int,
                                        argl: c_long, argp: *const c_void) -> c_int;
@@ -81,24 +142,106 @@ pub type CRYPTO_EX_dup = extern "C" fn(to: *mut CRYPTO_EX_DATA,
 pub type CRYPTO_EX_free = extern "C" fn(parent: *mut c_void, ptr: *mut c_void,
                                         ad: *mut CRYPTO_EX_DATA, idx: c_int,
                                         argl: c_long, argp: *mut c_void);
+pub type custom_ext_add_cb = extern "C" fn(s: *mut SSL, ext_type: c_uint,
+                                           out: *mut *const c_uchar, outlen: *mut size_t,
+                                           al: *mut c_int, add_arg: *mut c_void) -> c_int;
+pub type custom_ext_free_cb = extern "C" fn(s: *mut SSL, ext_type: c_uint,
+                                            out: *const c_uchar, add_arg: *mut c_void);
+pub type custom_ext_parse_cb = extern "C" fn(s: *mut SSL, ext_type: c_uint,
+                                             inp: *const c_uchar, inlen: size_t,
+                                             al: *mut c_int, parse_arg: *mut c_void) -> c_int;
 pub type PrivateKeyWriteCallback = extern "C" fn(buf: *mut c_char, size: c_int,
                                                  rwflag: c_int, user_data: *mut c_void)
                                                  -> c_int;
 
 pub static BIO_CTRL_EOF: c_int = 2;
+pub static BIO_CTRL_FLUSH: c_int = 11;
+
+pub static BN_FLG_CONSTTIME: c_int = 0x04;
+
+pub static CONF_MFLAGS_IGNORE_MISSING_FILE: c_ulong = 0x10;
 
 pub static CRYPTO_LOCK: c_int = 1;
 
+pub static CRYPTO_MEM_CHECK_DISABLE: c_int = 0x3;
+pub static CRYPTO_MEM_CHECK_ENABLE: c_int = 0x2;
+pub static CRYPTO_MEM_CHECK_OFF: c_int = 0x0;
+pub static CRYPTO_MEM_CHECK_ON: c_int = 0x1;
+
+pub static ERR_LIB_PEM: c_int = 9;
+pub static ERR_LIB_SSL: c_int = 20;
+pub static ERR_LIB_X509: c_int = 11;
+
 pub static MBSTRING_ASC:  c_int = MBSTRING_FLAG | 1;
 pub static MBSTRING_BMP:  c_int = MBSTRING_FLAG | 2;
 pub static MBSTRING_FLAG: c_int = 0x1000;
 pub static MBSTRING_UNIV: c_int = MBSTRING_FLAG | 4;
 pub static MBSTRING_UTF8: c_int = MBSTRING_FLAG;
 
-pub static NID_ext_key_usage: c_int = 126;
-pub static NID_key_usage:     c_int = 83;
+pub static NID_X9_62_prime256v1: c_int = 415;
+pub static NID_commonName:       c_int = 13;
+pub static NID_ext_key_usage:    c_int = 126;
+pub static NID_key_usage:        c_int = 83;
+pub static NID_secp384r1:        c_int = 715;
+pub static NID_secp521r1:        c_int = 716;
+pub static NID_subject_alt_name: c_int = 85;
+pub static NID_undef:            c_int = 0;
+
+pub static GEN_DNS: c_int = 2;
+pub static GEN_IPADD: c_int = 7;
+
+pub static PEM_R_NO_START_LINE: c_int = 108;
+
+pub static DTLS_CTRL_GET_TIMEOUT: c_int = 73;
+pub static DTLS_CTRL_HANDLE_TIMEOUT: c_int = 74;
+pub static DTLS_CTRL_LISTEN: c_int = 75;
+
+pub static SSLEAY_BUILT_ON: c_int = 3;
+pub static SSLEAY_CFLAGS: c_int = 2;
+pub static SSLEAY_DIR: c_int = 5;
+pub static SSLEAY_PLATFORM: c_int = 4;
+pub static SSLEAY_VERSION: c_int = 0;
+
+pub static SSL_AD_DECODE_ERROR: c_int = 50;
+pub static SSL_AD_UNRECOGNIZED_NAME: c_int = 112;
 
+pub static SSL_CB_ALERT: c_int = 0x4000;
+pub static SSL_CB_EXIT: c_int = 0x02;
+pub static SSL_CB_HANDSHAKE_DONE: c_int = 0x20;
+pub static SSL_CB_HANDSHAKE_START: c_int = 0x10;
+pub static SSL_CB_LOOP: c_int = 0x01;
+pub static SSL_CB_READ: c_int = 0x04;
+pub static SSL_CB_WRITE: c_int = 0x08;
+
+pub static SSL_CTRL_CLEAR_MODE: c_int = 78;
+pub static SSL_CTRL_CLEAR_OPTIONS: c_int = 77;
+pub static SSL_CTRL_EXTRA_CHAIN_CERT: c_int = 14;
+pub static SSL_CTRL_GET_SESS_CACHE_MODE: c_int = 45;
+pub static SSL_CTRL_GET_SESS_CACHE_SIZE: c_int = 43;
+pub static SSL_CTRL_GET_SESSION_REUSED: c_int = 8;
+pub static SSL_CTRL_GET_TLSEXT_STATUS_REQ_OCSP_RESP: c_int = 70;
+pub static SSL_CTRL_GET_TOTAL_RENEGOTIATIONS: c_int = 12;
+pub static SSL_CTRL_MODE: c_int = 33;
+pub static SSL_CTRL_OPTIONS: c_int = 32;
+pub static SSL_CTRL_SET_ECDH_AUTO: c_int = 94;
+pub static SSL_CTRL_SET_MTU: c_int = 17;
+pub static SSL_CTRL_SET_SESS_CACHE_MODE: c_int = 44;
+pub static SSL_CTRL_SET_SESS_CACHE_SIZE: c_int = 42;
 pub static SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+pub static SSL_CTRL_SET_TLSEXT_SERVERNAME_CB: c_int = 53;
+pub static SSL_CTRL_SET_TLSEXT_STATUS_REQ_CB: c_int = 63;
+pub static SSL_CTRL_SET_TLSEXT_STATUS_REQ_OCSP_RESP: c_int = 71;
+pub static SSL_CTRL_SET_TLSEXT_STATUS_REQ_TYPE: c_int = 65;
+pub static SSL_CTRL_SET_TLSEXT_TICKET_KEY_CB: c_int = 72;
+pub static SSL_CTRL_SET_TMP_DH: c_int = 3;
+pub static SSL_CTRL_SET_TMP_ECDH: c_int = 4;
+pub static SSL_R_CERTIFICATE_VERIFY_FAILED: c_int = 134;
+pub static SSL_R_SSLV3_ALERT_BAD_CERTIFICATE: c_int = 1042;
+pub static SSL_R_SSLV3_ALERT_CERTIFICATE_EXPIRED: c_int = 1045;
+pub static SSL_R_SSLV3_ALERT_HANDSHAKE_FAILURE: c_int = 1040;
+pub static SSL_R_TLSV1_ALERT_UNKNOWN_CA: c_int = 1048;
+pub static SSL_R_UNKNOWN_PROTOCOL: c_int = 252;
+pub static SSL_R_WRONG_VERSION_NUMBER: c_int = 267;
 pub static SSL_ERROR_NONE: c_int = 0;
 pub static SSL_ERROR_SSL: c_int = 1;
 pub static SSL_ERROR_SYSCALL: c_int = 5;
@@ -108,17 +251,71 @@ pub static SSL_ERROR_WANT_READ: c_int = 2;
 pub static SSL_ERROR_WANT_WRITE: c_int = 3;
 pub static SSL_ERROR_WANT_X509_LOOKUP: c_int = 4;
 pub static SSL_ERROR_ZERO_RETURN: c_int = 6;
+pub static SSL_MODE_ACCEPT_MOVING_WRITE_BUFFER: c_long = 0x00000002;
+pub static SSL_MODE_AUTO_RETRY: c_long = 0x00000004;
+pub static SSL_MODE_ENABLE_PARTIAL_WRITE: c_long = 0x00000001;
+pub static SSL_MODE_RELEASE_BUFFERS: c_long = 0x00000010;
+pub static SSL_OP_CIPHER_SERVER_PREFERENCE: c_long = 0x00400000;
+pub static SSL_OP_COOKIE_EXCHANGE: c_long = 0x00002000;
+pub static SSL_OP_NO_COMPRESSION: c_long = 0x00020000;
+pub static SSL_OP_NO_QUERY_MTU: c_long = 0x00001000;
+pub static SSL_OP_NO_SSLv2: c_long = 0x01000000;
+pub static SSL_OP_NO_SSLv3: c_long = 0x02000000;
+pub static SSL_OP_NO_TICKET: c_long = 0x00004000;
+pub static SSL_OP_NO_TLSv1: c_long = 0x04000000;
+pub static SSL_OP_NO_TLSv1_1: c_long = 0x10000000;
+pub static SSL_OP_NO_TLSv1_2: c_long = 0x08000000;
+pub static SSL_OP_SINGLE_DH_USE: c_long = 0x00100000;
+pub static SSL_OP_SINGLE_ECDH_USE: c_long = 0x00080000;
+pub static SSL_RECEIVED_SHUTDOWN: c_int = 2;
+pub static SSL_SENT_SHUTDOWN: c_int = 1;
+pub static SSL_SESS_CACHE_BOTH: c_long = SSL_SESS_CACHE_CLIENT | SSL_SESS_CACHE_SERVER;
+pub static SSL_SESS_CACHE_CLIENT: c_long = 0x0001;
+pub static SSL_SESS_CACHE_NO_AUTO_CLEAR: c_long = 0x0080;
+pub static SSL_SESS_CACHE_NO_INTERNAL: c_long = SSL_SESS_CACHE_NO_INTERNAL_LOOKUP | SSL_SESS_CACHE_NO_INTERNAL_STORE;
+pub static SSL_SESS_CACHE_NO_INTERNAL_LOOKUP: c_long = 0x0100;
+pub static SSL_SESS_CACHE_NO_INTERNAL_STORE: c_long = 0x0200;
+pub static SSL_SESS_CACHE_OFF: c_long = 0x0000;
+pub static SSL_SESS_CACHE_SERVER: c_long = 0x0002;
+pub static SSL_TLSEXT_ERR_ALERT_FATAL: c_int = 2;
+pub static SSL_TLSEXT_ERR_NOACK: c_int = 3;
+pub static SSL_TLSEXT_ERR_OK: c_int = 0;
+pub static SSL_ST_ACCEPT: c_int = 0x2000;
+pub static SSL_ST_CONNECT: c_int = 0x1000;
+pub static SSL_ST_OK: c_int = 0x03;
+pub static SSL_VERIFY_FAIL_IF_NO_PEER_CERT: c_int = 2;
 pub static SSL_VERIFY_NONE: c_int = 0;
 pub static SSL_VERIFY_PEER: c_int = 1;
 
 pub static TLSEXT_NAMETYPE_host_name: c_long = 0;
+pub static TLSEXT_STATUSTYPE_ocsp: c_long = 1;
+
+pub static V_ASN1_APPLICATION:      c_int = 0x40;
+pub static V_ASN1_CONSTRUCTED:      c_int = 0x20;
+pub static V_ASN1_CONTEXT_SPECIFIC: c_int = 0x80;
+pub static V_ASN1_PRIVATE:          c_int = 0xc0;
+pub static V_ASN1_UNIVERSAL:        c_int = 0x00;
 
+pub static V_ASN1_BOOLEAN:         c_int = 1;
 pub static V_ASN1_GENERALIZEDTIME: c_int = 24;
+pub static V_ASN1_IA5STRING:       c_int = 22;
+pub static V_ASN1_NULL:            c_int = 5;
+pub static V_ASN1_NUMERICSTRING:   c_int = 18;
+pub static V_ASN1_OBJECT:          c_int = 6;
+pub static V_ASN1_PRINTABLESTRING: c_int = 19;
+pub static V_ASN1_T61STRING:       c_int = 20;
 pub static V_ASN1_UTCTIME:         c_int = 23;
+pub static V_ASN1_UTF8STRING:      c_int = 12;
+pub static V_ASN1_VISIBLESTRING:   c_int = 26;
 
+pub static X509_CHECK_FLAG_NO_PARTIAL_WILDCARDS: c_uint = 0x4;
 pub static X509_FILETYPE_ASN1: c_int = 2;
 pub static X509_FILETYPE_DEFAULT: c_int = 3;
 pub static X509_FILETYPE_PEM: c_int = 1;
+pub static X509_V_FLAG_CRL_CHECK: c_ulong = 0x4;
+pub static X509_V_FLAG_CRL_CHECK_ALL: c_ulong = 0x8;
+pub static X509_V_FLAG_PARTIAL_CHAIN: c_ulong = 0x80000;
+pub static X509_V_FLAG_X509_STRICT: c_ulong = 0x20;
 pub static X509_V_ERR_AKID_ISSUER_SERIAL_MISMATCH: c_int = 31;
 pub static X509_V_ERR_AKID_SKID_MISMATCH: c_int = 30;
 pub static X509_V_ERR_APPLICATION_VERIFICATION: c_int = 50;
@@ -140,11 +337,13 @@ pub static X509_V_ERR_ERROR_IN_CERT_NOT_BEFORE_FIELD: c_int = 13;
 pub static X509_V_ERR_ERROR_IN_CRL_LAST_UPDATE_FIELD: c_int = 15;
 pub static X509_V_ERR_ERROR_IN_CRL_NEXT_UPDATE_FIELD: c_int = 16;
 pub static X509_V_ERR_EXCLUDED_VIOLATION: c_int = 48;
+pub static X509_V_ERR_HOSTNAME_MISMATCH: c_int = 62;
 pub static X509_V_ERR_INVALID_CA: c_int = 24;
 pub static X509_V_ERR_INVALID_EXTENSION: c_int = 41;
 pub static X509_V_ERR_INVALID_NON_CA: c_int = 37;
 pub static X509_V_ERR_INVALID_POLICY_EXTENSION: c_int = 42;
 pub static X509_V_ERR_INVALID_PURPOSE: c_int = 26;
+pub static X509_V_ERR_IP_ADDRESS_MISMATCH: c_int = 64;
 pub static X509_V_ERR_KEYUSAGE_NO_CERTSIGN: c_int = 32;
 pub static X509_V_ERR_KEYUSAGE_NO_CRL_SIGN: c_int = 35;
 pub static X509_V_ERR_KEYUSAGE_NO_DIGITAL_SIGNATURE: c_int = 39;
@@ -189,13 +388,33 @@ extern {}
 #[link(name="wsock32")]
 extern { }
 
-/* Since the openssl BN_is_zero is sometimes a macro, this wrapper is necessary. */
+/* Since the openssl BN_is_* predicates are sometimes macros, these wrappers are necessary. */
 pub unsafe fn BN_is_zero(a: *mut BIGNUM) -> c_int { bn_is_zero(a) }
+pub unsafe fn BN_is_one(a: *mut BIGNUM) -> c_int { bn_is_one(a) }
+pub unsafe fn BN_is_odd(a: *mut BIGNUM) -> c_int { bn_is_odd(a) }
+pub unsafe fn BN_is_word(a: *mut BIGNUM, w: c_ulong) -> c_int { bn_is_word(a, w) }
 
 /* Special import from native/bn_is_zero.c */
 #[link(name = "wrapped", kind = "static")]
 extern "C" {
     pub fn bn_is_zero(a: *mut BIGNUM) -> c_int;
+    pub fn bn_is_one(a: *mut BIGNUM) -> c_int;
+    pub fn bn_is_odd(a: *mut BIGNUM) -> c_int;
+    pub fn bn_is_word(a: *mut BIGNUM, w: c_ulong) -> c_int;
+}
+
+/* Special import from native/ssl_keylog.c */
+#[link(name = "wrapped", kind = "static")]
+extern "C" {
+    pub fn ssl_get_client_random(s: *const SSL, out: *mut u8) -> c_int;
+    pub fn ssl_get_master_key(s: *const SSL, out: *mut u8, outlen: c_int) -> c_int;
+}
+
+/* Special import from native/version.c */
+#[link(name = "wrapped", kind = "static")]
+extern "C" {
+    pub fn openssl_header_version() -> c_ulong;
+    pub fn openssl_header_version_text() -> *const c_char;
 }
 
 // Functions converted from macros
@@ -203,37 +422,152 @@ pub unsafe fn BIO_eof(b: *mut BIO) -> bool {
     BIO_ctrl(b, BIO_CTRL_EOF, 0, ptr::null_mut()) == 1
 }
 
+pub unsafe fn BIO_flush(b: *mut BIO) -> c_long {
+    BIO_ctrl(b, BIO_CTRL_FLUSH, 0, ptr::null_mut())
+}
+
+pub fn ERR_GET_LIB(l: c_ulong) -> c_int {
+    ((l >> 24) & 0x0FF) as c_int
+}
+
+pub fn ERR_GET_FUNC(l: c_ulong) -> c_int {
+    ((l >> 12) & 0xFFF) as c_int
+}
+
+pub fn ERR_GET_REASON(l: c_ulong) -> c_int {
+    (l & 0xFFF) as c_int
+}
+
+static mut MUTEXES: *mut Vec<NativeMutex> = 0 as *mut Vec<NativeMutex>;
+
+extern fn locking_function(mode: c_int, n: c_int, _file: *const c_char,
+                               _line: c_int) {
+    unsafe {
+        let mutex = (*MUTEXES).get_mut(n as uint);
+
+        if mode & CRYPTO_LOCK != 0 {
+            mutex.lock_noguard();
+        } else {
+            mutex.unlock_noguard();
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn current_thread_id() -> c_ulong {
+    extern {
+        fn pthread_self() -> pthread_t;
+    }
+    // pthread_t is an integer on some platforms and a pointer on others
+    unsafe { pthread_self() as c_ulong }
+}
+
+#[cfg(windows)]
+pub fn current_thread_id() -> c_ulong {
+    extern "system" {
+        fn GetCurrentThreadId() -> u32;
+    }
+    unsafe { GetCurrentThreadId() as c_ulong }
+}
+
+extern fn thread_id_function(id: *mut CRYPTO_THREADID) {
+    unsafe { CRYPTO_THREADID_set_numeric(id, current_thread_id()) }
+}
+
+/// Initializes the library and installs the locking and thread id callbacks
+/// libcrypto needs to be used from multiple threads.
+///
+/// This is called automatically by the types in this crate that need it, and
+/// only does work the first time it is called.
+pub fn init() {
+    static mut INIT: Once = ONCE_INIT;
+
+    unsafe {
+        INIT.doit(|| {
+            SSL_library_init();
+            SSL_load_error_strings();
+            ERR_load_crypto_strings();
+            OPENSSL_add_all_algorithms_noconf();
+
+            let num_locks = CRYPTO_num_locks();
+            let mutexes = box Vec::from_fn(num_locks as uint, |_| NativeMutex::new());
+            MUTEXES = mem::transmute(mutexes);
+
+            CRYPTO_set_locking_callback(locking_function);
+            CRYPTO_THREADID_set_callback(thread_id_function);
+        })
+    }
+}
+
+/// Releases the library's global state.
+pub unsafe fn cleanup() {
+    CONF_modules_unload(1);
+    ENGINE_cleanup();
+    EVP_cleanup();
+    CRYPTO_cleanup_all_ex_data();
+    ERR_remove_thread_state(ptr::null());
+    ERR_free_strings();
+}
+
 // True functions
 extern "C" {
+    pub fn ASN1_get_object(pp: *mut *const u8, plength: *mut c_long, ptag: *mut c_int,
+                           pclass: *mut c_int, omax: c_long) -> c_int;
+    pub fn ASN1_tag2str(tag: c_int) -> *const c_char;
+    pub fn ASN1_INTEGER_free(a: *mut ASN1_INTEGER);
     pub fn ASN1_INTEGER_set(dest: *mut ASN1_INTEGER, value: c_long) -> c_int;
+    pub fn ASN1_INTEGER_to_BN(ai: *const ASN1_INTEGER, bn: *mut BIGNUM) -> *mut BIGNUM;
+    pub fn ASN1_OBJECT_free(a: *mut ASN1_OBJECT);
     pub fn ASN1_STRING_type_new(ty: c_int) -> *mut ASN1_STRING;
+    pub fn ASN1_STRING_set(x: *mut ASN1_STRING, data: *const u8, len: c_int) -> c_int;
+    pub fn ASN1_STRING_data(x: *mut ASN1_STRING) -> *mut u8;
+    pub fn ASN1_STRING_length(x: *const ASN1_STRING) -> c_int;
+    pub fn ASN1_STRING_type(x: *const ASN1_STRING) -> c_int;
+    pub fn ASN1_TIME_new() -> *mut ASN1_TIME;
     pub fn ASN1_TIME_free(tm: *mut ASN1_TIME);
+    pub fn ASN1_TIME_set_string(s: *mut ASN1_TIME, str: *const c_char) -> c_int;
 
     pub fn BIO_ctrl(b: *mut BIO, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
     pub fn BIO_free_all(b: *mut BIO);
     pub fn BIO_new(type_: *const BIO_METHOD) -> *mut BIO;
+    pub fn BIO_new_file(filename: *const c_char, mode: *const c_char) -> *mut BIO;
+    pub fn BIO_number_written(b: *mut BIO) -> c_ulong;
     pub fn BIO_read(b: *mut BIO, buf: *mut c_void, len: c_int) -> c_int;
     pub fn BIO_write(b: *mut BIO, buf: *const c_void, len: c_int) -> c_int;
     pub fn BIO_s_mem() -> *const BIO_METHOD;
+    pub fn BIO_ctrl_pending(b: *mut BIO) -> size_t;
+    pub fn BIO_s_null() -> *const BIO_METHOD;
 
     pub fn BN_new() -> *mut BIGNUM;
+    pub fn BN_copy(a: *mut BIGNUM, b: *const BIGNUM) -> *mut BIGNUM;
     pub fn BN_dup(n: *mut BIGNUM) -> *mut BIGNUM;
+    pub fn BN_swap(a: *mut BIGNUM, b: *mut BIGNUM);
+    pub fn BN_clear(bn: *mut BIGNUM);
+    pub fn BN_to_ASN1_INTEGER(bn: *const BIGNUM, ai: *mut ASN1_INTEGER) -> *mut ASN1_INTEGER;
     pub fn BN_clear_free(bn: *mut BIGNUM);
 
     pub fn BN_CTX_new() -> *mut BN_CTX;
     pub fn BN_CTX_free(ctx: *mut BN_CTX);
 
+    pub fn BN_get_word(bn: *mut BIGNUM) -> c_ulong;
     pub fn BN_num_bits(bn: *mut BIGNUM) -> c_int;
     pub fn BN_set_negative(bn: *mut BIGNUM, n: c_int);
     pub fn BN_set_word(bn: *mut BIGNUM, n: c_ulong) -> c_int;
 
     /* Arithmetic operations on BIGNUMs */
+    pub fn BN_add_word(a: *mut BIGNUM, w: c_ulong) -> c_int;
+    pub fn BN_div_word(a: *mut BIGNUM, w: c_ulong) -> c_ulong;
+    pub fn BN_mod_word(a: *const BIGNUM, w: c_ulong) -> c_ulong;
+    pub fn BN_mul_word(a: *mut BIGNUM, w: c_ulong) -> c_int;
+    pub fn BN_sub_word(a: *mut BIGNUM, w: c_ulong) -> c_int;
     pub fn BN_add(r: *mut BIGNUM, a: *mut BIGNUM, b: *mut BIGNUM) -> c_int;
     pub fn BN_div(dv: *mut BIGNUM, rem: *mut BIGNUM, a: *mut BIGNUM, b: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
     pub fn BN_exp(r: *mut BIGNUM, a: *mut BIGNUM, p: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
     pub fn BN_gcd(r: *mut BIGNUM, a: *mut BIGNUM, b: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
     pub fn BN_mod_add(r: *mut BIGNUM, a: *mut BIGNUM, b: *mut BIGNUM, m: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
     pub fn BN_mod_exp(r: *mut BIGNUM, a: *mut BIGNUM, p: *mut BIGNUM, m: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    pub fn BN_mod_exp2_mont(r: *mut BIGNUM, a1: *mut BIGNUM, p1: *mut BIGNUM, a2: *mut BIGNUM, p2: *mut BIGNUM, m: *mut BIGNUM, ctx: *mut BN_CTX, in_mont: *mut c_void) -> c_int;
+    pub fn BN_mod_exp_mont_consttime(r: *mut BIGNUM, a: *mut BIGNUM, p: *mut BIGNUM, m: *mut BIGNUM, ctx: *mut BN_CTX, in_mont: *mut c_void) -> c_int;
     pub fn BN_mod_inverse(r: *mut BIGNUM, a: *mut BIGNUM, n: *mut BIGNUM, ctx: *mut BN_CTX) -> *const BIGNUM;
     pub fn BN_mod_mul(r: *mut BIGNUM, a: *mut BIGNUM, b: *mut BIGNUM, m: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
     pub fn BN_mod_sqr(r: *mut BIGNUM, a: *mut BIGNUM, m: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
@@ -243,6 +577,38 @@ extern "C" {
     pub fn BN_sqr(r: *mut BIGNUM, a: *mut BIGNUM, ctx: *mut BN_CTX) -> c_int;
     pub fn BN_sub(r: *mut BIGNUM, a: *mut BIGNUM, b: *mut BIGNUM) -> c_int;
 
+    /* Arithmetic in GF(2^m), missing when OpenSSL is built with no-ec2m */
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_add(r: *mut BIGNUM, a: *const BIGNUM, b: *const BIGNUM) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_div(r: *mut BIGNUM, a: *const BIGNUM, b: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_exp(r: *mut BIGNUM, a: *const BIGNUM, b: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_inv(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_mul(r: *mut BIGNUM, a: *const BIGNUM, b: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_solve_quad(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_sqr(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    #[cfg(feature = "ec2m")]
+    pub fn BN_GF2m_mod_sqrt(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+
+    /* Fast reductions modulo the NIST primes */
+    pub fn BN_get0_nist_prime_192() -> *const BIGNUM;
+    pub fn BN_get0_nist_prime_224() -> *const BIGNUM;
+    pub fn BN_get0_nist_prime_256() -> *const BIGNUM;
+    pub fn BN_get0_nist_prime_384() -> *const BIGNUM;
+    pub fn BN_get0_nist_prime_521() -> *const BIGNUM;
+    pub fn BN_nist_mod_192(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    pub fn BN_nist_mod_224(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    pub fn BN_nist_mod_256(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    pub fn BN_nist_mod_384(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    pub fn BN_nist_mod_521(r: *mut BIGNUM, a: *const BIGNUM, p: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+
     /* Bit operations on BIGNUMs */
     pub fn BN_clear_bit(a: *mut BIGNUM, n: c_int) -> c_int;
     pub fn BN_is_bit_set(a: *mut BIGNUM, n: c_int) -> c_int;
@@ -258,7 +624,8 @@ extern "C" {
     pub fn BN_ucmp(a: *mut BIGNUM, b: *mut BIGNUM) -> c_int;
 
     /* Prime handling */
-    pub fn BN_generate_prime_ex(r: *mut BIGNUM, bits: c_int, safe: c_int, add: *mut BIGNUM, rem: *mut BIGNUM, cb: *const c_void) -> c_int;
+    pub fn BN_kronecker(a: *const BIGNUM, b: *const BIGNUM, ctx: *mut BN_CTX) -> c_int;
+    pub fn BN_generate_prime_ex(r: *mut BIGNUM, bits: c_int, safe: c_int, add: *mut BIGNUM, rem: *mut BIGNUM, cb: *mut BN_GENCB) -> c_int;
     pub fn BN_is_prime_ex(p: *mut BIGNUM, checks: c_int, ctx: *mut BN_CTX, cb: *const c_void) -> c_int;
     pub fn BN_is_prime_fasttest_ex(p: *mut BIGNUM, checks: c_int, ctx: *mut BN_CTX, do_trial_division: c_int, cb: *const c_void) -> c_int;
 
@@ -271,26 +638,94 @@ extern "C" {
     /* Conversion from/to binary representation */
     pub fn BN_bin2bn(s: *const u8, size: c_int, ret: *mut BIGNUM) -> *mut BIGNUM;
     pub fn BN_bn2bin(a: *mut BIGNUM, to: *mut u8) -> c_int;
+    pub fn BN_bn2mpi(a: *mut BIGNUM, to: *mut u8) -> c_int;
+    pub fn BN_mpi2bn(s: *const u8, len: c_int, ret: *mut BIGNUM) -> *mut BIGNUM;
 
     /* Conversion from/to string representation */
     pub fn BN_bn2dec(a: *mut BIGNUM) -> *const c_char;
+    pub fn BN_bn2hex(a: *mut BIGNUM) -> *const c_char;
+    pub fn BN_dec2bn(a: *mut *mut BIGNUM, s: *const c_char) -> c_int;
+    pub fn BN_hex2bn(a: *mut *mut BIGNUM, s: *const c_char) -> c_int;
+
+    pub fn CONF_modules_load(cnf: *const CONF, appname: *const c_char,
+                             flags: c_ulong) -> c_int;
+    pub fn CONF_modules_unload(all: c_int);
+    pub fn CONF_modules_load_file(filename: *const c_char, appname: *const c_char,
+                                  flags: c_ulong) -> c_int;
 
     pub fn CRYPTO_num_locks() -> c_int;
     pub fn CRYPTO_set_locking_callback(func: extern "C" fn(mode: c_int,
                                                            n: c_int,
                                                            file: *const c_char,
                                                            line: c_int));
+    pub fn CRYPTO_THREADID_set_callback(func: extern "C" fn(id: *mut CRYPTO_THREADID)) -> c_int;
+    pub fn CRYPTO_THREADID_set_numeric(id: *mut CRYPTO_THREADID, val: c_ulong);
+    pub fn CRYPTO_cleanup_all_ex_data();
     pub fn CRYPTO_free(buf: *const c_char);
+    pub fn CRYPTO_malloc(num: c_int, file: *const c_char, line: c_int) -> *mut c_void;
+    pub fn CRYPTO_dbg_free(addr: *mut c_void, before_p: c_int);
+    pub fn CRYPTO_dbg_get_options() -> c_long;
+    pub fn CRYPTO_dbg_malloc(addr: *mut c_void, num: c_int, file: *const c_char,
+                             line: c_int, before_p: c_int);
+    pub fn CRYPTO_dbg_realloc(addr1: *mut c_void, addr2: *mut c_void, num: c_int,
+                              file: *const c_char, line: c_int, before_p: c_int);
+    pub fn CRYPTO_dbg_set_options(bits: c_long);
+    pub fn CRYPTO_mem_ctrl(mode: c_int) -> c_int;
+    pub fn CRYPTO_mem_leaks(bio: *mut BIO);
+    pub fn CRYPTO_set_mem_debug_functions(
+        m: Option<unsafe extern "C" fn(*mut c_void, c_int, *const c_char, c_int, c_int)>,
+        r: Option<unsafe extern "C" fn(*mut c_void, *mut c_void, c_int, *const c_char,
+                                       c_int, c_int)>,
+        f: Option<unsafe extern "C" fn(*mut c_void, c_int)>,
+        so: Option<unsafe extern "C" fn(c_long)>,
+        go: Option<unsafe extern "C" fn() -> c_long>) -> c_int;
     pub fn CRYPTO_memcmp(a: *const c_void, b: *const c_void,
                          len: size_t) -> c_int;
 
+    pub fn EC_KEY_free(key: *mut EC_KEY);
+    pub fn EC_KEY_new_by_curve_name(nid: c_int) -> *mut EC_KEY;
+
+    pub fn DH_check(dh: *const DH, codes: *mut c_int) -> c_int;
+    pub fn DH_free(dh: *mut DH);
+    pub fn DH_generate_parameters_ex(dh: *mut DH, prime_len: c_int, generator: c_int,
+                                     cb: *mut BN_GENCB) -> c_int;
+    pub fn DH_new() -> *mut DH;
+    pub fn DH_size(dh: *const DH) -> c_int;
+
+    pub fn ENGINE_by_id(id: *const c_char) -> *mut ENGINE;
+    pub fn ENGINE_cleanup();
+    pub fn ENGINE_ctrl_cmd_string(e: *mut ENGINE, cmd_name: *const c_char,
+                                  arg: *const c_char, cmd_optional: c_int) -> c_int;
+    pub fn ENGINE_finish(e: *mut ENGINE) -> c_int;
+    pub fn ENGINE_free(e: *mut ENGINE) -> c_int;
+    pub fn ENGINE_get_id(e: *const ENGINE) -> *const c_char;
+    pub fn ENGINE_get_name(e: *const ENGINE) -> *const c_char;
+    pub fn ENGINE_init(e: *mut ENGINE) -> c_int;
+    pub fn ENGINE_load_builtin_engines();
+    pub fn ENGINE_load_private_key(e: *mut ENGINE, key_id: *const c_char,
+                                   ui_method: *mut UI_METHOD,
+                                   callback_data: *mut c_void) -> *mut EVP_PKEY;
+    pub fn ENGINE_load_public_key(e: *mut ENGINE, key_id: *const c_char,
+                                  ui_method: *mut UI_METHOD,
+                                  callback_data: *mut c_void) -> *mut EVP_PKEY;
+    pub fn ENGINE_set_default(e: *mut ENGINE, flags: c_uint) -> c_int;
+
     pub fn ERR_get_error() -> c_ulong;
+    pub fn ERR_peek_last_error() -> c_ulong;
+    pub fn ERR_clear_error();
+    pub fn ERR_put_error(lib: c_int, func: c_int, reason: c_int, file: *const c_char,
+                         line: c_int);
 
+    pub fn ERR_error_string_n(err: c_ulong, buf: *mut c_char, len: size_t);
     pub fn ERR_lib_error_string(err: c_ulong) -> *const c_char;
     pub fn ERR_func_error_string(err: c_ulong) -> *const c_char;
     pub fn ERR_reason_error_string(err: c_ulong) -> *const c_char;
 
     pub fn ERR_load_crypto_strings();
+    pub fn ERR_free_strings();
+    pub fn ERR_remove_thread_state(tid: *const CRYPTO_THREADID);
+
+    pub fn EVP_cleanup();
 
     pub fn EVP_md5() -> *const EVP_MD;
     pub fn EVP_ripemd160() -> *const EVP_MD;
@@ -316,6 +751,9 @@ extern "C" {
 
     pub fn EVP_CipherInit(ctx: *mut EVP_CIPHER_CTX, evp: *const EVP_CIPHER,
                           key: *const u8, iv: *const u8, mode: c_int);
+    pub fn EVP_CipherInit_ex(ctx: *mut EVP_CIPHER_CTX, evp: *const EVP_CIPHER,
+                             imple: *mut ENGINE, key: *const u8, iv: *const u8,
+                             enc: c_int) -> c_int;
     pub fn EVP_CipherUpdate(ctx: *mut EVP_CIPHER_CTX, outbuf: *mut u8,
                             outlen: &mut c_uint, inbuf: *const u8, inlen: c_int);
     pub fn EVP_CipherFinal(ctx: *mut EVP_CIPHER_CTX, res: *mut u8, len: &mut c_int);
@@ -333,15 +771,61 @@ extern "C" {
     pub fn EVP_PKEY_get1_RSA(k: *mut EVP_PKEY) -> *mut RSA;
     pub fn EVP_PKEY_set1_RSA(k: *mut EVP_PKEY, r: *mut RSA) -> c_int;
 
+    #[cfg(feature = "fips")]
+    pub fn FIPS_mode() -> c_int;
+    #[cfg(feature = "fips")]
+    pub fn FIPS_mode_set(onoff: c_int) -> c_int;
+
     pub fn HMAC_CTX_init(ctx: *mut HMAC_CTX);
-    pub fn HMAC_Init_ex(ctx: *mut HMAC_CTX, key: *const u8, keylen: c_int, md: *const EVP_MD, imple: *const ENGINE);
+    pub fn HMAC_Init_ex(ctx: *mut HMAC_CTX, key: *const u8, keylen: c_int, md: *const EVP_MD, imple: *const ENGINE) -> c_int;
     pub fn HMAC_Final(ctx: *mut HMAC_CTX, output: *mut u8, len: *mut c_uint);
     pub fn HMAC_Update(ctx: *mut HMAC_CTX, input: *const u8, len: c_uint);
 
+    pub fn NCONF_new(meth: *mut CONF_METHOD) -> *mut CONF;
+    pub fn NCONF_free(conf: *mut CONF);
+    pub fn NCONF_load(conf: *mut CONF, file: *const c_char, eline: *mut c_long) -> c_int;
+    pub fn NCONF_get_string(conf: *const CONF, group: *const c_char,
+                            name: *const c_char) -> *const c_char;
+
+    pub fn NETSCAPE_SPKI_b64_decode(str: *const c_char, len: c_int) -> *mut NETSCAPE_SPKI;
+    pub fn NETSCAPE_SPKI_b64_encode(x: *mut NETSCAPE_SPKI) -> *mut c_char;
+    pub fn NETSCAPE_SPKI_free(x: *mut NETSCAPE_SPKI);
+    pub fn NETSCAPE_SPKI_get_pubkey(x: *mut NETSCAPE_SPKI) -> *mut EVP_PKEY;
+    pub fn NETSCAPE_SPKI_new() -> *mut NETSCAPE_SPKI;
+    pub fn NETSCAPE_SPKI_set_pubkey(x: *mut NETSCAPE_SPKI, pkey: *mut EVP_PKEY) -> c_int;
+    pub fn NETSCAPE_SPKI_sign(x: *mut NETSCAPE_SPKI, pkey: *mut EVP_PKEY,
+                              md: *const EVP_MD) -> c_int;
+    pub fn NETSCAPE_SPKI_verify(x: *mut NETSCAPE_SPKI, pkey: *mut EVP_PKEY) -> c_int;
+
+    pub fn OBJ_ln2nid(s: *const c_char) -> c_int;
+    pub fn OBJ_new_nid(num: c_int) -> c_int;
+    pub fn OBJ_nid2ln(n: c_int) -> *const c_char;
+    pub fn OBJ_nid2obj(n: c_int) -> *mut ASN1_OBJECT;
+    pub fn OBJ_nid2sn(n: c_int) -> *const c_char;
+    pub fn OBJ_obj2nid(o: *const ASN1_OBJECT) -> c_int;
+    pub fn OBJ_obj2txt(buf: *mut c_char, buf_len: c_int, a: *const ASN1_OBJECT,
+                       no_name: c_int) -> c_int;
+    pub fn OBJ_sn2nid(s: *const c_char) -> c_int;
+    pub fn OBJ_txt2obj(s: *const c_char, no_name: c_int) -> *mut ASN1_OBJECT;
+
+    pub fn OPENSSL_add_all_algorithms_noconf();
+    pub fn OPENSSL_config(config_name: *const c_char);
+    pub fn OPENSSL_cleanse(ptr: *mut c_void, len: size_t);
+    pub fn OPENSSL_load_builtin_modules();
+
+    pub fn PEM_read_bio(bio: *mut BIO, name: *mut *mut c_char, header: *mut *mut c_char,
+                        data: *mut *mut u8, len: *mut c_long) -> c_int;
     pub fn PEM_write_bio_PrivateKey(bio: *mut BIO, pkey: *mut EVP_PKEY, cipher: *const EVP_CIPHER,
                                     kstr: *mut c_char, klen: c_int,
                                     callback: *mut c_void,
                                     user_data: *mut c_void) -> c_int;
+    pub fn PEM_read_bio_DHparams(bio: *mut BIO, out: *mut *mut DH,
+                                 callback: Option<PrivateKeyWriteCallback>,
+                                 user_data: *mut c_void) -> *mut DH;
+    pub fn PEM_write_bio_DHparams(bio: *mut BIO, x: *mut DH) -> c_int;
+    pub fn PEM_read_bio_X509(bio: *mut BIO, out: *mut *mut X509,
+                             callback: Option<PrivateKeyWriteCallback>,
+                             user_data: *mut c_void) -> *mut X509;
     pub fn PEM_write_bio_X509(bio: *mut BIO, x509: *mut X509) -> c_int;
 
     pub fn PKCS5_PBKDF2_HMAC_SHA1(pass: *const u8, passlen: c_int,
@@ -350,7 +834,13 @@ extern "C" {
                                   out: *mut u8) -> c_int;
 
 
+    pub fn RAND_add(buf: *const c_void, num: c_int, entropy: c_double);
     pub fn RAND_bytes(buf: *mut u8, num: c_int) -> c_int;
+    pub fn RAND_get_rand_method() -> *const RAND_METHOD;
+    pub fn RAND_load_file(filename: *const c_char, max_bytes: c_long) -> c_int;
+    pub fn RAND_seed(buf: *const c_void, num: c_int);
+    pub fn RAND_set_rand_method(meth: *const RAND_METHOD) -> c_int;
+    pub fn RAND_status() -> c_int;
 
     pub fn RSA_generate_key(modsz: c_uint, e: c_uint, cb: *const u8, cbarg: *const u8) -> *mut RSA;
     pub fn RSA_private_decrypt(flen: c_uint, from: *const u8, to: *mut u8, k: *mut RSA,
@@ -363,6 +853,9 @@ extern "C" {
     pub fn RSA_verify(t: c_int, m: *const u8, mlen: c_uint, sig: *const u8, siglen: c_uint,
                       k: *mut RSA) -> c_int;
 
+    pub fn SSLeay() -> c_ulong;
+    pub fn SSLeay_version(t: c_int) -> *const c_char;
+
     pub fn SSL_library_init() -> c_int;
 
     pub fn SSL_load_error_strings();
@@ -376,31 +869,125 @@ extern "C" {
     #[cfg(feature = "tlsv1_2")]
     pub fn TLSv1_2_method() -> *const SSL_METHOD;
     pub fn SSLv23_method() -> *const SSL_METHOD;
+    pub fn DTLSv1_method() -> *const SSL_METHOD;
+    #[cfg(feature = "dtlsv1_2")]
+    pub fn DTLSv1_2_method() -> *const SSL_METHOD;
 
     pub fn SSL_new(ctx: *mut SSL_CTX) -> *mut SSL;
     pub fn SSL_free(ssl: *mut SSL);
     pub fn SSL_set_bio(ssl: *mut SSL, rbio: *mut BIO, wbio: *mut BIO);
     pub fn SSL_get_rbio(ssl: *mut SSL) -> *mut BIO;
     pub fn SSL_get_wbio(ssl: *mut SSL) -> *mut BIO;
+    pub fn SSL_accept(ssl: *mut SSL) -> c_int;
     pub fn SSL_connect(ssl: *mut SSL) -> c_int;
+    pub fn SSL_do_handshake(ssl: *mut SSL) -> c_int;
+    pub fn SSL_set_connect_state(ssl: *mut SSL);
+    pub fn SSL_set_accept_state(ssl: *mut SSL);
+    pub fn SSL_state(ssl: *const SSL) -> c_int;
+    pub fn SSL_set_msg_callback(ssl: *mut SSL,
+                                cb: Option<extern fn(c_int, c_int, c_int, *const c_void, size_t,
+                                                     *mut SSL, *mut c_void)>);
+    pub fn SSL_state_string_long(ssl: *const SSL) -> *const c_char;
+    pub fn SSL_alert_type_string_long(value: c_int) -> *const c_char;
+    pub fn SSL_alert_desc_string_long(value: c_int) -> *const c_char;
+    pub fn SSL_shutdown(ssl: *mut SSL) -> c_int;
+    pub fn SSL_get_shutdown(ssl: *const SSL) -> c_int;
+    pub fn SSL_renegotiate(ssl: *mut SSL) -> c_int;
+    pub fn SSL_renegotiate_pending(ssl: *mut SSL) -> c_int;
     pub fn SSL_ctrl(ssl: *mut SSL, cmd: c_int, larg: c_long,
                     parg: *mut c_void) -> c_long;
     pub fn SSL_get_error(ssl: *mut SSL, ret: c_int) -> c_int;
+    pub fn SSL_get_peer_certificate(ssl: *const SSL) -> *mut X509;
+    pub fn SSL_get_peer_cert_chain(ssl: *const SSL) -> *mut _STACK;
+    pub fn SSL_get_client_CA_list(ssl: *const SSL) -> *mut _STACK;
+    pub fn SSL_get_verify_result(ssl: *const SSL) -> c_long;
     pub fn SSL_read(ssl: *mut SSL, buf: *mut c_void, num: c_int) -> c_int;
+    pub fn SSL_pending(ssl: *const SSL) -> c_int;
+    pub fn SSL_peek(ssl: *mut SSL, buf: *mut c_void, num: c_int) -> c_int;
     pub fn SSL_write(ssl: *mut SSL, buf: *const c_void, num: c_int) -> c_int;
     pub fn SSL_get_ex_data_X509_STORE_CTX_idx() -> c_int;
+    pub fn SSL_get_ex_new_index(argl: c_long, argp: *const c_void,
+                                new_func: Option<CRYPTO_EX_new>,
+                                dup_func: Option<CRYPTO_EX_dup>,
+                                free_func: Option<CRYPTO_EX_free>)
+                                -> c_int;
+    pub fn SSL_set_ex_data(ssl: *mut SSL, idx: c_int, data: *mut c_void) -> c_int;
+    pub fn SSL_get_ex_data(ssl: *const SSL, idx: c_int) -> *mut c_void;
     pub fn SSL_get_SSL_CTX(ssl: *mut SSL) -> *mut SSL_CTX;
     pub fn SSL_get_current_compression(ssl: *mut SSL) -> *const COMP_METHOD;
+    pub fn SSL_get_current_cipher(ssl: *const SSL) -> *const SSL_CIPHER;
+    pub fn SSL_get_version(ssl: *const SSL) -> *const c_char;
+    pub fn SSL_get_servername(ssl: *const SSL, name_type: c_int) -> *const c_char;
+    #[cfg(feature = "x509_check_host")]
+    pub fn SSL_get0_param(ssl: *mut SSL) -> *mut X509_VERIFY_PARAM;
+    pub fn SSL_set_SSL_CTX(ssl: *mut SSL, ctx: *mut SSL_CTX) -> *mut SSL_CTX;
+    pub fn SSL_get1_session(ssl: *mut SSL) -> *mut SSL_SESSION;
+    pub fn SSL_export_keying_material(ssl: *mut SSL, out: *mut u8, olen: size_t,
+                                      label: *const c_char, llen: size_t,
+                                      context: *const u8, contextlen: size_t,
+                                      use_context: c_int) -> c_int;
+    pub fn SSL_set_session(ssl: *mut SSL, session: *mut SSL_SESSION) -> c_int;
+
+    pub fn SSL_SESSION_free(s: *mut SSL_SESSION);
+    pub fn SSL_SESSION_get_id(s: *const SSL_SESSION, len: *mut c_uint) -> *const u8;
 
     pub fn SSL_COMP_get_name(comp: *const COMP_METHOD) -> *const c_char;
 
+    pub fn SSL_CIPHER_get_name(cipher: *const SSL_CIPHER) -> *const c_char;
+    pub fn SSL_CIPHER_get_bits(cipher: *const SSL_CIPHER, alg_bits: *mut c_int) -> c_int;
+
     pub fn SSL_CTX_new(method: *const SSL_METHOD) -> *mut SSL_CTX;
     pub fn SSL_CTX_free(ctx: *mut SSL_CTX);
+    pub fn SSL_CTX_ctrl(ctx: *mut SSL_CTX, cmd: c_int, larg: c_long,
+                        parg: *mut c_void) -> c_long;
+    pub fn SSL_CTX_sess_set_new_cb(ctx: *mut SSL_CTX,
+                                   new_session_cb: Option<extern fn(*mut SSL, *mut SSL_SESSION) -> c_int>);
+    pub fn SSL_CTX_sess_set_get_cb(ctx: *mut SSL_CTX,
+                                   get_session_cb: Option<extern fn(*mut SSL, *mut u8, c_int,
+                                                                    *mut c_int) -> *mut SSL_SESSION>);
+    pub fn SSL_CTX_sess_set_remove_cb(ctx: *mut SSL_CTX,
+                                      remove_session_cb: Option<extern fn(*mut SSL_CTX, *mut SSL_SESSION)>);
+    pub fn SSL_CTX_set_cookie_generate_cb(ctx: *mut SSL_CTX,
+                                          cb: Option<extern fn(*mut SSL, *mut u8, *mut c_uint) -> c_int>);
+    pub fn SSL_CTX_set_cookie_verify_cb(ctx: *mut SSL_CTX,
+                                        cb: Option<extern fn(*mut SSL, *mut u8, c_uint) -> c_int>);
+    pub fn SSL_CTX_set_psk_client_callback(ctx: *mut SSL_CTX,
+                                           cb: Option<extern fn(*mut SSL, *const c_char, *mut c_char,
+                                                                c_uint, *mut u8, c_uint) -> c_uint>);
+    pub fn SSL_CTX_set_psk_server_callback(ctx: *mut SSL_CTX,
+                                           cb: Option<extern fn(*mut SSL, *const c_char, *mut u8,
+                                                                c_uint) -> c_uint>);
+    pub fn SSL_CTX_use_psk_identity_hint(ctx: *mut SSL_CTX, hint: *const c_char) -> c_int;
+    pub fn SSL_CTX_set_info_callback(ctx: *mut SSL_CTX,
+                                     cb: Option<extern fn(*const SSL, c_int, c_int)>);
+    pub fn SSL_CTX_get_timeout(ctx: *const SSL_CTX) -> c_long;
+    pub fn SSL_CTX_set_timeout(ctx: *mut SSL_CTX, t: c_long) -> c_long;
+    pub fn SSL_CTX_callback_ctrl(ctx: *mut SSL_CTX, cmd: c_int,
+                                 fp: Option<extern fn()>) -> c_long;
     pub fn SSL_CTX_set_verify(ctx: *mut SSL_CTX, mode: c_int,
                               verify_callback: Option<extern fn(c_int, *mut X509_STORE_CTX) -> c_int>);
     pub fn SSL_CTX_set_verify_depth(ctx: *mut SSL_CTX, depth: c_int);
+    pub fn SSL_CTX_get_verify_depth(ctx: *const SSL_CTX) -> c_int;
     pub fn SSL_CTX_load_verify_locations(ctx: *mut SSL_CTX, CAfile: *const c_char,
                                          CApath: *const c_char) -> c_int;
+    pub fn SSL_CTX_get_cert_store(ctx: *const SSL_CTX) -> *mut X509_STORE;
+    #[cfg(feature = "custom_ext")]
+    pub fn SSL_CTX_add_client_custom_ext(ctx: *mut SSL_CTX, ext_type: c_uint,
+                                         add_cb: Option<custom_ext_add_cb>,
+                                         free_cb: Option<custom_ext_free_cb>,
+                                         add_arg: *mut c_void,
+                                         parse_cb: Option<custom_ext_parse_cb>,
+                                         parse_arg: *mut c_void) -> c_int;
+    #[cfg(feature = "custom_ext")]
+    pub fn SSL_CTX_add_server_custom_ext(ctx: *mut SSL_CTX, ext_type: c_uint,
+                                         add_cb: Option<custom_ext_add_cb>,
+                                         free_cb: Option<custom_ext_free_cb>,
+                                         add_arg: *mut c_void,
+                                         parse_cb: Option<custom_ext_parse_cb>,
+                                         parse_arg: *mut c_void) -> c_int;
+    pub fn SSL_CTX_add_client_CA(ctx: *mut SSL_CTX, x: *mut X509) -> c_int;
+    pub fn SSL_CTX_set_client_CA_list(ctx: *mut SSL_CTX, list: *mut _STACK);
+    pub fn SSL_CTX_set_cert_store(ctx: *mut SSL_CTX, store: *mut X509_STORE);
     pub fn SSL_CTX_get_ex_new_index(argl: c_long, argp: *const c_void,
                                     new_func: Option<CRYPTO_EX_new>,
                                     dup_func: Option<CRYPTO_EX_dup>,
@@ -410,13 +997,23 @@ extern "C" {
                                -> c_int;
     pub fn SSL_CTX_get_ex_data(ctx: *mut SSL_CTX, idx: c_int) -> *mut c_void;
 
+    pub fn SSL_CTX_check_private_key(ctx: *const SSL_CTX) -> c_int;
+    pub fn SSL_CTX_set_cipher_list(ctx: *mut SSL_CTX, s: *const c_char) -> c_int;
+    pub fn SSL_CTX_set_default_verify_paths(ctx: *mut SSL_CTX) -> c_int;
+    pub fn SSL_CTX_set_session_id_context(ctx: *mut SSL_CTX, sid_ctx: *const u8,
+                                          sid_ctx_len: c_uint) -> c_int;
+    pub fn SSL_CTX_use_certificate(ctx: *mut SSL_CTX, x: *mut X509) -> c_int;
     pub fn SSL_CTX_use_certificate_file(ctx: *mut SSL_CTX, cert_file: *const c_char, file_type: c_int) -> c_int;
+    pub fn SSL_CTX_use_PrivateKey(ctx: *mut SSL_CTX, key: *mut EVP_PKEY) -> c_int;
     pub fn SSL_CTX_use_PrivateKey_file(ctx: *mut SSL_CTX, key_file: *const c_char, file_type: c_int) -> c_int;
 
 
     pub fn X509_add_ext(x: *mut X509, ext: *mut X509_EXTENSION, loc: c_int) -> c_int;
     pub fn X509_digest(x: *mut X509, digest: *const EVP_MD, buf: *mut c_char, len: *mut c_uint) -> c_int;
+    pub fn X509_dup(x: *mut X509) -> *mut X509;
     pub fn X509_free(x: *mut X509);
+    pub fn X509_get_ext_d2i(x: *mut X509, nid: c_int, crit: *mut c_int,
+                            idx: *mut c_int) -> *mut c_void;
     pub fn X509_get_serialNumber(x: *mut X509) -> *mut ASN1_INTEGER;
     pub fn X509_get_subject_name(x: *mut X509) -> *mut X509_NAME;
     pub fn X509_gmtime_adj(time: *mut ASN1_TIME, adj: c_long) -> *mut ASN1_TIME;
@@ -427,18 +1024,84 @@ extern "C" {
     pub fn X509_set_version(x: *mut X509, version: c_ulong) -> c_int;
     pub fn X509_set_pubkey(x: *mut X509, pkey: *mut EVP_PKEY) -> c_int;
     pub fn X509_sign(x: *mut X509, pkey: *mut EVP_PKEY, md: *const EVP_MD) -> c_int;
+    pub fn X509_verify_cert(ctx: *mut X509_STORE_CTX) -> c_int;
+    pub fn X509_verify_cert_error_string(n: c_long) -> *const c_char;
 
     pub fn X509_EXTENSION_free(ext: *mut X509_EXTENSION);
 
     pub fn X509_NAME_add_entry_by_txt(x: *mut X509, field: *const c_char, ty: c_int, bytes: *const c_char, len: c_int, loc: c_int, set: c_int) -> c_int;
+    pub fn X509_NAME_dup(name: *mut X509_NAME) -> *mut X509_NAME;
+    pub fn X509_NAME_free(name: *mut X509_NAME);
+    pub fn X509_NAME_oneline(name: *mut X509_NAME, buf: *mut c_char, size: c_int) -> *mut c_char;
+    pub fn X509_NAME_get_entry(name: *mut X509_NAME, loc: c_int) -> *mut X509_NAME_ENTRY;
+    pub fn X509_NAME_get_index_by_NID(name: *mut X509_NAME, nid: c_int, lastpos: c_int) -> c_int;
+
+    pub fn X509_NAME_ENTRY_get_data(ne: *mut X509_NAME_ENTRY) -> *mut ASN1_STRING;
+
+    pub fn X509_STORE_new() -> *mut X509_STORE;
+    pub fn X509_STORE_free(store: *mut X509_STORE);
+    pub fn X509_STORE_add_cert(store: *mut X509_STORE, x: *mut X509) -> c_int;
+    pub fn X509_STORE_load_locations(store: *mut X509_STORE, file: *const c_char,
+                                     dir: *const c_char) -> c_int;
+    pub fn X509_STORE_set_default_paths(store: *mut X509_STORE) -> c_int;
 
+    pub fn X509_STORE_CTX_new() -> *mut X509_STORE_CTX;
+    pub fn X509_STORE_CTX_free(ctx: *mut X509_STORE_CTX);
+    pub fn X509_STORE_CTX_init(ctx: *mut X509_STORE_CTX, store: *mut X509_STORE,
+                               x509: *mut X509, chain: *mut _STACK) -> c_int;
+    pub fn X509_STORE_CTX_get1_chain(ctx: *mut X509_STORE_CTX) -> *mut _STACK;
     pub fn X509_STORE_CTX_get_current_cert(ct: *mut X509_STORE_CTX) -> *mut X509;
     pub fn X509_STORE_CTX_get_error(ctx: *mut X509_STORE_CTX) -> c_int;
+    pub fn X509_STORE_CTX_get_error_depth(ctx: *mut X509_STORE_CTX) -> c_int;
     pub fn X509_STORE_CTX_get_ex_data(ctx: *mut X509_STORE_CTX, idx: c_int) -> *mut c_void;
+    pub fn X509_STORE_CTX_get_ex_new_index(argl: c_long, argp: *const c_void,
+                                           new_func: Option<CRYPTO_EX_new>,
+                                           dup_func: Option<CRYPTO_EX_dup>,
+                                           free_func: Option<CRYPTO_EX_free>)
+                                           -> c_int;
+    pub fn X509_STORE_CTX_set_depth(ctx: *mut X509_STORE_CTX, depth: c_int);
+    pub fn X509_STORE_CTX_set_error(ctx: *mut X509_STORE_CTX, s: c_int);
+    pub fn X509_STORE_CTX_set_ex_data(ctx: *mut X509_STORE_CTX, idx: c_int,
+                                      data: *mut c_void) -> c_int;
+    pub fn X509_STORE_CTX_set_flags(ctx: *mut X509_STORE_CTX, flags: c_ulong);
+    pub fn X509_STORE_CTX_set_time(ctx: *mut X509_STORE_CTX, flags: c_ulong, t: time_t);
+    pub fn X509_STORE_CTX_set_verify_cb(ctx: *mut X509_STORE_CTX,
+                                        verify_cb: Option<extern fn(c_int, *mut X509_STORE_CTX) -> c_int>);
+
+    #[cfg(feature = "x509_check_host")]
+    pub fn X509_VERIFY_PARAM_set_hostflags(param: *mut X509_VERIFY_PARAM, flags: c_uint);
+    #[cfg(feature = "x509_check_host")]
+    pub fn X509_VERIFY_PARAM_set1_host(param: *mut X509_VERIFY_PARAM, name: *const c_char,
+                                       namelen: size_t) -> c_int;
+    #[cfg(feature = "x509_check_host")]
+    pub fn X509_VERIFY_PARAM_set1_ip_asc(param: *mut X509_VERIFY_PARAM,
+                                         ipasc: *const c_char) -> c_int;
 
     pub fn X509V3_EXT_conf_nid(conf: *mut c_void, ctx: *mut X509V3_CTX, ext_nid: c_int, value: *mut c_char) -> *mut X509_EXTENSION;
     pub fn X509V3_set_ctx(ctx: *mut X509V3_CTX, issuer: *mut X509, subject: *mut X509, req: *mut X509_REQ, crl: *mut X509_CRL, flags: c_int);
 
+    pub fn GENERAL_NAMES_free(gens: *mut _STACK);
+
+    pub fn sk_free(st: *mut _STACK);
+    pub fn sk_new_null() -> *mut _STACK;
+    pub fn sk_num(st: *const _STACK) -> c_int;
+    pub fn sk_pop_free(st: *mut _STACK, func: unsafe extern "C" fn(*mut c_void));
+    pub fn sk_push(st: *mut _STACK, data: *mut c_void) -> c_int;
+    pub fn sk_value(st: *const _STACK, i: c_int) -> *mut c_void;
+
+    pub fn d2i_ASN1_OBJECT(a: *mut *mut ASN1_OBJECT, pp: *mut *const u8,
+                           length: c_long) -> *mut ASN1_OBJECT;
+
+    pub fn d2i_X509(a: *mut *mut X509, pp: *mut *const u8, length: c_long) -> *mut X509;
+    pub fn i2d_X509(x: *mut X509, pp: *mut *mut u8) -> c_int;
+
+    pub fn d2i_DHparams(a: *mut *mut DH, pp: *mut *const u8, length: c_long) -> *mut DH;
+    pub fn i2d_DHparams(a: *const DH, pp: *mut *mut u8) -> c_int;
+
+    pub fn d2i_SSL_SESSION(a: *mut *mut SSL_SESSION, pp: *mut *const u8,
+                           length: c_long) -> *mut SSL_SESSION;
+    pub fn i2d_SSL_SESSION(s: *mut SSL_SESSION, pp: *mut *mut u8) -> c_int;
+
     pub fn i2d_RSA_PUBKEY(k: *mut RSA, buf: *const *mut u8) -> c_int;
     pub fn d2i_RSA_PUBKEY(k: *const *mut RSA, buf: *const *const u8, len: c_uint) -> *mut RSA;
     pub fn i2d_RSAPrivateKey(k: *mut RSA, buf: *const *mut u8) -> c_int;
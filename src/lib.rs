@@ -10,11 +10,51 @@ extern crate serialize;
 extern crate sync;
 
 mod macros;
+mod util;
+
+use ssl::error::SslError;
 
 pub mod asn1;
 pub mod bn;
 pub mod bio;
+pub mod conf;
 pub mod crypto;
+pub mod dh;
+pub mod engine;
 pub mod ffi;
+pub mod pem;
 pub mod ssl;
+pub mod version;
 pub mod x509;
+
+/// Initializes the library.
+///
+/// This loads error strings, registers all algorithms and installs the
+/// callbacks needed for multithreaded use. It is called automatically as
+/// needed, but may be called explicitly to control when the work happens.
+/// Calls after the first have no effect.
+///
+/// The default OpenSSL configuration file is not loaded; see
+/// `init_with_config`.
+pub fn init() {
+    ffi::init();
+}
+
+/// Initializes the library as `init` does, and then applies the default
+/// OpenSSL configuration file as the `openssl` command line tool does.
+///
+/// The configuration can change process wide settings such as the default
+/// engines, so this is meant for applications rather than libraries. A
+/// missing file is ignored, but an invalid one is reported as an error.
+pub fn init_with_config() -> Result<(), SslError> {
+    ffi::init();
+    conf::load_default_file()
+}
+
+/// Frees the library's global state, e.g. before unloading it.
+///
+/// The library cannot be used again once this has been called, and it is not
+/// safe to call while other threads may be using it.
+pub unsafe fn cleanup() {
+    ffi::cleanup();
+}
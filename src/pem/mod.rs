@@ -0,0 +1,138 @@
+use libc::{c_char, c_long};
+use std::c_str::CString;
+use std::io::{Reader, Writer};
+use std::ptr;
+use std::slice;
+
+use bio::{MemBio};
+use ffi;
+use ssl::error::{SslError, StreamError};
+
+/// An iterator over the objects stored in a PEM encoded bundle
+///
+/// Each item is the label of the object (e.g. `CERTIFICATE`, `PRIVATE KEY`
+/// or `X509 CRL`) along with the decoded bytes between its `BEGIN` and `END`
+/// lines. For unencrypted objects those bytes are plain DER. Iteration stops
+/// at the end of the input, or after the first malformed object has been
+/// reported as an error.
+///
+/// # Example
+///
+/// ```
+/// use std::io::File;
+///
+/// use openssl::pem::PemIter;
+///
+/// let mut file = File::open(&Path::new("test/cert.pem")).unwrap();
+/// for item in PemIter::from_reader(&mut file).unwrap() {
+///     let (label, der) = item.unwrap();
+///     println!("{}: {} bytes", label, der.len());
+/// }
+/// ```
+pub struct PemIter {
+    bio: MemBio,
+    done: bool
+}
+
+impl PemIter {
+    /// Creates an iterator over the PEM objects in `data`
+    pub fn new(data: &[u8]) -> Result<PemIter, SslError> {
+        let mut bio = try!(MemBio::new());
+        try!(bio.write(data).map_err(StreamError));
+
+        Ok(PemIter {
+            bio: bio,
+            done: false
+        })
+    }
+
+    /// Creates an iterator over the PEM objects read from `reader`
+    pub fn from_reader(reader: &mut Reader) -> Result<PemIter, SslError> {
+        let data = try!(reader.read_to_end().map_err(StreamError));
+        PemIter::new(data.as_slice())
+    }
+}
+
+impl Iterator<Result<(String, Vec<u8>), SslError>> for PemIter {
+    fn next(&mut self) -> Option<Result<(String, Vec<u8>), SslError>> {
+        if self.done {
+            return None;
+        }
+
+        let mut name: *mut c_char = ptr::null_mut();
+        let mut header: *mut c_char = ptr::null_mut();
+        let mut data: *mut u8 = ptr::null_mut();
+        let mut len: c_long = 0;
+
+        let ret = unsafe {
+            ffi::PEM_read_bio(self.bio.get_handle(), &mut name, &mut header,
+                              &mut data, &mut len)
+        };
+
+        if ret == 0 {
+            self.done = true;
+
+            // Running out of input is reported by OpenSSL as a missing start
+            // line, which is how a well formed bundle ends.
+            let err = unsafe { ffi::ERR_peek_last_error() };
+            if ffi::ERR_GET_LIB(err) == ffi::ERR_LIB_PEM &&
+                    ffi::ERR_GET_REASON(err) == ffi::PEM_R_NO_START_LINE {
+                unsafe { ffi::ERR_clear_error() };
+                return None;
+            }
+            return Some(Err(SslError::get()));
+        }
+
+        unsafe {
+            let label = CString::new(name as *const c_char, false).to_string();
+            let der = slice::raw::buf_as_slice(data as *const u8, len as uint,
+                                               |der| der.to_vec());
+
+            ffi::CRYPTO_free(name as *const c_char);
+            ffi::CRYPTO_free(header as *const c_char);
+            ffi::CRYPTO_free(data as *const c_char);
+
+            Some(Ok((label, der)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, Reader};
+
+    use super::PemIter;
+
+    #[test]
+    fn test_bundle() {
+        let cert = File::open(&Path::new("test/cert.pem")).read_to_end().unwrap();
+        let key = File::open(&Path::new("test/key.pem")).read_to_end().unwrap();
+        let mut bundle = cert.clone();
+        bundle.push_all(key.as_slice());
+        bundle.push_all(cert.as_slice());
+
+        let labels: Vec<String> = PemIter::new(bundle.as_slice()).unwrap()
+            .map(|item| item.unwrap().val0())
+            .collect();
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels[0].as_slice(), "CERTIFICATE");
+        assert_eq!(labels[2].as_slice(), "CERTIFICATE");
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut iter = PemIter::new([]).unwrap();
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let data = b"-----BEGIN CERTIFICATE-----\nnot base64!\n-----END CERTIFICATE-----\n";
+        let mut iter = PemIter::new(data).unwrap();
+        match iter.next() {
+            Some(Err(_)) => {}
+            _ => fail!("expected an error")
+        }
+        assert!(iter.next().is_none());
+    }
+}
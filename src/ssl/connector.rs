@@ -0,0 +1,433 @@
+//! Preconfigured SSL clients and servers.
+//!
+//! `SslConnector` and `SslAcceptor` wrap an `SslContext` set up with secure
+//! defaults: SSLv2, SSLv3 and compression are disabled, only strong cipher
+//! suites are offered, and clients verify both the server's certificate and
+//! that it was issued for the host being connected to.
+//!
+//! ```no_run
+//! use std::io::net::tcp::TcpStream;
+//!
+//! use openssl::ssl::connector::SslConnectorBuilder;
+//!
+//! let connector = SslConnectorBuilder::new().unwrap().build();
+//! let stream = TcpStream::connect("google.com", 443).unwrap();
+//! let mut stream = connector.connect("google.com", stream).unwrap();
+//! stream.write(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+//! stream.flush().unwrap();
+//! ```
+
+use libc::c_int;
+use std::ascii::AsciiExt;
+use std::io::{Reader, Writer};
+use std::io::net::ip::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ptr;
+use std::slice;
+
+use crypto::pkey::PKey;
+use ffi;
+use ssl::{Ssl, SslContext, SslStream, SslMethod, Sslv23, SslVerifyPeer, SslVerifyNone, CurveP256};
+use ssl::{SslVersion, Ssl3, Tls1, Tls1_2};
+use ssl::{SSL_OP_NO_SSLV2, SSL_OP_NO_SSLV3, SSL_OP_NO_COMPRESSION};
+use ssl::{SSL_OP_CIPHER_SERVER_PREFERENCE, SSL_OP_SINGLE_DH_USE, SSL_OP_SINGLE_ECDH_USE};
+use ssl::{SSL_SESS_CACHE_CLIENT, SSL_SESS_CACHE_SERVER};
+use ssl::error::{SslError, HostnameMismatch};
+#[cfg(feature = "x509_check_host")]
+use ssl::error::VerifyError;
+use x509::X509;
+#[cfg(feature = "x509_check_host")]
+use x509::{X509HostnameMismatch, X509IpAddressMismatch};
+
+/// The cipher suites offered by default, preferring forward secrecy and
+/// AEAD modes and excluding anything weak or unauthenticated
+pub static DEFAULT_CIPHERS: &'static str =
+    "ECDH+AESGCM:DH+AESGCM:ECDH+AES256:DH+AES256:ECDH+AES128:DH+AES:\
+     RSA+AESGCM:RSA+AES:!aNULL:!eNULL:!MD5:!DSS:!RC4";
+
+/// A configuration recommended by Mozilla's Server Side TLS guidelines
+///
+/// The profiles trade compatibility with old clients against security, and
+/// are kept up to date with the guidelines by this crate.
+#[deriving(Show, Clone, PartialEq, Eq)]
+pub enum MozillaProfile {
+    /// TLS 1.2 with forward secret AEAD cipher suites only, for services
+    /// whose clients are all recent
+    MozillaModern,
+    /// TLS 1.0 to 1.2, preferring forward secret AEAD cipher suites, for
+    /// general purpose servers
+    MozillaIntermediate,
+    /// SSLv3 to TLS 1.2 including 3DES, for services which must support very
+    /// old clients such as Windows XP
+    MozillaOld
+}
+
+static MODERN_CIPHERS: &'static str =
+    "ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:\
+     ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:\
+     ECDHE-ECDSA-AES256-SHA384:ECDHE-RSA-AES256-SHA384:\
+     ECDHE-ECDSA-AES128-SHA256:ECDHE-RSA-AES128-SHA256";
+
+static INTERMEDIATE_CIPHERS: &'static str =
+    "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:\
+     ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:\
+     DHE-RSA-AES128-GCM-SHA256:DHE-RSA-AES256-GCM-SHA384:\
+     ECDHE-ECDSA-AES128-SHA256:ECDHE-RSA-AES128-SHA256:ECDHE-ECDSA-AES128-SHA:\
+     ECDHE-RSA-AES256-SHA384:ECDHE-RSA-AES128-SHA:ECDHE-ECDSA-AES256-SHA384:\
+     ECDHE-ECDSA-AES256-SHA:ECDHE-RSA-AES256-SHA:DHE-RSA-AES128-SHA256:\
+     DHE-RSA-AES128-SHA:DHE-RSA-AES256-SHA256:DHE-RSA-AES256-SHA:\
+     ECDHE-ECDSA-DES-CBC3-SHA:ECDHE-RSA-DES-CBC3-SHA:EDH-RSA-DES-CBC3-SHA:\
+     AES128-GCM-SHA256:AES256-GCM-SHA384:AES128-SHA256:AES256-SHA256:\
+     AES128-SHA:AES256-SHA:DES-CBC3-SHA:!DSS";
+
+static OLD_CIPHERS: &'static str =
+    "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:\
+     ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:\
+     DHE-RSA-AES128-GCM-SHA256:DHE-RSA-AES256-GCM-SHA384:\
+     ECDHE-ECDSA-AES128-SHA256:ECDHE-RSA-AES128-SHA256:ECDHE-ECDSA-AES128-SHA:\
+     ECDHE-RSA-AES256-SHA384:ECDHE-RSA-AES128-SHA:ECDHE-ECDSA-AES256-SHA384:\
+     ECDHE-ECDSA-AES256-SHA:ECDHE-RSA-AES256-SHA:DHE-RSA-AES128-SHA256:\
+     DHE-RSA-AES128-SHA:DHE-RSA-AES256-SHA256:DHE-RSA-AES256-SHA:\
+     ECDHE-ECDSA-DES-CBC3-SHA:ECDHE-RSA-DES-CBC3-SHA:EDH-RSA-DES-CBC3-SHA:\
+     AES128-GCM-SHA256:AES256-GCM-SHA384:AES128-SHA256:AES256-SHA256:\
+     AES128-SHA:AES256-SHA:DES-CBC3-SHA:HIGH:SEED:!aNULL:!eNULL:!EXPORT:\
+     !DES:!RC4:!MD5:!PSK:!aDH:!aECDH:!SRP";
+
+impl MozillaProfile {
+    /// Returns the lowest protocol version the profile allows.
+    pub fn min_version(&self) -> SslVersion {
+        match *self {
+            MozillaModern => Tls1_2,
+            MozillaIntermediate => Tls1,
+            MozillaOld => Ssl3
+        }
+    }
+
+    /// Returns the profile's cipher list, in OpenSSL's format.
+    pub fn ciphers(&self) -> &'static str {
+        match *self {
+            MozillaModern => MODERN_CIPHERS,
+            MozillaIntermediate => INTERMEDIATE_CIPHERS,
+            MozillaOld => OLD_CIPHERS
+        }
+    }
+
+    /// Configures `ctx`, which should have been created with `Sslv23`, with
+    /// the profile's protocol versions, cipher suites and options.
+    ///
+    /// Servers only offer the profile's DHE suites once given parameters
+    /// with `set_tmp_dh`; the guidelines call for at least 2048 bits.
+    pub fn apply(&self, ctx: &mut SslContext) -> Result<(), SslError> {
+        try!(lift(ctx.set_protocol_range(self.min_version(), Tls1_2)));
+        try!(lift(ctx.set_cipher_list(self.ciphers())));
+        ctx.set_options(SSL_OP_NO_COMPRESSION | SSL_OP_CIPHER_SERVER_PREFERENCE |
+                        SSL_OP_SINGLE_DH_USE | SSL_OP_SINGLE_ECDH_USE);
+        lift(ctx.set_tmp_ecdh(CurveP256))
+    }
+}
+
+fn lift(err: Option<SslError>) -> Result<(), SslError> {
+    match err {
+        None => Ok(()),
+        Some(err) => Err(err)
+    }
+}
+
+fn ctx(method: SslMethod) -> Result<SslContext, SslError> {
+    let mut ctx = try!(SslContext::new(method));
+    ctx.set_options(SSL_OP_NO_SSLV2 | SSL_OP_NO_SSLV3 | SSL_OP_NO_COMPRESSION);
+    try!(lift(ctx.set_cipher_list(DEFAULT_CIPHERS)));
+    Ok(ctx)
+}
+
+/// A builder for `SslConnector`s
+pub struct SslConnectorBuilder {
+    ctx: SslContext,
+    verify_hostname: bool
+}
+
+impl SslConnectorBuilder {
+    /// Creates a builder with secure defaults, trusting the system's CA
+    /// certificates.
+    pub fn new() -> Result<SslConnectorBuilder, SslError> {
+        let mut ctx = try!(ctx(Sslv23));
+        try!(lift(ctx.set_default_verify_paths()));
+        ctx.set_verify(SslVerifyPeer, None);
+        ctx.set_session_cache_mode(SSL_SESS_CACHE_CLIENT);
+        Ok(SslConnectorBuilder { ctx: ctx, verify_hostname: true })
+    }
+
+    /// Returns the underlying context, for settings not covered by the
+    /// defaults such as additional trusted CAs or a client certificate.
+    pub fn context_mut(&mut self) -> &mut SslContext {
+        &mut self.ctx
+    }
+
+    /// Sets whether `connect` checks that the server's certificate was
+    /// issued for the domain being connected to. Defaults to `true`.
+    ///
+    /// Only disable this when the certificate is checked some other way,
+    /// e.g. by pinning it; otherwise any certificate issued by a trusted CA
+    /// is accepted for any server.
+    pub fn set_verify_hostname(&mut self, verify: bool) {
+        self.verify_hostname = verify;
+    }
+
+    /// Creates the connector.
+    pub fn build(self) -> SslConnector {
+        SslConnector { ctx: self.ctx, verify_hostname: self.verify_hostname }
+    }
+}
+
+/// A client which verifies servers' certificates and host names
+pub struct SslConnector {
+    ctx: SslContext,
+    verify_hostname: bool
+}
+
+impl SslConnector {
+    /// Performs a handshake over `stream` with the server `domain`.
+    ///
+    /// `domain` is either a host name, which is sent with SNI and must match
+    /// a DNS name in the server's certificate, or its common name if it has
+    /// none, or an IP address, which must match an IP address in the
+    /// certificate.
+    pub fn connect<S: Reader + Writer>(&self, domain: &str, stream: S)
+                              -> Result<SslStream<S>, SslError> {
+        let ssl = try!(Ssl::new(&self.ctx));
+        // SNI only carries host names
+        if from_str::<IpAddr>(domain).is_none() {
+            try!(ssl.set_hostname(domain));
+        }
+        if !self.verify_hostname {
+            return SslStream::new_from(ssl, stream);
+        }
+        handshake_verifying_hostname(ssl, domain, stream)
+    }
+}
+
+// With OpenSSL 1.0.2 the name is checked along with the rest of the chain,
+// failing the handshake itself
+#[cfg(feature = "x509_check_host")]
+fn handshake_verifying_hostname<S: Reader + Writer>(ssl: Ssl, domain: &str, stream: S)
+                                                    -> Result<SslStream<S>, SslError> {
+    try!(ssl.set_verify_hostname(domain));
+    match SslStream::new_from(ssl, stream) {
+        Err(VerifyError(X509HostnameMismatch, _)) |
+        Err(VerifyError(X509IpAddressMismatch, _)) => Err(HostnameMismatch(domain.to_string())),
+        res => res
+    }
+}
+
+#[cfg(not(feature = "x509_check_host"))]
+fn handshake_verifying_hostname<S: Reader + Writer>(ssl: Ssl, domain: &str, stream: S)
+                                                    -> Result<SslStream<S>, SslError> {
+    let stream = try!(SslStream::new_from(ssl, stream));
+    match stream.get_ssl().get_peer_certificate() {
+        Some(ref cert) if verify_hostname(cert, domain) => Ok(stream),
+        _ => Err(HostnameMismatch(domain.to_string()))
+    }
+}
+
+/// A builder for `SslAcceptor`s
+pub struct SslAcceptorBuilder {
+    ctx: SslContext
+}
+
+impl SslAcceptorBuilder {
+    /// Creates a builder with secure defaults, serving `cert` with the
+    /// matching private key `key`.
+    ///
+    /// Clients are not asked for certificates unless the context is
+    /// reconfigured with `context_mut`.
+    pub fn new(cert: &X509, key: &PKey) -> Result<SslAcceptorBuilder, SslError> {
+        SslAcceptorBuilder::new_with_chain(cert, &[], key)
+    }
+
+    /// Like `new`, but also sends the intermediate certificates of `chain`,
+    /// ordered from the issuer of `cert` up towards the root, so that
+    /// clients only need to trust the root.
+    pub fn new_with_chain(cert: &X509, chain: &[X509], key: &PKey)
+                          -> Result<SslAcceptorBuilder, SslError> {
+        let mut ctx = try!(ctx(Sslv23));
+        ctx.set_options(SSL_OP_CIPHER_SERVER_PREFERENCE | SSL_OP_SINGLE_DH_USE |
+                        SSL_OP_SINGLE_ECDH_USE);
+        ctx.set_verify(SslVerifyNone, None);
+        ctx.set_session_cache_mode(SSL_SESS_CACHE_SERVER);
+
+        try!(lift(ctx.set_tmp_ecdh(CurveP256)));
+        try!(lift(ctx.set_session_id_context(b"rust-openssl")));
+        try!(lift(ctx.set_certificate(cert)));
+        for cert in chain.iter() {
+            try!(lift(ctx.add_extra_chain_cert(cert)));
+        }
+        try!(lift(ctx.set_private_key(key)));
+        try!(lift(ctx.check_private_key()));
+        Ok(SslAcceptorBuilder { ctx: ctx })
+    }
+
+    /// Returns the underlying context, e.g. to set Diffie-Hellman parameters
+    /// or require client certificates.
+    pub fn context_mut(&mut self) -> &mut SslContext {
+        &mut self.ctx
+    }
+
+    /// Creates the acceptor.
+    pub fn build(self) -> SslAcceptor {
+        SslAcceptor { ctx: self.ctx }
+    }
+}
+
+/// A server
+pub struct SslAcceptor {
+    ctx: SslContext
+}
+
+impl SslAcceptor {
+    /// Performs a handshake over `stream` with a connecting client.
+    pub fn accept<S: Reader + Writer>(&self, stream: S) -> Result<SslStream<S>, SslError> {
+        SslStream::new_server(&self.ctx, stream)
+    }
+}
+
+/// Checks that `cert` was issued for the host `name`.
+///
+/// A host name is matched against the DNS names of the certificate's subject
+/// alternative name extension, or its common names if it has none, following
+/// RFC 6125: names are compared case insensitively, and a wildcard may only
+/// make up the whole of the leftmost label of a name with at least three
+/// labels. An IP address is matched against the extension's IP addresses.
+///
+/// `SslConnector` does this automatically; it is only needed by clients
+/// setting up connections themselves.
+pub fn verify_hostname(cert: &X509, name: &str) -> bool {
+    match from_str::<IpAddr>(name) {
+        Some(ip) => {
+            let ip = ip_bytes(ip);
+            alt_names(cert, ffi::GEN_IPADD).iter().any(|addr| *addr == ip)
+        }
+        None => {
+            cert_names(cert).iter().any(|pattern| matches_hostname(pattern.as_slice(), name))
+        }
+    }
+}
+
+// An IP address in network byte order, as in the subject alternative name
+// extension
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        Ipv4Addr(a, b, c, d) => vec![a, b, c, d],
+        Ipv6Addr(a, b, c, d, e, f, g, h) => {
+            let mut bytes = vec![];
+            for segment in [a, b, c, d, e, f, g, h].iter() {
+                bytes.push((*segment >> 8) as u8);
+                bytes.push(*segment as u8);
+            }
+            bytes
+        }
+    }
+}
+
+// The entries of type `type_` in the certificate's subject alternative name
+// extension
+fn alt_names(cert: &X509, type_: c_int) -> Vec<Vec<u8>> {
+    let mut names = vec![];
+    unsafe {
+        let gens = ffi::X509_get_ext_d2i(cert.get_handle(), ffi::NID_subject_alt_name,
+                                         ptr::null_mut(), ptr::null_mut());
+        if gens.is_null() {
+            return names;
+        }
+        let gens = gens as *mut ffi::_STACK;
+        for i in range(0, ffi::sk_num(gens as *const ffi::_STACK)) {
+            let gen = ffi::sk_value(gens as *const ffi::_STACK, i) as *mut ffi::GENERAL_NAME;
+            if (*gen).type_ == type_ {
+                names.push(asn1_bytes((*gen).d as *mut ffi::ASN1_STRING));
+            }
+        }
+        ffi::GENERAL_NAMES_free(gens);
+    }
+    names
+}
+
+// The DNS names of the certificate's subject alternative name extension, or
+// its common names if there are none
+fn cert_names(cert: &X509) -> Vec<String> {
+    let mut names: Vec<String> = alt_names(cert, ffi::GEN_DNS).iter().map(|name| {
+        String::from_utf8_lossy(name.as_slice()).into_string()
+    }).collect();
+    if !names.is_empty() {
+        return names;
+    }
+
+    unsafe {
+        let subject = ffi::X509_get_subject_name(cert.get_handle());
+        let mut idx = -1;
+        loop {
+            idx = ffi::X509_NAME_get_index_by_NID(subject, ffi::NID_commonName, idx);
+            if idx < 0 {
+                break;
+            }
+            let entry = ffi::X509_NAME_get_entry(subject, idx);
+            names.push(asn1_string(ffi::X509_NAME_ENTRY_get_data(entry)));
+        }
+    }
+    names
+}
+
+unsafe fn asn1_bytes(s: *mut ffi::ASN1_STRING) -> Vec<u8> {
+    let data = ffi::ASN1_STRING_data(s);
+    let len = ffi::ASN1_STRING_length(s as *const ffi::ASN1_STRING);
+    slice::raw::buf_as_slice(data as *const u8, len as uint, |s| s.to_vec())
+}
+
+unsafe fn asn1_string(s: *mut ffi::ASN1_STRING) -> String {
+    String::from_utf8_lossy(asn1_bytes(s).as_slice()).into_string()
+}
+
+// Matches a certificate name against a host name following RFC 6125: names
+// are compared case insensitively, and a wildcard may only make up the whole
+// of the leftmost label of a name with at least three labels
+fn matches_hostname(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_right_chars('.').to_ascii_lower();
+    let host = host.trim_right_chars('.').to_ascii_lower();
+    if pattern.is_empty() || host.is_empty() {
+        return false;
+    }
+
+    if !pattern.as_slice().starts_with("*.") {
+        return pattern == host;
+    }
+
+    let suffix = pattern.as_slice().slice_from(1);
+    if suffix.slice_from(1).contains_char('*') || suffix.split('.').count() < 3 {
+        return false;
+    }
+    match host.as_slice().find('.') {
+        Some(dot) if dot > 0 => host.as_slice().slice_from(dot) == suffix,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches_hostname;
+
+    #[test]
+    fn test_exact_match() {
+        assert!(matches_hostname("example.com", "example.com"));
+        assert!(matches_hostname("Example.COM", "example.com"));
+        assert!(matches_hostname("example.com.", "example.com"));
+        assert!(!matches_hostname("example.com", "www.example.com"));
+        assert!(!matches_hostname("", ""));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(matches_hostname("*.example.com", "www.example.com"));
+        assert!(!matches_hostname("*.example.com", "example.com"));
+        assert!(!matches_hostname("*.example.com", "a.b.example.com"));
+        assert!(!matches_hostname("*.com", "example.com"));
+        assert!(!matches_hostname("w*.example.com", "www.example.com"));
+        assert!(!matches_hostname("*.*.example.com", "a.b.example.com"));
+    }
+}
@@ -0,0 +1,296 @@
+//! DTLS, TLS for datagram transports such as UDP.
+//!
+//! A `DtlsStream` runs over a `DatagramStream`, whose reads and writes each
+//! carry a single datagram, e.g. a UDP socket exchanging datagrams with one
+//! peer. Lost handshake messages are retransmitted as long as the stream
+//! supports read timeouts.
+
+use libc::{c_int, c_void};
+use std::cmp;
+use std::io::{IoResult, IoError, Reader, Writer};
+
+use ffi;
+use ssl::{Ssl, SslEngine, read_error, write_error, ssl_io_error, would_block};
+use ssl::error::{SslError, WantRead};
+
+/// A transport carrying one datagram per read or write
+pub trait DatagramStream: Reader + Writer {
+    /// Makes reads time out after `timeout_ms` milliseconds, or never if
+    /// it's None.
+    ///
+    /// DTLS uses this to retransmit handshake messages which may have been
+    /// lost. Streams which can't time out may leave it unimplemented, but
+    /// their handshakes will then wait forever if a datagram is lost.
+    fn set_read_timeout(&mut self, _timeout_ms: Option<u64>) {}
+}
+
+/// The maximum size of the datagrams sent unless set with `Ssl::set_mtu`,
+/// which fits in an Ethernet frame along with the IP and UDP headers
+pub static DEFAULT_MTU: uint = 1400;
+
+// The length of a DTLS record header: the content type, version, epoch,
+// sequence number and length
+static RECORD_HEADER_LEN: uint = 13;
+
+// The most data a record can carry, so writes are split into chunks of this
+// size
+static MAX_PLAINTEXT_LEN: uint = 16 * 1024;
+
+// The length of the largest record, whose body may add up to 2048 bytes of
+// MAC and padding to its data
+static MAX_RECORD_LEN: uint = RECORD_HEADER_LEN + MAX_PLAINTEXT_LEN + 2048;
+
+/// A DTLS connection over a datagram transport
+pub struct DtlsStream<S> {
+    engine: SslEngine,
+    stream: S,
+    buf: Vec<u8>,
+    // Records not yet accepted by the underlying stream
+    write_buf: Vec<u8>,
+    // An error sending records after an operation succeeded, which is
+    // returned by the next one
+    write_err: Option<IoError>,
+    mtu: uint
+}
+
+impl<S: DatagramStream> DtlsStream<S> {
+    fn new_base(engine: SslEngine, stream: S) -> DtlsStream<S> {
+        let mtu = engine.get_ssl().get_mtu().unwrap_or(DEFAULT_MTU);
+        engine.get_ssl().set_mtu(mtu);
+
+        DtlsStream {
+            engine: engine,
+            stream: stream,
+            buf: Vec::from_elem(MAX_RECORD_LEN, 0u8),
+            write_buf: vec![],
+            write_err: None,
+            mtu: mtu
+        }
+    }
+
+    /// Performs the client side of the handshake over `stream`.
+    pub fn connect(ssl: Ssl, stream: S) -> Result<DtlsStream<S>, SslError> {
+        let mut stream = DtlsStream::new_base(SslEngine::new_client(ssl), stream);
+        try!(stream.io(|engine| { engine.handshake() }));
+        Ok(stream)
+    }
+
+    /// Performs the server side of the handshake over `stream`.
+    pub fn accept(ssl: Ssl, stream: S) -> Result<DtlsStream<S>, SslError> {
+        let mut stream = DtlsStream::new_base(SslEngine::new_server(ssl), stream);
+        try!(stream.io(|engine| { engine.handshake() }));
+        Ok(stream)
+    }
+
+    /// Performs the server side of the handshake over `stream`, first
+    /// making the client prove it can receive at its address by echoing a
+    /// cookie.
+    ///
+    /// The context must have cookie callbacks set with
+    /// `SslContext::set_cookie_callbacks`.
+    pub fn listen(ssl: Ssl, stream: S) -> Result<DtlsStream<S>, SslError> {
+        let mut stream = DtlsStream::new_base(SslEngine::new_server(ssl), stream);
+        // Filled in with the peer's address, which memory BIOs don't know
+        let mut peer = [0u8, ..128];
+        try!(stream.io(|engine| {
+            let ret = unsafe {
+                // This is defined as a macro:
+                //      #define DTLSv1_listen(ssl, peer) \
+                //          SSL_ctrl(ssl,DTLS_CTRL_LISTEN,0, (void *)peer)
+                ffi::SSL_ctrl(engine.ssl.ssl, ffi::DTLS_CTRL_LISTEN, 0,
+                              peer.as_mut_ptr() as *mut c_void)
+            };
+            engine.result(ret as c_int)
+        }));
+        try!(stream.io(|engine| { engine.handshake() }));
+        Ok(stream)
+    }
+
+    /// Returns the `Ssl` object associated with this stream.
+    pub fn get_ssl(&self) -> &Ssl {
+        self.engine.get_ssl()
+    }
+
+    /// Returns the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the underlying stream, e.g. to set timeouts.
+    ///
+    /// Reading from or writing to it directly will corrupt the DTLS session.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    // Runs `op`, sending the records it produces and receiving datagrams
+    // until it has the data it needs
+    fn io<T>(&mut self, op: |&mut SslEngine| -> Result<T, SslError>) -> Result<T, SslError> {
+        match self.write_err.take() {
+            Some(err) => return Err(write_error(err)),
+            None => {}
+        }
+
+        loop {
+            let ret = op(&mut self.engine);
+            match self.write_records() {
+                Ok(()) => {}
+                // The operation can't be undone, so its result is returned
+                // and the error left for the next call
+                Err(err) => match ret {
+                    Ok(_) => {
+                        self.write_err = Some(err);
+                        return ret;
+                    }
+                    Err(_) => return Err(write_error(err))
+                }
+            }
+
+            match ret {
+                Err(WantRead(_)) => try!(self.read_datagram()),
+                ret => return ret
+            }
+        }
+    }
+
+    // Waits for a datagram, retransmitting the last handshake flight if the
+    // peer doesn't answer in time
+    fn read_datagram(&mut self) -> Result<(), SslError> {
+        let timeout = self.engine.get_ssl().get_dtls_timeout();
+        if timeout.is_some() {
+            self.stream.set_read_timeout(timeout);
+        }
+        let ret = self.stream.read(self.buf.as_mut_slice());
+        if timeout.is_some() {
+            self.stream.set_read_timeout(None);
+        }
+
+        match ret {
+            Ok(len) => {
+                self.engine.read_tls(self.buf.slice_to(len));
+                Ok(())
+            }
+            // The retransmission is sent once the caller retries
+            Err(ref err) if timeout.is_some() && would_block(err) => {
+                try!(self.engine.get_ssl().handle_dtls_timeout());
+                Ok(())
+            }
+            Err(err) => Err(read_error(err))
+        }
+    }
+
+    /// Sets the maximum size of the datagrams sent from now on, e.g. after
+    /// discovering the path MTU.
+    pub fn set_mtu(&mut self, mtu: uint) {
+        self.engine.get_ssl().set_mtu(mtu);
+        self.mtu = mtu;
+    }
+
+    fn write_records(&mut self) -> IoResult<()> {
+        loop {
+            match self.engine.write_tls(self.buf.as_mut_slice()) {
+                Some(len) => self.write_buf.push_all(self.buf.slice_to(len)),
+                None => break
+            }
+        }
+
+        let mut start = 0;
+        let mut ret = Ok(());
+        while start < self.write_buf.len() {
+            let len = datagram_len(self.write_buf.slice_from(start), self.mtu);
+            ret = self.stream.write(self.write_buf.slice(start, start + len));
+            if ret.is_err() {
+                break;
+            }
+            start += len;
+        }
+
+        // Keep the records the stream didn't accept
+        if start == self.write_buf.len() {
+            self.write_buf.clear();
+        } else if start > 0 {
+            self.write_buf = self.write_buf.slice_from(start).to_vec();
+        }
+        ret
+    }
+}
+
+// The length of the records at the start of `records` which fit in one
+// datagram, which is always at least one record
+fn datagram_len(records: &[u8], mtu: uint) -> uint {
+    let mut len = 0;
+    while len + RECORD_HEADER_LEN <= records.len() {
+        let body_len = ((records[len + 11] as uint) << 8) | records[len + 12] as uint;
+        let record_len = RECORD_HEADER_LEN + body_len;
+        if len > 0 && len + record_len > mtu {
+            break;
+        }
+        len += record_len;
+    }
+
+    // OpenSSL only writes whole records, so this is just a precaution
+    if len == 0 || len > records.len() {
+        records.len()
+    } else {
+        len
+    }
+}
+
+impl<S: DatagramStream> Reader for DtlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let ret = self.io(|engine| {
+            let ret = engine.ssl.read(buf);
+            engine.result(ret)
+        });
+        ret.map(|len| len as uint).map_err(ssl_io_error)
+    }
+}
+
+impl<S: DatagramStream> Writer for DtlsStream<S> {
+    /// Sends `buf` as a single record, and so in a single datagram, unless
+    /// it's longer than a record can hold.
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut start = 0;
+        while start < buf.len() {
+            let end = cmp::min(buf.len(), start + MAX_PLAINTEXT_LEN);
+            let ret = self.io(|engine| {
+                let ret = engine.ssl.write(buf.slice(start, end));
+                engine.result(ret)
+            });
+            start += try!(ret.map_err(ssl_io_error)) as uint;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        match self.write_err.take() {
+            Some(err) => return Err(err),
+            None => {}
+        }
+        try!(self.write_records());
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::datagram_len;
+
+    fn record(len: uint) -> Vec<u8> {
+        let mut record = vec![23u8, 0xfe, 0xff, 0, 1, 0, 0, 0, 0, 0, 1,
+                              (len >> 8) as u8, len as u8];
+        record.grow(len, 0);
+        record
+    }
+
+    #[test]
+    fn test_datagram_len() {
+        let mut records = record(100);
+        records.push_all(record(200).as_slice());
+        assert_eq!(datagram_len(records.as_slice(), 1400), 326);
+        assert_eq!(datagram_len(records.as_slice(), 200), 113);
+
+        let big = record(2000);
+        assert_eq!(datagram_len(big.as_slice(), 1400), 2013);
+    }
+}
@@ -1,22 +1,101 @@
-use libc::c_ulong;
+use libc::{c_char, c_int, c_ulong, size_t};
+use std::error::{Error, FromError};
+use std::fmt;
 use std::io::IoError;
-use std::c_str::CString;
 
 use ffi;
+use util::c_str_to_string;
+use x509::{X509ValidationError, X509CertHasExpired};
 
 /// An SSL error
-#[deriving(Show, Clone, PartialEq, Eq)]
+#[deriving(Clone, PartialEq, Eq)]
 pub enum SslError {
     /// The underlying stream has reported an error
     StreamError(IoError),
     /// The SSL session has been closed by the other end
     SslSessionClosed,
     /// An error in the OpenSSL library
-    OpenSslErrors(Vec<OpensslError>)
+    OpenSslErrors(Vec<OpensslError>),
+    /// The input to an operation was malformed
+    InvalidInput(String),
+    /// The handshake failed because the peer's certificate could not be
+    /// verified, along with the errors OpenSSL reported
+    VerifyError(X509ValidationError, Vec<OpensslError>),
+    /// The peer's certificate is valid but was not issued for the host name
+    /// which was connected to
+    HostnameMismatch(String),
+    /// The connection was aborted because the client attempted to
+    /// renegotiate, which the server's context rejects
+    RenegotiationRejected,
+    /// The operation can't continue until the underlying stream, which is
+    /// non-blocking or has a timeout set, becomes readable
+    WantRead(IoError),
+    /// The operation can't continue until the underlying stream, which is
+    /// non-blocking or has a timeout set, becomes writable
+    WantWrite(IoError)
+}
+
+impl fmt::Show for SslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write(self.description().as_bytes()));
+        match *self {
+            StreamError(ref err) | WantRead(ref err) | WantWrite(ref err) => write!(f, ": {}", err),
+            VerifyError(ref err, _) => write!(f, ": {}", err.description()),
+            _ => match self.detail() {
+                Some(detail) => write!(f, ": {}", detail),
+                None => Ok(())
+            }
+        }
+    }
+}
+
+impl Error for SslError {
+    fn description(&self) -> &str {
+        match *self {
+            StreamError(_) => "the underlying stream reported an error",
+            SslSessionClosed => "the SSL session was closed by the other end",
+            OpenSslErrors(_) => "an error occurred in the OpenSSL library",
+            InvalidInput(_) => "the input was malformed",
+            VerifyError(..) => "certificate verification failed",
+            HostnameMismatch(_) => "the certificate does not match the host name",
+            RenegotiationRejected => "the client attempted to renegotiate",
+            WantRead(_) => "the underlying stream must become readable to continue",
+            WantWrite(_) => "the underlying stream must become writable to continue"
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match *self {
+            StreamError(ref err) | WantRead(ref err) | WantWrite(ref err) => err.detail(),
+            SslSessionClosed | RenegotiationRejected => None,
+            OpenSslErrors(ref errs) if errs.is_empty() => None,
+            OpenSslErrors(ref errs) => {
+                let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
+                Some(msgs.connect("; "))
+            }
+            InvalidInput(ref msg) => Some(msg.clone()),
+            VerifyError(ref err, _) => Some(err.description().to_string()),
+            HostnameMismatch(ref host) => Some(host.clone())
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            StreamError(ref err) | WantRead(ref err) | WantWrite(ref err) => Some(err as &Error),
+            VerifyError(ref err, _) => Some(err as &Error),
+            _ => None
+        }
+    }
+}
+
+impl FromError<IoError> for SslError {
+    fn from_error(err: IoError) -> SslError {
+        StreamError(err)
+    }
 }
 
 /// An error from the OpenSSL library
-#[deriving(Show, Clone, PartialEq, Eq)]
+#[deriving(Clone, PartialEq, Eq)]
 pub enum OpensslError {
     /// An unknown error
     UnknownError {
@@ -25,20 +104,57 @@ pub enum OpensslError {
         /// The function reporting the error
         function: String,
         /// The reason for the error
-        reason: String
+        reason: String,
+        /// The packed error code, as returned by `ERR_get_error`
+        code: c_ulong,
+        /// The numeric code of the library reporting the error, e.g.
+        /// `ffi::ERR_LIB_SSL`
+        library_code: c_int,
+        /// The numeric code of the function reporting the error
+        function_code: c_int,
+        /// The numeric code of the reason for the error, e.g.
+        /// `ffi::SSL_R_CERTIFICATE_VERIFY_FAILED`
+        reason_code: c_int,
+        /// The full error string, as formatted by `ERR_error_string_n`
+        message: String
+    }
+}
+
+impl fmt::Show for OpensslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnknownError { ref message, .. } => f.write(message.as_bytes())
+        }
     }
 }
 
 fn get_lib(err: c_ulong) -> String {
-    unsafe { CString::new(ffi::ERR_lib_error_string(err), false) }.to_string()
+    c_str_to_string(unsafe { ffi::ERR_lib_error_string(err) })
 }
 
 fn get_func(err: c_ulong) -> String {
-    unsafe { CString::new(ffi::ERR_func_error_string(err), false).to_string() }
+    c_str_to_string(unsafe { ffi::ERR_func_error_string(err) })
 }
 
 fn get_reason(err: c_ulong) -> String {
-    unsafe { CString::new(ffi::ERR_reason_error_string(err), false).to_string() }
+    c_str_to_string(unsafe { ffi::ERR_reason_error_string(err) })
+}
+
+fn get_message(err: c_ulong) -> String {
+    let mut buf = [0 as c_char, ..256];
+    unsafe {
+        ffi::ERR_error_string_n(err, buf.as_mut_ptr(), buf.len() as size_t);
+    }
+    c_str_to_string(buf.as_ptr())
+}
+
+/// Discards any errors queued on the current thread's OpenSSL error stack.
+///
+/// OpenSSL reports errors through a thread local queue which is not emptied
+/// automatically, so errors left over by an earlier operation can otherwise be
+/// attributed to a later, unrelated one.
+pub fn clear_error_queue() {
+    unsafe { ffi::ERR_clear_error() }
 }
 
 #[test]
@@ -54,21 +170,186 @@ fn test_uknown_error_should_have_correct_messages() {
     assert_eq!(reason.as_slice(), "sslv3 alert handshake failure");
 }
 
+#[test]
+fn test_error_codes() {
+    let err = OpensslError::from_code(336032784);
+    assert_eq!(err.library_code(), ffi::ERR_LIB_SSL);
+    assert_eq!(err.reason_code(), ffi::SSL_R_SSLV3_ALERT_HANDSHAKE_FAILURE);
+    assert!(err.is_handshake_failure());
+    assert!(!err.is_peer_alert_certificate_expired());
+}
+
+#[test]
+fn test_certificate_expired() {
+    unsafe {
+        ffi::ERR_put_error(ffi::ERR_LIB_SSL, 0, ffi::SSL_R_SSLV3_ALERT_CERTIFICATE_EXPIRED,
+                           ::std::ptr::null(), 0);
+    }
+    assert!(SslError::get().is_certificate_expired());
+    assert!(VerifyError(X509CertHasExpired, vec![]).is_certificate_expired());
+    assert!(!OpenSslErrors(vec![]).is_certificate_expired());
+}
+
+#[test]
+fn test_get_drains_queue() {
+    unsafe {
+        ffi::ERR_put_error(ffi::ERR_LIB_SSL, 0, ffi::SSL_R_UNKNOWN_PROTOCOL,
+                           ::std::ptr::null(), 0);
+        ffi::ERR_put_error(ffi::ERR_LIB_PEM, 0, ffi::PEM_R_NO_START_LINE,
+                           ::std::ptr::null(), 0);
+    }
+    assert_eq!(SslError::get().openssl_errors().len(), 2);
+    assert_eq!(SslError::get().openssl_errors().len(), 0);
+}
+
+#[test]
+fn test_display() {
+    let err = InvalidInput("bad length".to_string());
+    assert_eq!(err.to_string().as_slice(), "the input was malformed: bad length");
+
+    let err = OpenSslErrors(vec!(OpensslError::from_code(336032784)));
+    assert!(err.to_string().as_slice().contains("sslv3 alert handshake failure"));
+}
+
+#[test]
+fn test_cause() {
+    use std::io::{IoError, OtherIoError};
+
+    let io = IoError { kind: OtherIoError, desc: "boom", detail: None };
+    let err: SslError = FromError::from_error(io);
+    assert!(err.cause().is_some());
+    assert!(SslSessionClosed.cause().is_none());
+}
+
+#[test]
+fn test_clear_error_queue() {
+    unsafe {
+        ffi::ERR_put_error(ffi::ERR_LIB_SSL, 0, ffi::SSL_R_UNKNOWN_PROTOCOL,
+                           ::std::ptr::null(), 0);
+    }
+    clear_error_queue();
+    assert_eq!(SslError::get().openssl_errors().len(), 0);
+}
+
 impl SslError {
     /// Creates a new `OpenSslErrors` with the current contents of the error
-    /// stack.
+    /// stack, emptying it.
     pub fn get() -> SslError {
         let mut errs = vec!();
         loop {
             match unsafe { ffi::ERR_get_error() } {
                 0 => break,
-                err => errs.push(UnknownError {
-                    library: get_lib(err),
-                    function: get_func(err),
-                    reason: get_reason(err)
-                })
+                err => errs.push(OpensslError::from_code(err))
             }
         }
         OpenSslErrors(errs)
     }
+
+    /// Returns the OpenSSL errors carried by this error, if any.
+    pub fn openssl_errors(&self) -> &[OpensslError] {
+        match *self {
+            OpenSslErrors(ref errs) => errs.as_slice(),
+            VerifyError(_, ref errs) => errs.as_slice(),
+            _ => &[]
+        }
+    }
+
+    /// Returns `true` if the peer's certificate failed verification.
+    pub fn is_certificate_verify_failed(&self) -> bool {
+        self.openssl_errors().iter().any(|e| e.is_certificate_verify_failed())
+    }
+
+    /// Returns `true` if either certificate in the handshake has expired:
+    /// the peer's failed our verification for that reason, or the peer
+    /// reported that ours has.
+    pub fn is_certificate_expired(&self) -> bool {
+        match *self {
+            VerifyError(X509CertHasExpired, _) => true,
+            _ => self.openssl_errors().iter().any(|e| e.is_peer_alert_certificate_expired())
+        }
+    }
+
+    /// Returns `true` if the handshake failed because the peers could not
+    /// agree on a set of parameters.
+    pub fn is_handshake_failure(&self) -> bool {
+        self.openssl_errors().iter().any(|e| e.is_handshake_failure())
+    }
+
+    /// Returns `true` if the peer does not appear to be speaking SSL/TLS.
+    pub fn is_wrong_protocol(&self) -> bool {
+        self.openssl_errors().iter().any(|e| e.is_wrong_protocol())
+    }
+}
+
+impl OpensslError {
+    /// Decodes a packed error code, as returned by `ERR_get_error`.
+    pub fn from_code(err: c_ulong) -> OpensslError {
+        UnknownError {
+            library: get_lib(err),
+            function: get_func(err),
+            reason: get_reason(err),
+            code: err,
+            library_code: ffi::ERR_GET_LIB(err),
+            function_code: ffi::ERR_GET_FUNC(err),
+            reason_code: ffi::ERR_GET_REASON(err),
+            message: get_message(err)
+        }
+    }
+
+    /// Returns the packed error code.
+    pub fn code(&self) -> c_ulong {
+        match *self {
+            UnknownError { code, .. } => code
+        }
+    }
+
+    /// Returns the numeric code of the library reporting the error.
+    pub fn library_code(&self) -> c_int {
+        match *self {
+            UnknownError { library_code, .. } => library_code
+        }
+    }
+
+    /// Returns the numeric code of the function reporting the error.
+    pub fn function_code(&self) -> c_int {
+        match *self {
+            UnknownError { function_code, .. } => function_code
+        }
+    }
+
+    /// Returns the numeric code of the reason for the error.
+    pub fn reason_code(&self) -> c_int {
+        match *self {
+            UnknownError { reason_code, .. } => reason_code
+        }
+    }
+
+    fn is_ssl_reason(&self, reason: c_int) -> bool {
+        self.library_code() == ffi::ERR_LIB_SSL && self.reason_code() == reason
+    }
+
+    /// Returns `true` if the peer's certificate failed verification.
+    pub fn is_certificate_verify_failed(&self) -> bool {
+        self.is_ssl_reason(ffi::SSL_R_CERTIFICATE_VERIFY_FAILED)
+    }
+
+    /// Returns `true` if the peer reported that our certificate has expired.
+    ///
+    /// Our own verification of the peer's certificate is not reported this
+    /// way; see `SslError::is_certificate_expired`.
+    pub fn is_peer_alert_certificate_expired(&self) -> bool {
+        self.is_ssl_reason(ffi::SSL_R_SSLV3_ALERT_CERTIFICATE_EXPIRED)
+    }
+
+    /// Returns `true` if the handshake failed because the peers could not
+    /// agree on a set of parameters.
+    pub fn is_handshake_failure(&self) -> bool {
+        self.is_ssl_reason(ffi::SSL_R_SSLV3_ALERT_HANDSHAKE_FAILURE)
+    }
+
+    /// Returns `true` if the peer does not appear to be speaking SSL/TLS.
+    pub fn is_wrong_protocol(&self) -> bool {
+        self.is_ssl_reason(ffi::SSL_R_UNKNOWN_PROTOCOL) ||
+            self.is_ssl_reason(ffi::SSL_R_WRONG_VERSION_NUMBER)
+    }
 }
@@ -1,41 +1,117 @@
-use libc::{c_int, c_void, c_char, c_long};
-use std::io::{IoResult, IoError, EndOfFile, Stream, Reader, Writer};
+use libc::{c_char, c_int, c_uint, c_void, c_long, size_t};
+use std::c_str::CString;
+use std::io::{IoResult, IoError, EndOfFile, ConnectionAborted, ResourceUnavailable, TimedOut};
+use std::io::OtherIoError;
+use std::io::{ShortWrite, Reader, Writer};
+#[cfg(feature = "x509_check_host")]
+use std::io::net::ip::IpAddr;
+use std::io::net::tcp::TcpStream;
 use std::mem;
 use std::ptr;
-use std::rt::mutex::NativeMutex;
+use std::slice;
 use std::string;
+use serialize::hex::ToHex;
 use sync::one::{Once, ONCE_INIT};
 
 use bio::{MemBio};
+use crypto::pkey::{PKey};
+use dh::{Dh};
 use ffi;
-use ssl::error::{SslError, SslSessionClosed, StreamError};
-use x509::{X509StoreContext, X509FileType};
+use ssl::error::{SslError, SslSessionClosed, StreamError, OpenSslErrors, VerifyError, InvalidInput};
+use ssl::error::{RenegotiationRejected, WantRead, WantWrite};
+use x509::{X509, X509StoreContext, X509FileType, X509ValidationError};
+use x509::verify::X509Store;
 
+pub mod connector;
+pub mod dtls;
 pub mod error;
+pub mod split;
 #[cfg(test)]
 mod tests;
 
 static mut VERIFY_IDX: c_int = -1;
-static mut MUTEXES: *mut Vec<NativeMutex> = 0 as *mut Vec<NativeMutex>;
+static mut SERVERNAME_IDX: c_int = -1;
+static mut TICKET_KEY_IDX: c_int = -1;
+static mut RENEGOTIATION_IDX: c_int = -1;
+static mut MTU_IDX: c_int = -1;
+static mut MSG_IDX: c_int = -1;
+static mut STATUS_IDX: c_int = -1;
+static mut INFO_IDX: c_int = -1;
+static mut REJECT_RENEGOTIATION_IDX: c_int = -1;
+static mut KEYLOG_IDX: c_int = -1;
+// Indices for callback data stored with set_ctx_data
+static mut VERIFY_DATA_IDX: c_int = -1;
+static mut SERVERNAME_DATA_IDX: c_int = -1;
+static mut TICKET_KEY_DATA_IDX: c_int = -1;
+static mut SESSION_CACHE_IDX: c_int = -1;
+static mut COOKIE_IDX: c_int = -1;
+static mut PSK_CLIENT_IDX: c_int = -1;
+static mut PSK_SERVER_IDX: c_int = -1;
+static mut OCSP_RESPONDER_IDX: c_int = -1;
 
 fn init() {
     static mut INIT: Once = ONCE_INIT;
 
+    ffi::init();
+
     unsafe {
         INIT.doit(|| {
-            ffi::SSL_library_init();
-            ffi::SSL_load_error_strings();
-            ffi::ERR_load_crypto_strings();
             let verify_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
                                                            None, None);
             assert!(verify_idx >= 0);
             VERIFY_IDX = verify_idx;
 
-            let num_locks = ffi::CRYPTO_num_locks();
-            let mutexes = box Vec::from_fn(num_locks as uint, |_| NativeMutex::new());
-            MUTEXES = mem::transmute(mutexes);
+            let servername_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                               None, None);
+            assert!(servername_idx >= 0);
+            SERVERNAME_IDX = servername_idx;
+
+            let ticket_key_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                               None, None);
+            assert!(ticket_key_idx >= 0);
+            TICKET_KEY_IDX = ticket_key_idx;
+
+            let status_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                           None, None);
+            assert!(status_idx >= 0);
+            STATUS_IDX = status_idx;
+
+            let info_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                         None, None);
+            assert!(info_idx >= 0);
+            INFO_IDX = info_idx;
+
+            let reject_renegotiation_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                                         None, None);
+            assert!(reject_renegotiation_idx >= 0);
+            REJECT_RENEGOTIATION_IDX = reject_renegotiation_idx;
 
-            ffi::CRYPTO_set_locking_callback(locking_function);
+            let keylog_idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                           None, None);
+            assert!(keylog_idx >= 0);
+            KEYLOG_IDX = keylog_idx;
+
+            let renegotiation_idx = ffi::SSL_get_ex_new_index(0, ptr::null(), None,
+                                                              None, None);
+            assert!(renegotiation_idx >= 0);
+            RENEGOTIATION_IDX = renegotiation_idx;
+
+            let mtu_idx = ffi::SSL_get_ex_new_index(0, ptr::null(), None, None, None);
+            assert!(mtu_idx >= 0);
+            MTU_IDX = mtu_idx;
+
+            let msg_idx = ffi::SSL_get_ex_new_index(0, ptr::null(), None, None, None);
+            assert!(msg_idx >= 0);
+            MSG_IDX = msg_idx;
+
+            VERIFY_DATA_IDX = new_ctx_data_idx();
+            SERVERNAME_DATA_IDX = new_ctx_data_idx();
+            TICKET_KEY_DATA_IDX = new_ctx_data_idx();
+            SESSION_CACHE_IDX = new_ctx_data_idx();
+            COOKIE_IDX = new_ctx_data_idx();
+            PSK_CLIENT_IDX = new_ctx_data_idx();
+            PSK_SERVER_IDX = new_ctx_data_idx();
+            OCSP_RESPONDER_IDX = new_ctx_data_idx();
         });
     }
 }
@@ -59,6 +135,11 @@ pub enum SslMethod {
     #[cfg(feature = "tlsv1_2")]
     /// Support TLSv1.2 protocol, requires `feature="tlsv1_2"`
     Tlsv1_2,
+    /// Only support the DTLSv1 protocol, for use with a `DtlsStream`
+    Dtlsv1,
+    #[cfg(feature = "dtlsv1_2")]
+    /// Only support the DTLSv1.2 protocol, requires `feature="dtlsv1_2"`
+    Dtlsv1_2,
 }
 
 impl SslMethod {
@@ -72,11 +153,147 @@ impl SslMethod {
             #[cfg(feature = "tlsv1_1")]
             Tlsv1_1 => ffi::TLSv1_1_method(),
             #[cfg(feature = "tlsv1_2")]
-            Tlsv1_2 => ffi::TLSv1_2_method()
+            Tlsv1_2 => ffi::TLSv1_2_method(),
+            Dtlsv1 => ffi::DTLSv1_method(),
+            #[cfg(feature = "dtlsv1_2")]
+            Dtlsv1_2 => ffi::DTLSv1_2_method()
+        }
+    }
+}
+
+/// A protocol version, for use with `SslContext::set_protocol_range`
+#[deriving(Show, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SslVersion {
+    Ssl3,
+    Tls1,
+    Tls1_1,
+    Tls1_2,
+}
+
+impl SslVersion {
+    fn disable_option(&self) -> SslOptions {
+        match *self {
+            Ssl3 => SSL_OP_NO_SSLV3,
+            Tls1 => SSL_OP_NO_TLSV1,
+            Tls1_1 => SSL_OP_NO_TLSV1_1,
+            Tls1_2 => SSL_OP_NO_TLSV1_2
+        }
+    }
+}
+
+bitflags! {
+    #[doc="Options modifying the behavior of an `SslContext`"]
+    flags SslOptions: c_long {
+        #[doc="Disables the insecure SSLv2 protocol"]
+        static SSL_OP_NO_SSLV2 = ffi::SSL_OP_NO_SSLv2,
+        #[doc="Disables the SSLv3 protocol, which is vulnerable to POODLE"]
+        static SSL_OP_NO_SSLV3 = ffi::SSL_OP_NO_SSLv3,
+        static SSL_OP_NO_TLSV1 = ffi::SSL_OP_NO_TLSv1,
+        static SSL_OP_NO_TLSV1_1 = ffi::SSL_OP_NO_TLSv1_1,
+        static SSL_OP_NO_TLSV1_2 = ffi::SSL_OP_NO_TLSv1_2,
+        #[doc="Disables TLS compression, which enables the CRIME attack"]
+        static SSL_OP_NO_COMPRESSION = ffi::SSL_OP_NO_COMPRESSION,
+        #[doc="Disables RFC 5077 session tickets"]
+        static SSL_OP_NO_TICKET = ffi::SSL_OP_NO_TICKET,
+        #[doc="Makes a server choose ciphers by its own preference order rather than the client's"]
+        static SSL_OP_CIPHER_SERVER_PREFERENCE = ffi::SSL_OP_CIPHER_SERVER_PREFERENCE,
+        #[doc="Generates a new key for each ephemeral Diffie-Hellman key exchange"]
+        static SSL_OP_SINGLE_DH_USE = ffi::SSL_OP_SINGLE_DH_USE,
+        #[doc="Generates a new key for each ephemeral ECDH key exchange"]
+        static SSL_OP_SINGLE_ECDH_USE = ffi::SSL_OP_SINGLE_ECDH_USE
+    }
+}
+
+bitflags! {
+    #[doc="Modes changing the semantics of reads and writes"]
+    flags SslMode: c_long {
+        #[doc="Lets a write return after writing part of its buffer, as soon as a record has been sent"]
+        static SSL_MODE_ENABLE_PARTIAL_WRITE = ffi::SSL_MODE_ENABLE_PARTIAL_WRITE,
+        #[doc="Allows a write to be retried with a different buffer holding the same data"]
+        static SSL_MODE_ACCEPT_MOVING_WRITE_BUFFER =
+            ffi::SSL_MODE_ACCEPT_MOVING_WRITE_BUFFER,
+        #[doc="Makes reads continue after a renegotiation instead of asking to be retried"]
+        static SSL_MODE_AUTO_RETRY = ffi::SSL_MODE_AUTO_RETRY,
+        #[doc="Frees the read and write buffers of idle connections, saving around 34KB per connection"]
+        static SSL_MODE_RELEASE_BUFFERS = ffi::SSL_MODE_RELEASE_BUFFERS
+    }
+}
+
+/// A type of certificate status response a client can request
+#[deriving(Show, Hash, PartialEq, Eq)]
+pub enum StatusType {
+    /// An OCSP response, as described in RFC 6066
+    StatusOcsp
+}
+
+impl StatusType {
+    fn to_raw(&self) -> c_long {
+        match *self {
+            StatusOcsp => ffi::TLSEXT_STATUSTYPE_ocsp
+        }
+    }
+}
+
+/// A named elliptic curve for ephemeral ECDH key exchange
+#[deriving(Show, Hash, PartialEq, Eq)]
+pub enum EcdhCurve {
+    /// NIST P-256, also known as prime256v1 or secp256r1
+    CurveP256,
+    /// NIST P-384, also known as secp384r1
+    CurveP384,
+    /// NIST P-521, also known as secp521r1
+    CurveP521,
+}
+
+impl EcdhCurve {
+    fn nid(&self) -> c_int {
+        match *self {
+            CurveP256 => ffi::NID_X9_62_prime256v1,
+            CurveP384 => ffi::NID_secp384r1,
+            CurveP521 => ffi::NID_secp521r1
         }
     }
 }
 
+bitflags! {
+    #[doc="Which sessions an `SslContext` caches for resumption"]
+    flags SslSessionCacheMode: c_long {
+        #[doc="Disables caching"]
+        static SSL_SESS_CACHE_OFF = ffi::SSL_SESS_CACHE_OFF,
+        #[doc="Caches client sessions; they must still be reused explicitly with `Ssl::set_session`"]
+        static SSL_SESS_CACHE_CLIENT = ffi::SSL_SESS_CACHE_CLIENT,
+        #[doc="Caches server sessions, the default"]
+        static SSL_SESS_CACHE_SERVER = ffi::SSL_SESS_CACHE_SERVER,
+        static SSL_SESS_CACHE_BOTH = ffi::SSL_SESS_CACHE_BOTH,
+        #[doc="Disables the periodic removal of expired sessions"]
+        static SSL_SESS_CACHE_NO_AUTO_CLEAR = ffi::SSL_SESS_CACHE_NO_AUTO_CLEAR,
+        #[doc="Doesn't look up sessions to resume in the internal cache"]
+        static SSL_SESS_CACHE_NO_INTERNAL_LOOKUP = ffi::SSL_SESS_CACHE_NO_INTERNAL_LOOKUP,
+        #[doc="Doesn't add new sessions to the internal cache"]
+        static SSL_SESS_CACHE_NO_INTERNAL_STORE = ffi::SSL_SESS_CACHE_NO_INTERNAL_STORE,
+        static SSL_SESS_CACHE_NO_INTERNAL = ffi::SSL_SESS_CACHE_NO_INTERNAL
+    }
+}
+
+bitflags! {
+    #[doc="Which close_notify alerts have been exchanged on a connection"]
+    flags ShutdownState: c_int {
+        #[doc="A close_notify alert has been sent to the peer"]
+        static SSL_SENT_SHUTDOWN = ffi::SSL_SENT_SHUTDOWN,
+        #[doc="The peer's close_notify alert has been received"]
+        static SSL_RECEIVED_SHUTDOWN = ffi::SSL_RECEIVED_SHUTDOWN
+    }
+}
+
+/// The outcome of `SslStream::shutdown`
+#[deriving(Show, PartialEq, Eq)]
+pub enum ShutdownResult {
+    /// A close_notify alert was sent, but the peer's hasn't been received
+    ShutdownSent,
+    /// Close_notify alerts have been both sent and received
+    ShutdownReceived
+}
+
 /// Determines the type of certificate verification used
 #[repr(i32)]
 pub enum SslVerifyMode {
@@ -86,41 +303,55 @@ pub enum SslVerifyMode {
     SslVerifyNone = ffi::SSL_VERIFY_NONE
 }
 
-// Creates a static index for user data of type T
-// Registers a destructor for the data which will be called
-// when context is freed
-fn get_verify_data_idx<T>() -> c_int {
-    static mut VERIFY_DATA_IDX: c_int = -1;
-    static mut INIT: Once = ONCE_INIT;
+// Callback data stored in a context's ex_data. The type of the data can
+// differ between contexts using the same index, so the function freeing it
+// is kept alongside it rather than registered with the index.
+struct CtxData {
+    data: *mut c_void,
+    free: unsafe fn(*mut c_void)
+}
 
-    extern fn free_data_box<T>(_parent: *mut c_void, ptr: *mut c_void,
-                               _ad: *mut ffi::CRYPTO_EX_DATA, _idx: c_int,
-                               _argl: c_long, _argp: *mut c_void) {
-        let _: Box<T> = unsafe { mem::transmute(ptr) };
-    }
+unsafe fn free_box<T>(data: *mut c_void) {
+    let _: Box<T> = mem::transmute(data);
+}
 
-    unsafe {
-        INIT.doit(|| {
-            let idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
-                                                    None, Some(free_data_box::<T>));
-            assert!(idx >= 0);
-            VERIFY_DATA_IDX = idx;
-        });
-        VERIFY_DATA_IDX
+extern fn free_ctx_data(_parent: *mut c_void, ptr: *mut c_void,
+                        _ad: *mut ffi::CRYPTO_EX_DATA, _idx: c_int,
+                        _argl: c_long, _argp: *mut c_void) {
+    if !ptr.is_null() {
+        unsafe {
+            let data: Box<CtxData> = mem::transmute(ptr);
+            (data.free)(data.data);
+        }
     }
 }
 
-extern fn locking_function(mode: c_int, n: c_int, _file: *const c_char,
-                               _line: c_int) {
-    unsafe {
-        let mutex = (*MUTEXES).get_mut(n as uint);
+// Creates an index for data stored with set_ctx_data
+unsafe fn new_ctx_data_idx() -> c_int {
+    let idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None, None, Some(free_ctx_data));
+    assert!(idx >= 0);
+    idx
+}
 
-        if mode & ffi::CRYPTO_LOCK != 0 {
-            mutex.lock_noguard();
-        } else {
-            mutex.unlock_noguard();
-        }
+// Stores data in a context, to be freed along with it. Connections may be
+// using data already stored at the index, so it can't be replaced.
+unsafe fn set_ctx_data<T>(ctx: *mut ffi::SSL_CTX, idx: c_int, data: T) -> Option<SslError> {
+    if !ffi::SSL_CTX_get_ex_data(ctx, idx).is_null() {
+        return Some(InvalidInput("the callback data has already been set".to_string()));
     }
+    let data = box CtxData {
+        data: mem::transmute(box data),
+        free: free_box::<T>
+    };
+    ffi::SSL_CTX_set_ex_data(ctx, idx, mem::transmute(data));
+    None
+}
+
+// Returns the data stored in a context by set_ctx_data, which must have been
+// of type T
+unsafe fn get_ctx_data<'a, T>(ctx: *mut ffi::SSL_CTX, idx: c_int) -> &'a T {
+    let data: &CtxData = mem::transmute(ffi::SSL_CTX_get_ex_data(ctx, idx));
+    mem::transmute(data.data)
 }
 
 extern fn raw_verify(preverify_ok: c_int, x509_ctx: *mut ffi::X509_STORE_CTX)
@@ -151,235 +382,2156 @@ extern fn raw_verify_with_data<T>(preverify_ok: c_int,
         let verify = ffi::SSL_CTX_get_ex_data(ssl_ctx, VERIFY_IDX);
         let verify: Option<VerifyCallbackData<T>> = mem::transmute(verify);
 
-        let data = ffi::SSL_CTX_get_ex_data(ssl_ctx, get_verify_data_idx::<T>());
-        let data: Box<T> = mem::transmute(data);
+        let data: &T = get_ctx_data(ssl_ctx, VERIFY_DATA_IDX);
 
         let ctx = X509StoreContext::new(x509_ctx);
 
-        let res = match verify {
+        match verify {
             None => preverify_ok,
-            Some(verify) => verify(preverify_ok != 0, &ctx, &*data) as c_int
-        };
-
-        // Since data might be required on the next verification
-        // it is time to forget about it and avoid dropping
-        // data will be freed once OpenSSL considers it is time
-        // to free all context data
-        mem::forget(data);
-        res
+            Some(verify) => verify(preverify_ok != 0, &ctx, data) as c_int
+        }
     }
 }
 
-/// The signature of functions that can be used to manually verify certificates
-pub type VerifyCallback = fn(preverify_ok: bool,
-                             x509_ctx: &X509StoreContext) -> bool;
+/// The signature of functions called when a new session should be added to
+/// an external cache
+pub type NewSessionCallback<T> = fn(data: &T, session: &SslSession);
 
-/// The signature of functions that can be used to manually verify certificates
-/// when user-data should be carried for all verification process
-pub type VerifyCallbackData<T> = fn(preverify_ok: bool,
-                                    x509_ctx: &X509StoreContext,
-                                    data: &T) -> bool;
+/// The signature of functions which look up a session to resume by its id
+/// in an external cache
+pub type GetSessionCallback<T> = fn(data: &T, id: &[u8]) -> Option<SslSession>;
 
-// FIXME: macro may be instead of inlining?
-#[inline]
-fn wrap_ssl_result(res: c_int) -> Option<SslError> {
-    if res == 0 {
-        Some(SslError::get())
-    } else {
-        None
-    }
+/// The signature of functions called when a session, identified by its id,
+/// should be removed from an external cache, e.g. because it expired
+pub type RemoveSessionCallback<T> = fn(data: &T, id: &[u8]);
+
+struct SessionCacheCallbacks<T> {
+    new: NewSessionCallback<T>,
+    get: GetSessionCallback<T>,
+    remove: RemoveSessionCallback<T>,
+    data: T
 }
 
-/// An SSL context object
-pub struct SslContext {
-    ctx: *mut ffi::SSL_CTX
+unsafe fn session_cache_callbacks<'a, T>(ctx: *mut ffi::SSL_CTX) -> &'a SessionCacheCallbacks<T> {
+    get_ctx_data(ctx, SESSION_CACHE_IDX)
 }
 
-impl Drop for SslContext {
-    fn drop(&mut self) {
-        unsafe { ffi::SSL_CTX_free(self.ctx) }
+extern fn raw_new_session<T>(ssl: *mut ffi::SSL, session: *mut ffi::SSL_SESSION) -> c_int {
+    unsafe {
+        let callbacks = session_cache_callbacks::<T>(ffi::SSL_get_SSL_CTX(ssl));
+
+        // OpenSSL keeps its reference, which the wrapper mustn't release
+        let session = SslSession { session: session };
+        (callbacks.new)(&callbacks.data, &session);
+        mem::forget(session);
+        0
     }
 }
 
-impl SslContext {
-    /// Creates a new SSL context.
-    pub fn new(method: SslMethod) -> Result<SslContext, SslError> {
-        init();
+extern fn raw_get_session<T>(ssl: *mut ffi::SSL, id: *mut u8, len: c_int,
+                             copy: *mut c_int) -> *mut ffi::SSL_SESSION {
+    unsafe {
+        let callbacks = session_cache_callbacks::<T>(ffi::SSL_get_SSL_CTX(ssl));
 
-        let ctx = unsafe { ffi::SSL_CTX_new(method.to_raw()) };
-        if ctx == ptr::null_mut() {
-            return Err(SslError::get());
+        let session = slice::raw::buf_as_slice(id as *const u8, len as uint, |id| {
+            (callbacks.get)(&callbacks.data, id)
+        });
+        // The reference owned by the session passes to OpenSSL
+        *copy = 0;
+        match session {
+            Some(session) => {
+                let raw = session.session;
+                mem::forget(session);
+                raw
+            }
+            None => ptr::null_mut()
         }
+    }
+}
 
-        Ok(SslContext { ctx: ctx })
+extern fn raw_remove_session<T>(ctx: *mut ffi::SSL_CTX, session: *mut ffi::SSL_SESSION) {
+    unsafe {
+        let callbacks = session_cache_callbacks::<T>(ctx);
+
+        let mut len = 0;
+        let id = ffi::SSL_SESSION_get_id(session as *const ffi::SSL_SESSION, &mut len);
+        slice::raw::buf_as_slice(id, len as uint, |id| {
+            (callbacks.remove)(&callbacks.data, id)
+        });
     }
+}
 
-    /// Configures the certificate verification method for new connections.
-    pub fn set_verify(&mut self, mode: SslVerifyMode,
-                      verify: Option<VerifyCallback>) {
-        unsafe {
-            ffi::SSL_CTX_set_ex_data(self.ctx, VERIFY_IDX,
-                                     mem::transmute(verify));
-            ffi::SSL_CTX_set_verify(self.ctx, mode as c_int, Some(raw_verify));
-        }
+// The handshake states tracked in an SSL's RENEGOTIATION_IDX slot, which
+// holds the state itself rather than a pointer
+static HANDSHAKE_PENDING: uint = 0;
+static HANDSHAKE_DONE: uint = 1;
+static CLIENT_RENEGOTIATED: uint = 2;
+
+unsafe fn set_handshake_state(ssl: *const ffi::SSL, state: uint) {
+    ffi::SSL_set_ex_data(ssl as *mut ffi::SSL, RENEGOTIATION_IDX, state as *mut c_void);
+}
+
+fn get_handshake_state(ssl: *const ffi::SSL) -> uint {
+    unsafe { ffi::SSL_get_ex_data(ssl, RENEGOTIATION_IDX) as uint }
+}
+
+// A handshake starting after one has completed is a renegotiation, which
+// is recorded here and acted on by the stream, since OpenSSL offers no way
+// to abort the handshake from within the callback
+unsafe fn track_renegotiation(ssl: *const ffi::SSL, where_: c_int) {
+    let state = get_handshake_state(ssl);
+    if where_ & ffi::SSL_CB_HANDSHAKE_DONE != 0 && state == HANDSHAKE_PENDING {
+        set_handshake_state(ssl, HANDSHAKE_DONE);
+    } else if where_ & ffi::SSL_CB_HANDSHAKE_START != 0 && state == HANDSHAKE_DONE {
+        set_handshake_state(ssl, CLIENT_RENEGOTIATED);
     }
+}
 
-    /// Configures the certificate verification method for new connections also
-    /// carrying supplied data.
-    // Note: no option because there is no point to set data without providing
-    // a function handling it
-    pub fn set_verify_with_data<T>(&mut self, mode: SslVerifyMode,
-                                   verify: VerifyCallbackData<T>,
-                                   data: T) {
-        let data = box data;
-        unsafe {
-            ffi::SSL_CTX_set_ex_data(self.ctx, VERIFY_IDX,
-                                     mem::transmute(Some(verify)));
-            ffi::SSL_CTX_set_ex_data(self.ctx, get_verify_data_idx::<T>(),
-                                     mem::transmute(data));
-            ffi::SSL_CTX_set_verify(self.ctx, mode as c_int, Some(raw_verify_with_data::<T>));
-        }
+bitflags! {
+    #[doc="The kind of event reported to an `InfoCallback`"]
+    flags InfoEvent: c_int {
+        #[doc="The handshake has moved to a new state"]
+        static SSL_CB_LOOP = ffi::SSL_CB_LOOP,
+        #[doc="A handshake function has returned, with its return value"]
+        static SSL_CB_EXIT = ffi::SSL_CB_EXIT,
+        static SSL_CB_READ = ffi::SSL_CB_READ,
+        static SSL_CB_WRITE = ffi::SSL_CB_WRITE,
+        #[doc="An alert has been read or written, with the alert as the value"]
+        static SSL_CB_ALERT = ffi::SSL_CB_ALERT,
+        static SSL_CB_HANDSHAKE_START = ffi::SSL_CB_HANDSHAKE_START,
+        static SSL_CB_HANDSHAKE_DONE = ffi::SSL_CB_HANDSHAKE_DONE,
+        #[doc="The connection is a client's"]
+        static SSL_ST_CONNECT = ffi::SSL_ST_CONNECT,
+        #[doc="The connection is a server's"]
+        static SSL_ST_ACCEPT = ffi::SSL_ST_ACCEPT,
+        static SSL_CB_READ_ALERT = ffi::SSL_CB_READ | ffi::SSL_CB_ALERT,
+        static SSL_CB_WRITE_ALERT = ffi::SSL_CB_WRITE | ffi::SSL_CB_ALERT,
+        static SSL_CB_CONNECT_LOOP = ffi::SSL_ST_CONNECT | ffi::SSL_CB_LOOP,
+        static SSL_CB_CONNECT_EXIT = ffi::SSL_ST_CONNECT | ffi::SSL_CB_EXIT,
+        static SSL_CB_ACCEPT_LOOP = ffi::SSL_ST_ACCEPT | ffi::SSL_CB_LOOP,
+        static SSL_CB_ACCEPT_EXIT = ffi::SSL_ST_ACCEPT | ffi::SSL_CB_EXIT
     }
+}
 
-    /// Sets verification depth
-    pub fn set_verify_depth(&mut self, depth: uint) {
-        unsafe {
-            ffi::SSL_CTX_set_verify_depth(self.ctx, depth as c_int);
+/// The signature of functions following a connection's progress, e.g. to
+/// log it. They're passed the event and its value, such as an alert.
+///
+/// `Ssl::state_string` describes the connection's state, and `alert_string`
+/// the alerts reported with `SSL_CB_ALERT`.
+pub type InfoCallback = fn(ssl: &Ssl, event: InfoEvent, value: i32);
+
+extern fn raw_info(ssl: *const ffi::SSL, where_: c_int, ret: c_int) {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl as *mut ffi::SSL);
+        if !ffi::SSL_CTX_get_ex_data(ssl_ctx, REJECT_RENEGOTIATION_IDX).is_null() {
+            track_renegotiation(ssl, where_);
         }
-    }
 
-    #[allow(non_snake_case)]
-    /// Specifies the file that contains trusted CA certificates.
-    pub fn set_CA_file(&mut self, file: &Path) -> Option<SslError> {
-        wrap_ssl_result(file.with_c_str(|file| {
-            unsafe {
-                ffi::SSL_CTX_load_verify_locations(self.ctx, file, ptr::null())
+        let keylog = ffi::SSL_CTX_get_ex_data(ssl_ctx, KEYLOG_IDX);
+        if !keylog.is_null() && where_ & ffi::SSL_CB_HANDSHAKE_DONE != 0 {
+            let keylog: KeylogCallback = mem::transmute(keylog);
+            let ssl = Ssl { ssl: ssl as *mut ffi::SSL };
+            match keylog_line(ssl.ssl) {
+                Some(line) => keylog(&ssl, line.as_slice()),
+                None => {}
             }
-        }))
-    }
+            mem::forget(ssl);
+        }
 
-    /// Specifies the file that contains certificate
-    pub fn set_certificate_file(&mut self, file: &Path,
-                                file_type: X509FileType) -> Option<SslError> {
-        wrap_ssl_result(file.with_c_str(|file| {
-            unsafe {
-                ffi::SSL_CTX_use_certificate_file(self.ctx, file, file_type as c_int)
-            }
-        }))
+        let callback = ffi::SSL_CTX_get_ex_data(ssl_ctx, INFO_IDX);
+        if !callback.is_null() {
+            let callback: InfoCallback = mem::transmute(callback);
+            let ssl = Ssl { ssl: ssl as *mut ffi::SSL };
+            callback(&ssl, InfoEvent::from_bits_truncate(where_), ret as i32);
+            mem::forget(ssl);
+        }
     }
+}
 
-    /// Specifies the file that contains private key
-    pub fn set_private_key_file(&mut self, file: &Path,
-                                file_type: X509FileType) -> Option<SslError> {
-        wrap_ssl_result(file.with_c_str(|file| {
-            unsafe {
-                ffi::SSL_CTX_use_PrivateKey_file(self.ctx, file, file_type as c_int)
-            }
-        }))
+/// The signature of functions inspecting the protocol messages a
+/// connection sends and receives, for debugging.
+///
+/// `written` is whether the message was sent rather than received, and
+/// `content_type` is the record type it was carried in, e.g. 22 for
+/// handshake messages or 21 for alerts.
+pub type MsgCallback = fn(ssl: &Ssl, written: bool, version: i32, content_type: i32,
+                          msg: &[u8]);
+
+extern fn raw_msg(write_p: c_int, version: c_int, content_type: c_int, buf: *const c_void,
+                  len: size_t, ssl: *mut ffi::SSL, _arg: *mut c_void) {
+    unsafe {
+        let callback = ffi::SSL_get_ex_data(ssl as *const ffi::SSL, MSG_IDX);
+        let callback: MsgCallback = mem::transmute(callback);
+
+        let ssl = Ssl { ssl: ssl };
+        slice::raw::buf_as_slice(buf as *const u8, len as uint, |msg| {
+            callback(&ssl, write_p != 0, version as i32, content_type as i32, msg)
+        });
+        mem::forget(ssl);
     }
 }
 
-#[allow(dead_code)]
-struct MemBioRef<'ssl> {
-    ssl: &'ssl Ssl,
-    bio: MemBio,
-}
+/// The signature of functions receiving a line of the NSS key log format
+/// for each completed handshake
+pub type KeylogCallback = fn(ssl: &Ssl, line: &str);
 
-impl<'ssl> MemBioRef<'ssl> {
-    fn read(&mut self, buf: &mut [u8]) -> Option<uint> {
-        (&mut self.bio as &mut Reader).read(buf).ok()
+// Formats the connection's secrets as a CLIENT_RANDOM line
+unsafe fn keylog_line(ssl: *const ffi::SSL) -> Option<String> {
+    let mut client_random = [0u8, ..32];
+    let mut master_key = [0u8, ..48];
+    if ffi::ssl_get_client_random(ssl, client_random.as_mut_ptr()) == 0 {
+        return None;
     }
-
-    fn write(&mut self, buf: &[u8]) {
-        let _ = (&mut self.bio as &mut Writer).write(buf);
+    let len = ffi::ssl_get_master_key(ssl, master_key.as_mut_ptr(), master_key.len() as c_int);
+    if len == 0 {
+        return None;
     }
-}
 
-pub struct Ssl {
-    ssl: *mut ffi::SSL
+    Some(format!("CLIENT_RANDOM {} {}", client_random.to_hex(),
+                 master_key.slice_to(len as uint).to_hex()))
 }
 
-impl Drop for Ssl {
-    fn drop(&mut self) {
-        unsafe { ffi::SSL_free(self.ssl) }
+/// Describes an alert reported to an `InfoCallback`, e.g. `fatal: handshake
+/// failure`.
+pub fn alert_string(value: i32) -> String {
+    unsafe {
+        let level = ffi::SSL_alert_type_string_long(value as c_int);
+        let desc = ffi::SSL_alert_desc_string_long(value as c_int);
+        format!("{}: {}", string::raw::from_buf(level as *const u8),
+                string::raw::from_buf(desc as *const u8))
     }
 }
 
-impl Ssl {
-    pub fn new(ctx: &SslContext) -> Result<Ssl, SslError> {
-        let ssl = unsafe { ffi::SSL_new(ctx.ctx) };
-        if ssl == ptr::null_mut() {
-            return Err(SslError::get());
-        }
-        let ssl = Ssl { ssl: ssl };
+/// The maximum length of a DTLS cookie
+pub static MAX_COOKIE_LEN: uint = 255;
 
-        let rbio = try!(MemBio::new());
-        let wbio = try!(MemBio::new());
+/// The signature of functions generating the cookie a DTLS server sends a
+/// client to prove it can receive at its address, at most `MAX_COOKIE_LEN`
+/// bytes long
+pub type CookieGenerateCallback<T> = fn(ssl: &Ssl, data: &T) -> Vec<u8>;
 
-        unsafe { ffi::SSL_set_bio(ssl.ssl, rbio.unwrap(), wbio.unwrap()) }
-        Ok(ssl)
-    }
+/// The signature of functions checking the cookie a DTLS client returned
+pub type CookieVerifyCallback<T> = fn(ssl: &Ssl, data: &T, cookie: &[u8]) -> bool;
 
-    fn get_rbio<'a>(&'a self) -> MemBioRef<'a> {
-        unsafe { self.wrap_bio(ffi::SSL_get_rbio(self.ssl)) }
-    }
+struct CookieCallbacks<T> {
+    generate: CookieGenerateCallback<T>,
+    verify: CookieVerifyCallback<T>,
+    data: T
+}
 
-    fn get_wbio<'a>(&'a self) -> MemBioRef<'a> {
-        unsafe { self.wrap_bio(ffi::SSL_get_wbio(self.ssl)) }
-    }
+unsafe fn cookie_callbacks<'a, T>(ssl: *mut ffi::SSL) -> &'a CookieCallbacks<T> {
+    get_ctx_data(ffi::SSL_get_SSL_CTX(ssl), COOKIE_IDX)
+}
 
-    fn wrap_bio<'a>(&'a self, bio: *mut ffi::BIO) -> MemBioRef<'a> {
-        assert!(bio != ptr::null_mut());
-        MemBioRef {
-            ssl: self,
-            bio: MemBio::borrowed(bio)
+extern fn raw_cookie_generate<T>(ssl: *mut ffi::SSL, cookie: *mut u8,
+                                 cookie_len: *mut c_uint) -> c_int {
+    unsafe {
+        let callbacks = cookie_callbacks::<T>(ssl);
+
+        let ssl = Ssl { ssl: ssl };
+        let value = (callbacks.generate)(&ssl, &callbacks.data);
+        mem::forget(ssl);
+
+        if value.len() > MAX_COOKIE_LEN {
+            return 0;
         }
+        ptr::copy_nonoverlapping_memory(cookie, value.as_ptr(), value.len());
+        *cookie_len = value.len() as c_uint;
+        1
     }
+}
 
-    fn connect(&self) -> c_int {
-        unsafe { ffi::SSL_connect(self.ssl) }
-    }
+extern fn raw_cookie_verify<T>(ssl: *mut ffi::SSL, cookie: *mut u8,
+                               cookie_len: c_uint) -> c_int {
+    unsafe {
+        let callbacks = cookie_callbacks::<T>(ssl);
 
-    fn read(&self, buf: &mut [u8]) -> c_int {
-        unsafe { ffi::SSL_read(self.ssl, buf.as_ptr() as *mut c_void,
-                               buf.len() as c_int) }
-    }
+        let ssl = Ssl { ssl: ssl };
+        let ok = slice::raw::buf_as_slice(cookie as *const u8, cookie_len as uint, |cookie| {
+            (callbacks.verify)(&ssl, &callbacks.data, cookie)
+        });
+        mem::forget(ssl);
 
-    fn write(&self, buf: &[u8]) -> c_int {
-        unsafe { ffi::SSL_write(self.ssl, buf.as_ptr() as *const c_void,
-                                buf.len() as c_int) }
+        ok as c_int
     }
+}
 
-    fn get_error(&self, ret: c_int) -> LibSslError {
-        let err = unsafe { ffi::SSL_get_error(self.ssl, ret) };
-        match FromPrimitive::from_int(err as int) {
-            Some(err) => err,
-            None => unreachable!()
-        }
-    }
+/// The signature of functions choosing the identity and pre-shared key a
+/// client authenticates with, given the server's identity hint if it sent
+/// one. Returning None aborts the handshake.
+pub type PskClientCallback<T> = fn(ssl: &Ssl, data: &T, hint: Option<&[u8]>)
+                                   -> Option<(Vec<u8>, Vec<u8>)>;
 
-    /// Set the host name to be used with SNI (Server Name Indication).
-    pub fn set_hostname(&self, hostname: &str) -> Result<(), SslError> {
-        let ret = hostname.with_c_str(|hostname| {
-            unsafe {
-                // This is defined as a macro:
-                //      #define SSL_set_tlsext_host_name(s,name) \
-                //          SSL_ctrl(s,SSL_CTRL_SET_TLSEXT_HOSTNAME,TLSEXT_NAMETYPE_host_name,(char *)name)
+/// The signature of functions looking up the pre-shared key of the
+/// identity a client presented. Returning None aborts the handshake.
+pub type PskServerCallback<T> = fn(ssl: &Ssl, data: &T, identity: &[u8]) -> Option<Vec<u8>>;
 
-                ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_SET_TLSEXT_HOSTNAME,
-                              ffi::TLSEXT_NAMETYPE_host_name,
-                              hostname as *const c_void as *mut c_void)
-            }
-        });
+struct PskClient<T> {
+    callback: PskClientCallback<T>,
+    data: T
+}
+
+struct PskServer<T> {
+    callback: PskServerCallback<T>,
+    data: T
+}
+
+extern fn raw_psk_client<T>(ssl: *mut ffi::SSL, hint: *const c_char, identity: *mut c_char,
+                            max_identity_len: c_uint, psk: *mut u8,
+                            max_psk_len: c_uint) -> c_uint {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let client: &PskClient<T> = get_ctx_data(ssl_ctx, PSK_CLIENT_IDX);
+
+        let hint = if hint.is_null() {
+            None
+        } else {
+            Some(CString::new(hint, false))
+        };
+        let ssl = Ssl { ssl: ssl };
+        let ret = (client.callback)(&ssl, &client.data,
+                                    hint.as_ref().map(|hint| hint.as_bytes_no_nul()));
+        mem::forget(ssl);
+
+        match ret {
+            // The identity is written with a terminating NUL
+            Some((ref id, ref key)) if id.len() < max_identity_len as uint &&
+                                        !id.as_slice().contains(&0) &&
+                                        key.len() <= max_psk_len as uint => {
+                ptr::copy_nonoverlapping_memory(identity as *mut u8, id.as_ptr(), id.len());
+                *identity.offset(id.len() as int) = 0;
+                ptr::copy_nonoverlapping_memory(psk, key.as_ptr(), key.len());
+                key.len() as c_uint
+            }
+            _ => 0
+        }
+    }
+}
+
+extern fn raw_psk_server<T>(ssl: *mut ffi::SSL, identity: *const c_char, psk: *mut u8,
+                            max_psk_len: c_uint) -> c_uint {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let server: &PskServer<T> = get_ctx_data(ssl_ctx, PSK_SERVER_IDX);
+
+        if identity.is_null() {
+            return 0;
+        }
+        let identity = CString::new(identity, false);
+        let ssl = Ssl { ssl: ssl };
+        let ret = (server.callback)(&ssl, &server.data, identity.as_bytes_no_nul());
+        mem::forget(ssl);
+
+        match ret {
+            Some(ref key) if key.len() <= max_psk_len as uint => {
+                ptr::copy_nonoverlapping_memory(psk, key.as_ptr(), key.len());
+                key.len() as c_uint
+            }
+            _ => 0
+        }
+    }
+}
+
+/// The signature of functions a client uses to check the OCSP response
+/// stapled by the server, if it sent one. Returning false aborts the
+/// handshake.
+///
+/// The response is DER-encoded, and must be checked against the server's
+/// certificate chain, e.g. by an OCSP library.
+pub type StatusCallback = fn(ssl: &Ssl, response: Option<&[u8]>) -> bool;
+
+extern fn raw_client_status(ssl: *mut ffi::SSL, _arg: *mut c_void) -> c_int {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let callback = ffi::SSL_CTX_get_ex_data(ssl_ctx, STATUS_IDX);
+        let callback: StatusCallback = mem::transmute(callback);
+
+        let ssl = Ssl { ssl: ssl };
+        let ok = match ssl.get_ocsp_response() {
+            Some(response) => callback(&ssl, Some(response.as_slice())),
+            None => callback(&ssl, None)
+        };
+        mem::forget(ssl);
+
+        ok as c_int
+    }
+}
+
+/// The signature of functions a server uses to choose the DER-encoded OCSP
+/// response to staple for a client which requested one, or None to staple
+/// nothing
+pub type OcspResponderCallback<T> = fn(ssl: &Ssl, data: &T) -> Option<Vec<u8>>;
+
+struct OcspResponder<T> {
+    callback: OcspResponderCallback<T>,
+    data: T
+}
+
+extern fn raw_server_status<T>(ssl: *mut ffi::SSL, _arg: *mut c_void) -> c_int {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let responder: &OcspResponder<T> = get_ctx_data(ssl_ctx, OCSP_RESPONDER_IDX);
+
+        let ssl = Ssl { ssl: ssl };
+        let ret = match (responder.callback)(&ssl, &responder.data) {
+            Some(response) => match ssl.set_ocsp_response(response.as_slice()) {
+                Ok(()) => ffi::SSL_TLSEXT_ERR_OK,
+                Err(_) => ffi::SSL_TLSEXT_ERR_ALERT_FATAL
+            },
+            None => ffi::SSL_TLSEXT_ERR_NOACK
+        };
+        mem::forget(ssl);
+
+        ret
+    }
+}
+
+/// The signature of functions returning the contents of a custom TLS
+/// extension to send, or None to leave it out
+#[cfg(feature = "custom_ext")]
+pub type CustomExtAddCallback<T> = fn(ssl: &Ssl, data: &T) -> Option<Vec<u8>>;
+
+/// The signature of functions handling the contents of a custom TLS
+/// extension received from the peer; returning false aborts the handshake
+#[cfg(feature = "custom_ext")]
+pub type CustomExtParseCallback<T> = fn(ssl: &Ssl, data: &T, ext: &[u8]) -> bool;
+
+#[cfg(feature = "custom_ext")]
+struct CustomExt<T> {
+    add: CustomExtAddCallback<T>,
+    parse: CustomExtParseCallback<T>,
+    data: T
+}
+
+// The extensions registered on a context, which OpenSSL holds by pointer
+// without ever freeing
+#[cfg(feature = "custom_ext")]
+struct CustomExts {
+    exts: Vec<(*mut c_void, unsafe fn(*mut c_void))>
+}
+
+#[cfg(feature = "custom_ext")]
+impl Drop for CustomExts {
+    fn drop(&mut self) {
+        for &(ext, free) in self.exts.iter() {
+            unsafe { free(ext) }
+        }
+    }
+}
+
+#[cfg(feature = "custom_ext")]
+unsafe fn free_custom_ext<T>(ext: *mut c_void) {
+    let _: Box<CustomExt<T>> = mem::transmute(ext);
+}
+
+#[cfg(feature = "custom_ext")]
+fn get_custom_exts_idx() -> c_int {
+    static mut CUSTOM_EXTS_IDX: c_int = -1;
+    static mut INIT: Once = ONCE_INIT;
+
+    extern fn free_data_box(_parent: *mut c_void, ptr: *mut c_void,
+                            _ad: *mut ffi::CRYPTO_EX_DATA, _idx: c_int,
+                            _argl: c_long, _argp: *mut c_void) {
+        if !ptr.is_null() {
+            let _: Box<CustomExts> = unsafe { mem::transmute(ptr) };
+        }
+    }
+
+    unsafe {
+        INIT.doit(|| {
+            let idx = ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                    None, Some(free_data_box));
+            assert!(idx >= 0);
+            CUSTOM_EXTS_IDX = idx;
+        });
+        CUSTOM_EXTS_IDX
+    }
+}
+
+#[cfg(feature = "custom_ext")]
+extern fn raw_custom_ext_add<T>(ssl: *mut ffi::SSL, _ext_type: c_uint,
+                                out: *mut *const u8, outlen: *mut size_t,
+                                _al: *mut c_int, arg: *mut c_void) -> c_int {
+    unsafe {
+        let ext: &CustomExt<T> = mem::transmute(arg);
+
+        let ssl = Ssl { ssl: ssl };
+        let res = (ext.add)(&ssl, &ext.data);
+        mem::forget(ssl);
+
+        match res {
+            Some(contents) => {
+                // Freed by raw_custom_ext_free once the extension is sent
+                let buf = ffi::CRYPTO_malloc(contents.len() as c_int, ptr::null(), 0) as *mut u8;
+                if buf.is_null() && !contents.is_empty() {
+                    return -1;
+                }
+                ptr::copy_nonoverlapping_memory(buf, contents.as_ptr(), contents.len());
+                *out = buf as *const u8;
+                *outlen = contents.len() as size_t;
+                1
+            }
+            None => 0
+        }
+    }
+}
+
+#[cfg(feature = "custom_ext")]
+extern fn raw_custom_ext_free(_ssl: *mut ffi::SSL, _ext_type: c_uint, out: *const u8,
+                              _arg: *mut c_void) {
+    unsafe { ffi::CRYPTO_free(out as *const c_char) }
+}
+
+#[cfg(feature = "custom_ext")]
+extern fn raw_custom_ext_parse<T>(ssl: *mut ffi::SSL, _ext_type: c_uint,
+                                  contents: *const u8, len: size_t,
+                                  al: *mut c_int, arg: *mut c_void) -> c_int {
+    unsafe {
+        let ext: &CustomExt<T> = mem::transmute(arg);
+
+        let ssl = Ssl { ssl: ssl };
+        let ok = if len == 0 {
+            (ext.parse)(&ssl, &ext.data, &[])
+        } else {
+            slice::raw::buf_as_slice(contents, len as uint, |contents| {
+                (ext.parse)(&ssl, &ext.data, contents)
+            })
+        };
+        mem::forget(ssl);
+
+        if ok {
+            1
+        } else {
+            *al = ffi::SSL_AD_DECODE_ERROR;
+            0
+        }
+    }
+}
+
+/// A key protecting session tickets, which are encrypted with AES-128-CBC
+/// and authenticated with HMAC-SHA256
+///
+/// Every server sharing tickets must use the same keys; they should be
+/// random and rotated regularly.
+#[deriving(Clone)]
+pub struct TicketKey {
+    /// Identifies the key, so a ticket can be matched with it
+    pub name: [u8, ..16],
+    pub aes_key: [u8, ..16],
+    pub hmac_key: [u8, ..16],
+}
+
+/// The key a `TicketKeyCallback` selects
+pub enum TicketKeyAction {
+    /// Encrypts or decrypts the ticket with the key
+    UseTicketKey(TicketKey),
+    /// Decrypts the ticket with the key, then issues a replacement under the
+    /// current key, for keys which are being retired
+    RenewTicketKey(TicketKey),
+    /// Rejects the ticket, falling back to a full handshake
+    NoTicketKey,
+}
+
+/// The signature of functions that choose session ticket keys
+///
+/// `name` is None when a new ticket is being issued, in which case the
+/// current key must be returned, or the name from a ticket presented by a
+/// client, in which case the key with that name should be.
+pub type TicketKeyCallback<T> = fn(data: &T, name: Option<&[u8]>) -> TicketKeyAction;
+
+extern fn raw_ticket_key<T>(ssl: *mut ffi::SSL, key_name: *mut u8, iv: *mut u8,
+                            cipher_ctx: *mut ffi::EVP_CIPHER_CTX,
+                            hmac_ctx: *mut ffi::HMAC_CTX, enc: c_int) -> c_int {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let callback = ffi::SSL_CTX_get_ex_data(ssl_ctx, TICKET_KEY_IDX);
+        let callback: TicketKeyCallback<T> = mem::transmute(callback);
+
+        let data: &T = get_ctx_data(ssl_ctx, TICKET_KEY_DATA_IDX);
+
+        let res = if enc == 1 {
+            match callback(&*data, None) {
+                UseTicketKey(key) | RenewTicketKey(key) => {
+                    if ffi::RAND_bytes(iv, 16) <= 0 {
+                        -1
+                    } else {
+                        ptr::copy_nonoverlapping_memory(key_name, key.name.as_ptr(), 16);
+                        init_ticket_crypto(&key, iv, cipher_ctx, hmac_ctx, 1)
+                    }
+                }
+                // A ticket can't be issued without a key
+                NoTicketKey => -1
+            }
+        } else {
+            let name = slice::raw::buf_as_slice(key_name as *const u8, 16, |name| {
+                callback(&*data, Some(name))
+            });
+            match name {
+                UseTicketKey(key) => init_ticket_crypto(&key, iv, cipher_ctx, hmac_ctx, 0),
+                RenewTicketKey(key) => match init_ticket_crypto(&key, iv, cipher_ctx, hmac_ctx, 0) {
+                    1 => 2,
+                    err => err
+                },
+                NoTicketKey => 0
+            }
+        };
+        res
+    }
+}
+
+unsafe fn init_ticket_crypto(key: &TicketKey, iv: *const u8,
+                             cipher_ctx: *mut ffi::EVP_CIPHER_CTX,
+                             hmac_ctx: *mut ffi::HMAC_CTX, enc: c_int) -> c_int {
+    if ffi::EVP_CipherInit_ex(cipher_ctx, ffi::EVP_aes_128_cbc(), ptr::null_mut(),
+                              key.aes_key.as_ptr(), iv, enc) != 1 {
+        return -1;
+    }
+    if ffi::HMAC_Init_ex(hmac_ctx, key.hmac_key.as_ptr(), key.hmac_key.len() as c_int,
+                         ffi::EVP_sha256(), ptr::null()) != 1 {
+        return -1;
+    }
+    1
+}
+
+fn servername_result(ok: bool, ad: *mut c_int) -> c_int {
+    if ok {
+        ffi::SSL_TLSEXT_ERR_OK
+    } else {
+        unsafe { *ad = ffi::SSL_AD_UNRECOGNIZED_NAME };
+        ffi::SSL_TLSEXT_ERR_ALERT_FATAL
+    }
+}
+
+extern fn raw_servername(ssl: *mut ffi::SSL, ad: *mut c_int, _arg: *mut c_void) -> c_int {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let callback = ffi::SSL_CTX_get_ex_data(ssl_ctx, SERVERNAME_IDX);
+        let callback: ServerNameCallback = mem::transmute(callback);
+
+        // The connection belongs to its SslStream, so it mustn't be freed here
+        let mut ssl = Ssl { ssl: ssl };
+        let ok = callback(&mut ssl);
+        mem::forget(ssl);
+
+        servername_result(ok, ad)
+    }
+}
+
+extern fn raw_servername_with_data<T>(ssl: *mut ffi::SSL, ad: *mut c_int,
+                                      _arg: *mut c_void) -> c_int {
+    unsafe {
+        let ssl_ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let callback = ffi::SSL_CTX_get_ex_data(ssl_ctx, SERVERNAME_IDX);
+        let callback: ServerNameCallbackData<T> = mem::transmute(callback);
+
+        let data: &T = get_ctx_data(ssl_ctx, SERVERNAME_DATA_IDX);
+
+        let mut ssl = Ssl { ssl: ssl };
+        let ok = callback(&mut ssl, data);
+        mem::forget(ssl);
+
+        servername_result(ok, ad)
+    }
+}
+
+/// The signature of functions that can be used to handle the host name
+/// requested by a client with SNI
+///
+/// Returning false aborts the handshake with an `unrecognized_name` alert.
+pub type ServerNameCallback = fn(ssl: &mut Ssl) -> bool;
+
+/// The signature of functions that can be used to handle the host name
+/// requested by a client with SNI, carrying user data
+pub type ServerNameCallbackData<T> = fn(ssl: &mut Ssl, data: &T) -> bool;
+
+/// The signature of functions that can be used to manually verify certificates
+pub type VerifyCallback = fn(preverify_ok: bool,
+                             x509_ctx: &X509StoreContext) -> bool;
+
+/// The signature of functions that can be used to manually verify certificates
+/// when user-data should be carried for all verification process
+pub type VerifyCallbackData<T> = fn(preverify_ok: bool,
+                                    x509_ctx: &X509StoreContext,
+                                    data: &T) -> bool;
+
+// FIXME: macro may be instead of inlining?
+#[inline]
+fn wrap_ssl_result(res: c_int) -> Option<SslError> {
+    if res == 0 {
+        Some(SslError::get())
+    } else {
+        None
+    }
+}
+
+// Frees the data stored at an index created by `SslContextIndex::new` or
+// `SslIndex::new`
+extern fn free_ex_data<T>(_parent: *mut c_void, ptr: *mut c_void,
+                          _ad: *mut ffi::CRYPTO_EX_DATA, _idx: c_int,
+                          _argl: c_long, _argp: *mut c_void) {
+    if !ptr.is_null() {
+        let _: Box<T> = unsafe { mem::transmute(ptr) };
+    }
+}
+
+/// A slot for data of type `T` stored on `SslContext`s, e.g. state used by
+/// their callbacks
+pub struct SslContextIndex<T> {
+    idx: c_int
+}
+
+impl<T> SslContextIndex<T> {
+    /// Allocates a new slot.
+    ///
+    /// Slots are never released, so they should be created once and
+    /// reused, e.g. at startup.
+    pub fn new() -> Result<SslContextIndex<T>, SslError> {
+        init();
+
+        let idx = unsafe {
+            ffi::SSL_CTX_get_ex_new_index(0, ptr::null(), None, None,
+                                          Some(free_ex_data::<T>))
+        };
+        if idx < 0 {
+            return Err(SslError::get());
+        }
+        Ok(SslContextIndex { idx: idx })
+    }
+}
+
+/// A slot for data of type `T` stored on `Ssl`s, e.g. per-connection state
+/// used by callbacks
+pub struct SslIndex<T> {
+    idx: c_int
+}
+
+impl<T> SslIndex<T> {
+    /// Allocates a new slot.
+    ///
+    /// Slots are never released, so they should be created once and
+    /// reused, e.g. at startup.
+    pub fn new() -> Result<SslIndex<T>, SslError> {
+        init();
+
+        let idx = unsafe {
+            ffi::SSL_get_ex_new_index(0, ptr::null(), None, None, Some(free_ex_data::<T>))
+        };
+        if idx < 0 {
+            return Err(SslError::get());
+        }
+        Ok(SslIndex { idx: idx })
+    }
+}
+
+/// An SSL context object
+pub struct SslContext {
+    ctx: *mut ffi::SSL_CTX
+}
+
+impl Drop for SslContext {
+    fn drop(&mut self) {
+        unsafe { ffi::SSL_CTX_free(self.ctx) }
+    }
+}
+
+impl SslContext {
+    /// Creates a new SSL context.
+    pub fn new(method: SslMethod) -> Result<SslContext, SslError> {
+        init();
+
+        let ctx = unsafe { ffi::SSL_CTX_new(method.to_raw()) };
+        if ctx == ptr::null_mut() {
+            return Err(SslError::get());
+        }
+
+        Ok(SslContext { ctx: ctx })
+    }
+
+    /// Configures the certificate verification method for new connections.
+    pub fn set_verify(&mut self, mode: SslVerifyMode,
+                      verify: Option<VerifyCallback>) {
+        unsafe {
+            ffi::SSL_CTX_set_ex_data(self.ctx, VERIFY_IDX,
+                                     mem::transmute(verify));
+            ffi::SSL_CTX_set_verify(self.ctx, mode as c_int, Some(raw_verify));
+        }
+    }
+
+    /// Configures the certificate verification method for new connections also
+    /// carrying supplied data.
+    ///
+    /// The data can only be set once, as connections may still be using it.
+    // Note: no option because there is no point to set data without providing
+    // a function handling it
+    pub fn set_verify_with_data<T>(&mut self, mode: SslVerifyMode,
+                                   verify: VerifyCallbackData<T>,
+                                   data: T) -> Option<SslError> {
+        unsafe {
+            let err = set_ctx_data(self.ctx, VERIFY_DATA_IDX, data);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_set_ex_data(self.ctx, VERIFY_IDX,
+                                     mem::transmute(Some(verify)));
+            ffi::SSL_CTX_set_verify(self.ctx, mode as c_int, Some(raw_verify_with_data::<T>));
+        }
+        None
+    }
+
+    /// Stores `data` in the slot `index`.
+    ///
+    /// Connections created from the context can borrow the data with
+    /// `Ssl::ctx_ex_data` for as long as they live, so a slot can only be
+    /// set once; this fails if it already holds data.
+    pub fn set_ex_data<T>(&mut self, index: &SslContextIndex<T>, data: T) -> Option<SslError> {
+        unsafe {
+            if !ffi::SSL_CTX_get_ex_data(self.ctx, index.idx).is_null() {
+                return Some(InvalidInput("the slot already holds data".to_string()));
+            }
+            let data = box data;
+            ffi::SSL_CTX_set_ex_data(self.ctx, index.idx, mem::transmute(data));
+        }
+        None
+    }
+
+    /// Returns the data stored in the slot `index`, if any.
+    pub fn ex_data<'a, T>(&'a self, index: &SslContextIndex<T>) -> Option<&'a T> {
+        unsafe {
+            let data = ffi::SSL_CTX_get_ex_data(self.ctx, index.idx);
+            if data.is_null() {
+                None
+            } else {
+                Some(&*(data as *const T))
+            }
+        }
+    }
+
+    /// Adds a custom extension of type `ext_type` to the ClientHello,
+    /// requires `feature="custom_ext"` (OpenSSL 1.0.2 or later)
+    ///
+    /// `add` provides the contents sent to the server, and `parse` handles
+    /// those of the server's response, if it sends one. Fails if the type is
+    /// already registered or is handled by OpenSSL itself.
+    #[cfg(feature = "custom_ext")]
+    pub fn add_client_custom_ext<T>(&mut self, ext_type: u16,
+                                    add: CustomExtAddCallback<T>,
+                                    parse: CustomExtParseCallback<T>,
+                                    data: T) -> Option<SslError> {
+        self.add_custom_ext(ffi::SSL_CTX_add_client_custom_ext, ext_type, add, parse, data)
+    }
+
+    /// Adds a custom extension of type `ext_type` to the ServerHello,
+    /// requires `feature="custom_ext"` (OpenSSL 1.0.2 or later)
+    ///
+    /// `parse` handles the contents sent by a client, and `add` then provides
+    /// those of the response. Nothing is sent to clients which didn't send
+    /// the extension. Fails if the type is already registered or is handled
+    /// by OpenSSL itself.
+    #[cfg(feature = "custom_ext")]
+    pub fn add_server_custom_ext<T>(&mut self, ext_type: u16,
+                                    add: CustomExtAddCallback<T>,
+                                    parse: CustomExtParseCallback<T>,
+                                    data: T) -> Option<SslError> {
+        self.add_custom_ext(ffi::SSL_CTX_add_server_custom_ext, ext_type, add, parse, data)
+    }
+
+    #[cfg(feature = "custom_ext")]
+    fn add_custom_ext<T>(&mut self,
+                         register: unsafe extern "C" fn(*mut ffi::SSL_CTX, c_uint,
+                                                        Option<ffi::custom_ext_add_cb>,
+                                                        Option<ffi::custom_ext_free_cb>,
+                                                        *mut c_void,
+                                                        Option<ffi::custom_ext_parse_cb>,
+                                                        *mut c_void) -> c_int,
+                         ext_type: u16, add: CustomExtAddCallback<T>,
+                         parse: CustomExtParseCallback<T>, data: T) -> Option<SslError> {
+        let ext = box CustomExt { add: add, parse: parse, data: data };
+        unsafe {
+            let ext: *mut c_void = mem::transmute(ext);
+            let res = register(self.ctx, ext_type as c_uint,
+                               Some(raw_custom_ext_add::<T>), Some(raw_custom_ext_free),
+                               ext, Some(raw_custom_ext_parse::<T>), ext);
+            if res == 0 {
+                free_custom_ext::<T>(ext);
+                return Some(SslError::get());
+            }
+
+            // The context frees the extension's data along with itself
+            let idx = get_custom_exts_idx();
+            let mut exts = ffi::SSL_CTX_get_ex_data(self.ctx, idx) as *mut CustomExts;
+            if exts.is_null() {
+                let new = box CustomExts { exts: vec![] };
+                exts = mem::transmute(new);
+                ffi::SSL_CTX_set_ex_data(self.ctx, idx, exts as *mut c_void);
+            }
+            (*exts).exts.push((ext, free_custom_ext::<T>));
+        }
+        None
+    }
+
+    /// Sets a callback called on servers when a client requests a host name
+    /// with SNI, before the certificate is chosen.
+    ///
+    /// The callback can inspect the name with `Ssl::get_servername` and
+    /// switch the connection to another context with `Ssl::set_ssl_context`.
+    pub fn set_servername_callback(&mut self, callback: ServerNameCallback) {
+        unsafe {
+            ffi::SSL_CTX_set_ex_data(self.ctx, SERVERNAME_IDX, mem::transmute(callback));
+            self.set_raw_servername_callback(raw_servername);
+        }
+    }
+
+    /// Like `set_servername_callback`, also carrying supplied data, such as
+    /// the contexts of each virtual host.
+    ///
+    /// The data can only be set once, as connections may still be using it.
+    pub fn set_servername_callback_with_data<T>(&mut self,
+                                                callback: ServerNameCallbackData<T>,
+                                                data: T) -> Option<SslError> {
+        unsafe {
+            let err = set_ctx_data(self.ctx, SERVERNAME_DATA_IDX, data);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_set_ex_data(self.ctx, SERVERNAME_IDX, mem::transmute(callback));
+            self.set_raw_servername_callback(raw_servername_with_data::<T>);
+        }
+        None
+    }
+
+    unsafe fn set_raw_servername_callback(&mut self,
+                                          callback: extern fn(*mut ffi::SSL, *mut c_int,
+                                                              *mut c_void) -> c_int) {
+        // This is defined as a macro:
+        //      #define SSL_CTX_set_tlsext_servername_callback(ctx, cb) \
+        //          SSL_CTX_callback_ctrl(ctx,SSL_CTRL_SET_TLSEXT_SERVERNAME_CB,(void (*)(void))cb)
+        ffi::SSL_CTX_callback_ctrl(self.ctx, ffi::SSL_CTRL_SET_TLSEXT_SERVERNAME_CB,
+                                   Some(mem::transmute(callback)));
+    }
+
+    /// Sets the callback a client uses to check stapled OCSP responses,
+    /// which are requested with `Ssl::set_status_type`.
+    pub fn set_status_callback(&mut self, callback: StatusCallback) {
+        unsafe {
+            ffi::SSL_CTX_set_ex_data(self.ctx, STATUS_IDX, mem::transmute(callback));
+            self.set_raw_status_callback(raw_client_status);
+        }
+    }
+
+    /// Makes a server staple OCSP responses chosen by `callback` to the
+    /// handshakes of clients which request them.
+    ///
+    /// The responder can only be set once, as connections may still be
+    /// using it.
+    pub fn set_ocsp_responder<T>(&mut self, callback: OcspResponderCallback<T>,
+                                 data: T) -> Option<SslError> {
+        let responder = OcspResponder { callback: callback, data: data };
+        unsafe {
+            let err = set_ctx_data(self.ctx, OCSP_RESPONDER_IDX, responder);
+            if err.is_some() {
+                return err;
+            }
+            self.set_raw_status_callback(raw_server_status::<T>);
+        }
+        None
+    }
+
+    unsafe fn set_raw_status_callback(&mut self,
+                                      callback: extern fn(*mut ffi::SSL, *mut c_void) -> c_int) {
+        // This is defined as a macro:
+        //      #define SSL_CTX_set_tlsext_status_cb(ssl, cb) \
+        //          SSL_CTX_callback_ctrl(ssl,SSL_CTRL_SET_TLSEXT_STATUS_REQ_CB,(void (*)(void))cb)
+        ffi::SSL_CTX_callback_ctrl(self.ctx, ffi::SSL_CTRL_SET_TLSEXT_STATUS_REQ_CB,
+                                   Some(mem::transmute(callback)));
+    }
+
+    /// Backs a server's session cache with an external store, such as one
+    /// shared by several processes.
+    ///
+    /// `new` is called with each session established and `get` to look up
+    /// a session a client asks to resume which isn't in the internal cache.
+    /// Sessions are serialized with `SslSession::to_der` and read back with
+    /// `SslSession::from_der`. The internal cache can be bypassed entirely
+    /// with `SSL_SESS_CACHE_NO_INTERNAL`.
+    ///
+    /// The callbacks can only be set once, as connections may still be
+    /// using them.
+    pub fn set_session_cache_callbacks<T>(&mut self, new: NewSessionCallback<T>,
+                                          get: GetSessionCallback<T>,
+                                          remove: RemoveSessionCallback<T>,
+                                          data: T) -> Option<SslError> {
+        let callbacks = SessionCacheCallbacks {
+            new: new,
+            get: get,
+            remove: remove,
+            data: data
+        };
+        unsafe {
+            let err = set_ctx_data(self.ctx, SESSION_CACHE_IDX, callbacks);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_sess_set_new_cb(self.ctx, Some(raw_new_session::<T>));
+            ffi::SSL_CTX_sess_set_get_cb(self.ctx, Some(raw_get_session::<T>));
+            ffi::SSL_CTX_sess_set_remove_cb(self.ctx, Some(raw_remove_session::<T>));
+        }
+        None
+    }
+
+    /// Sets the callbacks a DTLS server uses for its cookie exchange, which
+    /// `DtlsStream::listen` requires.
+    ///
+    /// Cookies are typically a MAC of the client's address under a secret
+    /// key, so that the server needn't keep state for unverified clients.
+    /// The callbacks can only be set once, as connections may still be using
+    /// them.
+    pub fn set_cookie_callbacks<T>(&mut self, generate: CookieGenerateCallback<T>,
+                                   verify: CookieVerifyCallback<T>,
+                                   data: T) -> Option<SslError> {
+        let callbacks = CookieCallbacks {
+            generate: generate,
+            verify: verify,
+            data: data
+        };
+        unsafe {
+            let err = set_ctx_data(self.ctx, COOKIE_IDX, callbacks);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_set_cookie_generate_cb(self.ctx, Some(raw_cookie_generate::<T>));
+            ffi::SSL_CTX_set_cookie_verify_cb(self.ctx, Some(raw_cookie_verify::<T>));
+        }
+        None
+    }
+
+    /// Sets the callback a client uses to choose a pre-shared key, for
+    /// certificate-free PSK cipher suites such as `PSK-AES128-CBC-SHA`.
+    ///
+    /// The callback can only be set once, as connections may still be using
+    /// it.
+    pub fn set_psk_client_callback<T>(&mut self, callback: PskClientCallback<T>,
+                                      data: T) -> Option<SslError> {
+        let client = PskClient { callback: callback, data: data };
+        unsafe {
+            let err = set_ctx_data(self.ctx, PSK_CLIENT_IDX, client);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_set_psk_client_callback(self.ctx, Some(raw_psk_client::<T>));
+        }
+        None
+    }
+
+    /// Sets the callback a server uses to look up clients' pre-shared keys,
+    /// for certificate-free PSK cipher suites such as `PSK-AES128-CBC-SHA`.
+    ///
+    /// The callback can only be set once, as connections may still be using
+    /// it.
+    pub fn set_psk_server_callback<T>(&mut self, callback: PskServerCallback<T>,
+                                      data: T) -> Option<SslError> {
+        let server = PskServer { callback: callback, data: data };
+        unsafe {
+            let err = set_ctx_data(self.ctx, PSK_SERVER_IDX, server);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_set_psk_server_callback(self.ctx, Some(raw_psk_server::<T>));
+        }
+        None
+    }
+
+    /// Sets the hint a server sends to help clients choose their PSK
+    /// identity.
+    pub fn set_psk_identity_hint(&mut self, hint: &str) -> Option<SslError> {
+        wrap_ssl_result(hint.with_c_str(|hint| {
+            unsafe { ffi::SSL_CTX_use_psk_identity_hint(self.ctx, hint) }
+        }))
+    }
+
+    /// Makes a server abort connections whose client attempts to
+    /// renegotiate, which can otherwise be used to exhaust the server's CPU.
+    ///
+    /// Reads and writes on such a connection fail with
+    /// `RenegotiationRejected`. Renegotiations started by the server with
+    /// `SslStream::renegotiate` are unaffected.
+    pub fn set_reject_client_renegotiation(&mut self, reject: bool) {
+        unsafe {
+            // The flag is stored directly rather than behind a pointer
+            ffi::SSL_CTX_set_ex_data(self.ctx, REJECT_RENEGOTIATION_IDX,
+                                     reject as uint as *mut c_void);
+            ffi::SSL_CTX_set_info_callback(self.ctx, Some(raw_info));
+        }
+    }
+
+    /// Sets a callback receiving the secrets of each connection in the NSS
+    /// key log format, as written to `SSLKEYLOGFILE` by browsers, so that
+    /// captured traffic can be decrypted with tools such as Wireshark.
+    ///
+    /// Anyone reading the lines can decrypt the connections, so this is
+    /// only meant for debugging.
+    pub fn set_keylog_callback(&mut self, callback: KeylogCallback) {
+        unsafe {
+            ffi::SSL_CTX_set_ex_data(self.ctx, KEYLOG_IDX, mem::transmute(callback));
+            ffi::SSL_CTX_set_info_callback(self.ctx, Some(raw_info));
+        }
+    }
+
+    /// Sets a callback following the progress of connections, e.g. to log
+    /// handshakes and diagnose their failures.
+    pub fn set_info_callback(&mut self, callback: InfoCallback) {
+        unsafe {
+            ffi::SSL_CTX_set_ex_data(self.ctx, INFO_IDX, mem::transmute(callback));
+            ffi::SSL_CTX_set_info_callback(self.ctx, Some(raw_info));
+        }
+    }
+
+    /// Makes a server protect session tickets with keys chosen by
+    /// `callback`, so that servers sharing the keys can resume each other's
+    /// sessions.
+    ///
+    /// Without this, OpenSSL uses a random key private to the context.
+    /// Tickets can be disabled altogether with `SSL_OP_NO_TICKET`. The
+    /// callback can only be set once, as connections may still be using it.
+    pub fn set_ticket_key_callback<T>(&mut self, callback: TicketKeyCallback<T>,
+                                      data: T) -> Option<SslError> {
+        unsafe {
+            let err = set_ctx_data(self.ctx, TICKET_KEY_DATA_IDX, data);
+            if err.is_some() {
+                return err;
+            }
+            ffi::SSL_CTX_set_ex_data(self.ctx, TICKET_KEY_IDX, mem::transmute(callback));
+
+            let raw: extern fn(*mut ffi::SSL, *mut u8, *mut u8, *mut ffi::EVP_CIPHER_CTX,
+                               *mut ffi::HMAC_CTX, c_int) -> c_int = raw_ticket_key::<T>;
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_tlsext_ticket_key_cb(ssl, cb) \
+            //          SSL_CTX_callback_ctrl(ssl,SSL_CTRL_SET_TLSEXT_TICKET_KEY_CB,(void (*)(void))cb)
+            ffi::SSL_CTX_callback_ctrl(self.ctx, ffi::SSL_CTRL_SET_TLSEXT_TICKET_KEY_CB,
+                                       Some(mem::transmute(raw)));
+        }
+        None
+    }
+
+    /// Sets verification depth
+    ///
+    /// This is the maximum number of intermediate certificates allowed
+    /// between the peer's certificate and a trusted one; longer chains fail
+    /// verification. Without this, OpenSSL's default of 100 applies.
+    pub fn set_verify_depth(&mut self, depth: uint) {
+        unsafe {
+            ffi::SSL_CTX_set_verify_depth(self.ctx, depth as c_int);
+        }
+    }
+
+    /// Returns the verification depth set by `set_verify_depth`, or `None`
+    /// if it hasn't been set and OpenSSL's default applies
+    pub fn get_verify_depth(&self) -> Option<uint> {
+        let depth = unsafe {
+            ffi::SSL_CTX_get_verify_depth(self.ctx as *const ffi::SSL_CTX)
+        };
+        if depth < 0 {
+            None
+        } else {
+            Some(depth as uint)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    /// Specifies the file that contains trusted CA certificates.
+    pub fn set_CA_file(&mut self, file: &Path) -> Option<SslError> {
+        wrap_ssl_result(file.with_c_str(|file| {
+            unsafe {
+                ffi::SSL_CTX_load_verify_locations(self.ctx, file, ptr::null())
+            }
+        }))
+    }
+
+    /// Trusts `cert` as a CA certificate, in addition to any loaded from
+    /// files.
+    pub fn add_root_certificate(&mut self, cert: &X509) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            let store = ffi::SSL_CTX_get_cert_store(self.ctx as *const ffi::SSL_CTX);
+            ffi::X509_STORE_add_cert(store, cert.get_handle())
+        })
+    }
+
+    /// Adds the subject of `cert` to the CAs a server names when requesting
+    /// a client certificate.
+    ///
+    /// Many clients only offer a certificate issued by one of the named CAs.
+    pub fn add_client_ca(&mut self, cert: &X509) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            ffi::SSL_CTX_add_client_CA(self.ctx, cert.get_handle())
+        })
+    }
+
+    /// Replaces the CAs a server names when requesting a client certificate
+    /// with the subjects of `certs`.
+    ///
+    /// The list is left unchanged if this fails.
+    pub fn set_client_ca_list(&mut self, certs: &[X509]) -> Option<SslError> {
+        unsafe {
+            let list = ffi::sk_new_null();
+            if list.is_null() {
+                return Some(SslError::get());
+            }
+            for cert in certs.iter() {
+                let name = ffi::X509_NAME_dup(ffi::X509_get_subject_name(cert.get_handle()));
+                if name.is_null() || ffi::sk_push(list, name) == 0 {
+                    let err = SslError::get();
+                    if !name.is_null() {
+                        ffi::X509_NAME_free(name);
+                    }
+                    ffi::sk_pop_free(list, ffi::X509_NAME_free);
+                    return Some(err);
+                }
+            }
+            // The context takes ownership of the list
+            ffi::SSL_CTX_set_client_CA_list(self.ctx, list);
+        }
+        None
+    }
+
+    /// Replaces the trusted CA certificates with those of `store`.
+    pub fn set_cert_store(&mut self, store: X509Store) {
+        unsafe {
+            // The context takes ownership of the store
+            ffi::SSL_CTX_set_cert_store(self.ctx, store.get_handle());
+            mem::forget(store);
+        }
+    }
+
+    /// Specifies the file that contains certificate
+    pub fn set_certificate_file(&mut self, file: &Path,
+                                file_type: X509FileType) -> Option<SslError> {
+        wrap_ssl_result(file.with_c_str(|file| {
+            unsafe {
+                ffi::SSL_CTX_use_certificate_file(self.ctx, file, file_type as c_int)
+            }
+        }))
+    }
+
+    /// Specifies the file that contains private key
+    pub fn set_private_key_file(&mut self, file: &Path,
+                                file_type: X509FileType) -> Option<SslError> {
+        wrap_ssl_result(file.with_c_str(|file| {
+            unsafe {
+                ffi::SSL_CTX_use_PrivateKey_file(self.ctx, file, file_type as c_int)
+            }
+        }))
+    }
+
+    /// Specifies the private key.
+    ///
+    /// The key may be held by an engine (see `Engine::load_private_key`), in
+    /// which case its material is never exposed to the process.
+    pub fn set_private_key(&mut self, key: &PKey) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            ffi::SSL_CTX_use_PrivateKey(self.ctx, key.get_handle())
+        })
+    }
+
+    /// Sets the Diffie-Hellman parameters used for DHE cipher suites.
+    ///
+    /// Servers offer no DHE suites without them. The parameters are copied,
+    /// so `dh` may be dropped afterwards.
+    pub fn set_tmp_dh(&mut self, dh: &Dh) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_tmp_dh(ctx,dh) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_SET_TMP_DH,0,(char *)dh)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_SET_TMP_DH, 0,
+                              dh.get_handle() as *mut c_void) as c_int
+        })
+    }
+
+    /// Sets the curve used for ECDHE cipher suites.
+    ///
+    /// Servers won't negotiate ECDHE suites unless a curve is configured,
+    /// either with this or `set_ecdh_auto`.
+    pub fn set_tmp_ecdh(&mut self, curve: EcdhCurve) -> Option<SslError> {
+        unsafe {
+            let key = ffi::EC_KEY_new_by_curve_name(curve.nid());
+            if key.is_null() {
+                return Some(SslError::get());
+            }
+
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_tmp_ecdh(ctx,ecdh) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_SET_TMP_ECDH,0,(char *)ecdh)
+            // The context keeps its own copy of the key.
+            let ret = ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_SET_TMP_ECDH, 0,
+                                        key as *mut c_void) as c_int;
+            ffi::EC_KEY_free(key);
+            wrap_ssl_result(ret)
+        }
+    }
+
+    #[cfg(feature = "ecdh_auto")]
+    /// Makes the server pick the ECDHE curve from those supported by both
+    /// sides, requires `feature="ecdh_auto"` (OpenSSL 1.0.2 or later)
+    pub fn set_ecdh_auto(&mut self, onoff: bool) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_ecdh_auto(ctx, onoff) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_SET_ECDH_AUTO,onoff,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_SET_ECDH_AUTO, onoff as c_long,
+                              ptr::null_mut()) as c_int
+        })
+    }
+
+    /// Checks that the private key matches the certificate
+    ///
+    /// Call this once both are set, so that a mismatched pair is reported
+    /// at startup rather than by the first handshake to fail. It is also an
+    /// error for either to be missing.
+    pub fn check_private_key(&self) -> Option<SslError> {
+        wrap_ssl_result(unsafe { ffi::SSL_CTX_check_private_key(self.ctx) })
+    }
+
+    /// Specifies the certificate
+    ///
+    /// Along with `set_private_key`, this lets certificates fetched from a
+    /// secret store or generated at runtime be used without writing them to
+    /// a file.
+    pub fn set_certificate(&mut self, cert: &X509) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            ffi::SSL_CTX_use_certificate(self.ctx, cert.get_handle())
+        })
+    }
+
+    /// Adds an intermediate certificate sent along with the certificate, so
+    /// that peers can build a chain to a CA they trust.
+    ///
+    /// Certificates are sent in the order they are added, which should be
+    /// from the issuer of the certificate up towards the root.
+    pub fn add_extra_chain_cert(&mut self, cert: &X509) -> Option<SslError> {
+        unsafe {
+            let cert = ffi::X509_dup(cert.get_handle());
+            if cert.is_null() {
+                return Some(SslError::get());
+            }
+            // This is defined as a macro:
+            //      #define SSL_CTX_add_extra_chain_cert(ctx,x509) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_EXTRA_CHAIN_CERT,0,(char *)x509)
+            let res = ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_EXTRA_CHAIN_CERT, 0,
+                                        cert as *mut c_void) as c_int;
+            // The context takes ownership of the copy only on success
+            if res == 0 {
+                ffi::X509_free(cert);
+            }
+            wrap_ssl_result(res)
+        }
+    }
+
+    /// Trusts the CA certificates in OpenSSL's default locations, usually
+    /// those of the operating system.
+    ///
+    /// The locations can be overridden with the `SSL_CERT_FILE` and
+    /// `SSL_CERT_DIR` environment variables.
+    pub fn set_default_verify_paths(&mut self) -> Option<SslError> {
+        wrap_ssl_result(unsafe { ffi::SSL_CTX_set_default_verify_paths(self.ctx) })
+    }
+
+    /// Restricts the cipher suites which may be negotiated, using OpenSSL's
+    /// cipher list format, e.g. `HIGH:!aNULL:!MD5`.
+    ///
+    /// Fails with `InvalidInput` if the list doesn't select any cipher suite
+    /// supported by the linked OpenSSL, leaving the previous list in place.
+    pub fn set_cipher_list(&mut self, cipher_list: &str) -> Option<SslError> {
+        let res = cipher_list.with_c_str(|cipher_list| {
+            unsafe { ffi::SSL_CTX_set_cipher_list(self.ctx, cipher_list) }
+        });
+        if res == 0 {
+            unsafe { ffi::ERR_clear_error() };
+            Some(InvalidInput(format!("no cipher suites match \"{}\"", cipher_list)))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the given options in addition to those already set, returning
+    /// the new set of options in effect.
+    pub fn set_options(&mut self, options: SslOptions) -> SslOptions {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_options(ctx,op) \
+            //          SSL_CTX_ctrl((ctx),SSL_CTRL_OPTIONS,(op),NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_OPTIONS, options.bits(), ptr::null_mut())
+        };
+        SslOptions::from_bits_truncate(raw)
+    }
+
+    /// Clears the given options, returning the new set of options in
+    /// effect.
+    pub fn clear_options(&mut self, options: SslOptions) -> SslOptions {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_clear_options(ctx,op) \
+            //          SSL_CTX_ctrl((ctx),SSL_CTRL_CLEAR_OPTIONS,(op),NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_CLEAR_OPTIONS, options.bits(),
+                              ptr::null_mut())
+        };
+        SslOptions::from_bits_truncate(raw)
+    }
+
+    /// Returns the options in effect.
+    pub fn get_options(&self) -> SslOptions {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_get_options(ctx) \
+            //          SSL_CTX_ctrl((ctx),SSL_CTRL_OPTIONS,0,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_OPTIONS, 0, ptr::null_mut())
+        };
+        SslOptions::from_bits_truncate(raw)
+    }
+
+    /// Sets the given modes in addition to those already set, returning the
+    /// new set of modes in effect.
+    ///
+    /// `SslStream` handles partial writes and retries itself, so these only
+    /// change its memory use and the number of calls made into OpenSSL.
+    pub fn set_mode(&mut self, mode: SslMode) -> SslMode {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_mode(ctx,op) \
+            //          SSL_CTX_ctrl((ctx),SSL_CTRL_MODE,(op),NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_MODE, mode.bits(), ptr::null_mut())
+        };
+        SslMode::from_bits_truncate(raw)
+    }
+
+    /// Clears the given modes, returning the remaining set of modes.
+    pub fn clear_mode(&mut self, mode: SslMode) -> SslMode {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_clear_mode(ctx,op) \
+            //          SSL_CTX_ctrl((ctx),SSL_CTRL_CLEAR_MODE,(op),NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_CLEAR_MODE, mode.bits(), ptr::null_mut())
+        };
+        SslMode::from_bits_truncate(raw)
+    }
+
+    /// Returns the modes currently set.
+    pub fn get_mode(&self) -> SslMode {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_get_mode(ctx) \
+            //          SSL_CTX_ctrl((ctx),SSL_CTRL_MODE,0,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_MODE, 0, ptr::null_mut())
+        };
+        SslMode::from_bits_truncate(raw)
+    }
+
+    /// Restricts the negotiated protocol to the versions from `min` to `max`
+    /// inclusive. SSLv2 is always disabled.
+    ///
+    /// This works by disabling the versions outside of the range, so it only
+    /// has an effect on contexts created with `Sslv23`. Versions the linked
+    /// OpenSSL doesn't support are never negotiated regardless.
+    pub fn set_protocol_range(&mut self, min: SslVersion, max: SslVersion) -> Option<SslError> {
+        if min > max {
+            return Some(InvalidInput(format!("minimum protocol version {} is above maximum {}",
+                                             min, max)));
+        }
+
+        let versions = [Ssl3, Tls1, Tls1_1, Tls1_2];
+        let mut disabled = SSL_OP_NO_SSLV2;
+        let mut enabled = SslOptions::empty();
+        for version in versions.iter() {
+            if *version < min || *version > max {
+                disabled.insert(version.disable_option());
+            } else {
+                enabled.insert(version.disable_option());
+            }
+        }
+        self.clear_options(enabled);
+        self.set_options(disabled);
+        None
+    }
+
+    /// Sets which sessions are cached, returning the previous mode.
+    pub fn set_session_cache_mode(&mut self, mode: SslSessionCacheMode) -> SslSessionCacheMode {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_set_session_cache_mode(ctx,m) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_SET_SESS_CACHE_MODE,m,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_SET_SESS_CACHE_MODE, mode.bits(),
+                              ptr::null_mut())
+        };
+        SslSessionCacheMode::from_bits_truncate(raw)
+    }
+
+    /// Returns which sessions are cached.
+    pub fn get_session_cache_mode(&self) -> SslSessionCacheMode {
+        let raw = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_get_session_cache_mode(ctx) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_GET_SESS_CACHE_MODE,0,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_GET_SESS_CACHE_MODE, 0, ptr::null_mut())
+        };
+        SslSessionCacheMode::from_bits_truncate(raw)
+    }
+
+    /// Sets the maximum number of sessions in the internal cache, where 0
+    /// means unlimited, returning the previous size. The default is 20480.
+    pub fn set_session_cache_size(&mut self, size: uint) -> uint {
+        unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_sess_set_cache_size(ctx,t) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_SET_SESS_CACHE_SIZE,t,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_SET_SESS_CACHE_SIZE, size as c_long,
+                              ptr::null_mut()) as uint
+        }
+    }
+
+    /// Returns the maximum number of sessions in the internal cache.
+    pub fn get_session_cache_size(&self) -> uint {
+        unsafe {
+            // This is defined as a macro:
+            //      #define SSL_CTX_sess_get_cache_size(ctx) \
+            //          SSL_CTX_ctrl(ctx,SSL_CTRL_GET_SESS_CACHE_SIZE,0,NULL)
+            ffi::SSL_CTX_ctrl(self.ctx, ffi::SSL_CTRL_GET_SESS_CACHE_SIZE, 0,
+                              ptr::null_mut()) as uint
+        }
+    }
+
+    /// Sets the lifetime in seconds of new sessions, returning the previous
+    /// value. The default is 300.
+    pub fn set_session_timeout(&mut self, secs: u32) -> u32 {
+        unsafe { ffi::SSL_CTX_set_timeout(self.ctx, secs as c_long) as u32 }
+    }
+
+    /// Returns the lifetime in seconds of new sessions.
+    pub fn get_session_timeout(&self) -> u32 {
+        unsafe { ffi::SSL_CTX_get_timeout(self.ctx as *const ffi::SSL_CTX) as u32 }
+    }
+
+    /// Sets the context within which cached sessions may be reused by a
+    /// server. Sessions are never reused across contexts with different ids.
+    pub fn set_session_id_context(&mut self, sid_ctx: &[u8]) -> Option<SslError> {
+        wrap_ssl_result(unsafe {
+            ffi::SSL_CTX_set_session_id_context(self.ctx, sid_ctx.as_ptr(),
+                                                sid_ctx.len() as c_uint)
+        })
+    }
+}
+
+#[allow(dead_code)]
+struct MemBioRef<'ssl> {
+    ssl: &'ssl Ssl,
+    bio: MemBio,
+}
+
+impl<'ssl> MemBioRef<'ssl> {
+    fn read(&mut self, buf: &mut [u8]) -> Option<uint> {
+        (&mut self.bio as &mut Reader).read(buf).ok()
+    }
+
+    fn write(&mut self, buf: &[u8]) {
+        let _ = (&mut self.bio as &mut Writer).write(buf);
+    }
+}
+
+/// The state of an SSL session, which a client can use to resume it on a
+/// later connection and skip the full handshake
+pub struct SslSession {
+    session: *mut ffi::SSL_SESSION
+}
+
+impl Drop for SslSession {
+    fn drop(&mut self) {
+        unsafe { ffi::SSL_SESSION_free(self.session) }
+    }
+}
+
+impl SslSession {
+    /// Reads a session serialized with `to_der`
+    pub fn from_der(der: &[u8]) -> Result<SslSession, SslError> {
+        init();
+
+        let mut ptr = der.as_ptr();
+        let session = unsafe {
+            ffi::d2i_SSL_SESSION(ptr::null_mut(), &mut ptr, der.len() as c_long)
+        };
+        try_ssl_null!(session);
+
+        Ok(SslSession { session: session })
+    }
+
+    /// Returns the session's id, the key under which it is cached.
+    pub fn id(&self) -> Vec<u8> {
+        unsafe {
+            let mut len = 0;
+            let id = ffi::SSL_SESSION_get_id(self.session as *const ffi::SSL_SESSION, &mut len);
+            slice::raw::buf_as_slice(id, len as uint, |id| id.to_vec())
+        }
+    }
+
+    /// Serializes the session, e.g. to persist it across restarts.
+    ///
+    /// The result contains the session's master secret and must be kept
+    /// confidential.
+    pub fn to_der(&self) -> Result<Vec<u8>, SslError> {
+        unsafe {
+            let len = ffi::i2d_SSL_SESSION(self.session, ptr::null_mut());
+            try_ssl_if!(len <= 0);
+
+            let mut buf = Vec::from_elem(len as uint, 0u8);
+            let mut ptr = buf.as_mut_ptr();
+            try_ssl_if!(ffi::i2d_SSL_SESSION(self.session, &mut ptr) != len);
+            Ok(buf)
+        }
+    }
+}
+
+pub struct Ssl {
+    ssl: *mut ffi::SSL
+}
+
+impl Drop for Ssl {
+    fn drop(&mut self) {
+        unsafe { ffi::SSL_free(self.ssl) }
+    }
+}
+
+impl Ssl {
+    pub fn new(ctx: &SslContext) -> Result<Ssl, SslError> {
+        let ssl = unsafe { ffi::SSL_new(ctx.ctx) };
+        if ssl == ptr::null_mut() {
+            return Err(SslError::get());
+        }
+        let ssl = Ssl { ssl: ssl };
+
+        let rbio = try!(MemBio::new());
+        let wbio = try!(MemBio::new());
+
+        unsafe { ffi::SSL_set_bio(ssl.ssl, rbio.unwrap(), wbio.unwrap()) }
+        Ok(ssl)
+    }
+
+    /// Stores `data` in the slot `index`, dropping any data stored there
+    /// before.
+    ///
+    /// Callbacks such as those for SNI and PSK can then find per-connection
+    /// state with `ex_data`.
+    pub fn set_ex_data<T>(&mut self, index: &SslIndex<T>, data: T) {
+        unsafe {
+            let old = ffi::SSL_get_ex_data(self.ssl as *const ffi::SSL, index.idx);
+            if !old.is_null() {
+                let _: Box<T> = mem::transmute(old);
+            }
+            let data = box data;
+            ffi::SSL_set_ex_data(self.ssl, index.idx, mem::transmute(data));
+        }
+    }
+
+    /// Returns the data stored in the slot `index`, if any.
+    pub fn ex_data<'a, T>(&'a self, index: &SslIndex<T>) -> Option<&'a T> {
+        unsafe {
+            let data = ffi::SSL_get_ex_data(self.ssl as *const ffi::SSL, index.idx);
+            if data.is_null() {
+                None
+            } else {
+                Some(&*(data as *const T))
+            }
+        }
+    }
+
+    /// Returns the data stored in the slot `index` of the connection's
+    /// context, if any.
+    pub fn ctx_ex_data<'a, T>(&'a self, index: &SslContextIndex<T>) -> Option<&'a T> {
+        unsafe {
+            let ctx = ffi::SSL_get_SSL_CTX(self.ssl);
+            let data = ffi::SSL_CTX_get_ex_data(ctx, index.idx);
+            if data.is_null() {
+                None
+            } else {
+                Some(&*(data as *const T))
+            }
+        }
+    }
+
+    fn get_rbio<'a>(&'a self) -> MemBioRef<'a> {
+        unsafe { self.wrap_bio(ffi::SSL_get_rbio(self.ssl)) }
+    }
+
+    fn get_wbio<'a>(&'a self) -> MemBioRef<'a> {
+        unsafe { self.wrap_bio(ffi::SSL_get_wbio(self.ssl)) }
+    }
+
+    fn wrap_bio<'a>(&'a self, bio: *mut ffi::BIO) -> MemBioRef<'a> {
+        assert!(bio != ptr::null_mut());
+        MemBioRef {
+            ssl: self,
+            bio: MemBio::borrowed(bio)
+        }
+    }
+
+    fn connect(&self) -> c_int {
+        // SSL_get_error relies on the queue being empty beforehand
+        error::clear_error_queue();
+        unsafe { ffi::SSL_connect(self.ssl) }
+    }
+
+    fn accept(&self) -> c_int {
+        error::clear_error_queue();
+        unsafe { ffi::SSL_accept(self.ssl) }
+    }
+
+    fn do_handshake(&self) -> c_int {
+        error::clear_error_queue();
+        unsafe { ffi::SSL_do_handshake(self.ssl) }
+    }
+
+    fn shutdown(&self) -> c_int {
+        error::clear_error_queue();
+        unsafe { ffi::SSL_shutdown(self.ssl) }
+    }
+
+    fn read(&self, buf: &mut [u8]) -> c_int {
+        error::clear_error_queue();
+        unsafe { ffi::SSL_read(self.ssl, buf.as_ptr() as *mut c_void,
+                               buf.len() as c_int) }
+    }
+
+    fn peek(&self, buf: &mut [u8]) -> c_int {
+        error::clear_error_queue();
+        unsafe { ffi::SSL_peek(self.ssl, buf.as_ptr() as *mut c_void,
+                               buf.len() as c_int) }
+    }
+
+    fn write(&self, buf: &[u8]) -> c_int {
+        error::clear_error_queue();
+        unsafe { ffi::SSL_write(self.ssl, buf.as_ptr() as *const c_void,
+                                buf.len() as c_int) }
+    }
+
+    // Collects the error queue, identifying certificate verification failures
+    fn get_ssl_error(&self) -> SslError {
+        match SslError::get() {
+            OpenSslErrors(errs) => {
+                if errs.iter().any(|e| e.is_certificate_verify_failed()) {
+                    match self.get_verify_result() {
+                        Some(err) => return VerifyError(err, errs),
+                        None => {}
+                    }
+                }
+                OpenSslErrors(errs)
+            }
+            err => err
+        }
+    }
+
+    // Identifies an SSL_ERROR_SYSCALL, which with an empty error queue means
+    // the peer went away without sending close_notify
+    fn get_syscall_error(&self) -> SslError {
+        match self.get_ssl_error() {
+            OpenSslErrors(ref errs) if errs.is_empty() => StreamError(IoError {
+                kind: ConnectionAborted,
+                desc: "connection closed without close_notify",
+                detail: None
+            }),
+            err => err
+        }
+    }
+
+    fn get_error(&self, ret: c_int) -> LibSslError {
+        let err = unsafe { ffi::SSL_get_error(self.ssl, ret) };
+        match FromPrimitive::from_int(err as int) {
+            Some(err) => err,
+            None => unreachable!()
+        }
+    }
+
+    /// Set the host name to be used with SNI (Server Name Indication).
+    pub fn set_hostname(&self, hostname: &str) -> Result<(), SslError> {
+        let ret = hostname.with_c_str(|hostname| {
+            unsafe {
+                // This is defined as a macro:
+                //      #define SSL_set_tlsext_host_name(s,name) \
+                //          SSL_ctrl(s,SSL_CTRL_SET_TLSEXT_HOSTNAME,TLSEXT_NAMETYPE_host_name,(char *)name)
+
+                ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_SET_TLSEXT_HOSTNAME,
+                              ffi::TLSEXT_NAMETYPE_host_name,
+                              hostname as *const c_void as *mut c_void)
+            }
+        });
+
+        // For this case, 0 indicates failure.
+        if ret == 0 {
+            Err(SslError::get())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets the host name or IP address that the peer's certificate must
+    /// have been issued for, which is then checked during the handshake
+    /// along with the rest of the chain.
+    ///
+    /// Requires OpenSSL 1.0.2 or newer.
+    #[cfg(feature = "x509_check_host")]
+    pub fn set_verify_hostname(&self, host: &str) -> Result<(), SslError> {
+        let ret = unsafe {
+            let param = ffi::SSL_get0_param(self.ssl);
+            if from_str::<IpAddr>(host).is_some() {
+                host.with_c_str(|ip| ffi::X509_VERIFY_PARAM_set1_ip_asc(param, ip))
+            } else {
+                ffi::X509_VERIFY_PARAM_set_hostflags(param,
+                                                     ffi::X509_CHECK_FLAG_NO_PARTIAL_WILDCARDS);
+                ffi::X509_VERIFY_PARAM_set1_host(param, host.as_ptr() as *const c_char,
+                                                 host.len() as size_t)
+            }
+        };
 
-        // For this case, 0 indicates failure.
         if ret == 0 {
             Err(SslError::get())
         } else {
-            Ok(())
+            Ok(())
+        }
+    }
+
+    /// Returns the connection's session, for resumption by later connections.
+    pub fn get_session(&self) -> Option<SslSession> {
+        let session = unsafe { ffi::SSL_get1_session(self.ssl) };
+        if session.is_null() {
+            None
+        } else {
+            Some(SslSession { session: session })
+        }
+    }
+
+    /// Attempts to resume `session`. This must be called before the
+    /// handshake, i.e. before creating an `SslStream` with `new_from`.
+    pub fn set_session(&self, session: &SslSession) -> Result<(), SslError> {
+        unsafe {
+            try_ssl!(ffi::SSL_set_session(self.ssl, session.session));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of decrypted bytes buffered by OpenSSL, which can
+    /// be read without reading from the underlying stream.
+    pub fn pending(&self) -> uint {
+        unsafe { ffi::SSL_pending(self.ssl as *const ffi::SSL) as uint }
+    }
+
+    /// Returns whether the handshake resumed a previous session.
+    pub fn session_reused(&self) -> bool {
+        unsafe {
+            // This is defined as a macro:
+            //      #define SSL_session_reused(ssl) \
+            //          SSL_ctrl((ssl),SSL_CTRL_GET_SESSION_REUSED,0,NULL)
+            ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_GET_SESSION_REUSED, 0, ptr::null_mut()) == 1
+        }
+    }
+
+    /// Returns which close_notify alerts have been sent and received.
+    pub fn get_shutdown(&self) -> ShutdownState {
+        let state = unsafe { ffi::SSL_get_shutdown(self.ssl as *const ffi::SSL) };
+        ShutdownState::from_bits_truncate(state)
+    }
+
+    /// Schedules a renegotiation, which is carried out by the next
+    /// handshake, read or write.
+    pub fn renegotiate(&self) -> Result<(), SslError> {
+        unsafe {
+            try_ssl!(ffi::SSL_renegotiate(self.ssl));
+            // Otherwise the resulting handshake would count as the client's
+            set_handshake_state(self.ssl as *const ffi::SSL, HANDSHAKE_PENDING);
+        }
+        Ok(())
+    }
+
+    /// Returns whether a renegotiation has been scheduled but not yet
+    /// completed.
+    pub fn renegotiate_pending(&self) -> bool {
+        unsafe { ffi::SSL_renegotiate_pending(self.ssl) == 1 }
+    }
+
+    /// Returns the number of renegotiations carried out on the connection.
+    pub fn total_renegotiations(&self) -> uint {
+        unsafe {
+            // This is defined as a macro:
+            //      #define SSL_total_renegotiations(ssl) \
+            //          SSL_ctrl((ssl),SSL_CTRL_GET_TOTAL_RENEGOTIATIONS,0,NULL)
+            ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_GET_TOTAL_RENEGOTIATIONS, 0,
+                          ptr::null_mut()) as uint
+        }
+    }
+
+    fn client_renegotiated(&self) -> bool {
+        get_handshake_state(self.ssl as *const ffi::SSL) == CLIENT_RENEGOTIATED
+    }
+
+    /// Makes a client ask the server to staple a certificate status
+    /// response of type `status` to the handshake. This must be called
+    /// before the handshake.
+    pub fn set_status_type(&self, status: StatusType) -> Result<(), SslError> {
+        let ret = unsafe {
+            // This is defined as a macro:
+            //      #define SSL_set_tlsext_status_type(ssl, type) \
+            //          SSL_ctrl(ssl,SSL_CTRL_SET_TLSEXT_STATUS_REQ_TYPE,type, NULL)
+            ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_SET_TLSEXT_STATUS_REQ_TYPE,
+                          status.to_raw(), ptr::null_mut())
+        };
+
+        if ret == 1 {
+            Ok(())
+        } else {
+            Err(SslError::get())
+        }
+    }
+
+    /// Returns the DER-encoded OCSP response the server stapled to the
+    /// handshake, if any.
+    pub fn get_ocsp_response(&self) -> Option<Vec<u8>> {
+        unsafe {
+            let mut response: *const u8 = ptr::null();
+            // This is defined as a macro:
+            //      #define SSL_get_tlsext_status_ocsp_resp(ssl, arg) \
+            //          SSL_ctrl(ssl,SSL_CTRL_GET_TLSEXT_STATUS_REQ_OCSP_RESP,0, (void *)arg)
+            let len = ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_GET_TLSEXT_STATUS_REQ_OCSP_RESP, 0,
+                                    &mut response as *mut *const u8 as *mut c_void);
+            if len < 0 || response.is_null() {
+                None
+            } else {
+                Some(slice::raw::buf_as_slice(response, len as uint, |r| r.to_vec()))
+            }
+        }
+    }
+
+    /// Sets the DER-encoded OCSP response a server staples to the
+    /// handshake, e.g. from a server name callback.
+    ///
+    /// The response must not be empty.
+    pub fn set_ocsp_response(&self, response: &[u8]) -> Result<(), SslError> {
+        if response.is_empty() {
+            return Err(InvalidInput("the OCSP response is empty".to_string()));
+        }
+        unsafe {
+            // OpenSSL takes ownership of the response and frees it with the
+            // connection
+            let buf = ffi::CRYPTO_malloc(response.len() as c_int, ptr::null(), 0) as *mut u8;
+            try_ssl_null!(buf);
+            ptr::copy_nonoverlapping_memory(buf, response.as_ptr(), response.len());
+
+            // This is defined as a macro:
+            //      #define SSL_set_tlsext_status_ocsp_resp(ssl, arg, arglen) \
+            //          SSL_ctrl(ssl,SSL_CTRL_SET_TLSEXT_STATUS_REQ_OCSP_RESP,arglen, (void *)arg)
+            ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_SET_TLSEXT_STATUS_REQ_OCSP_RESP,
+                          response.len() as c_long, buf as *mut c_void);
+        }
+        Ok(())
+    }
+
+    /// Derives `len` bytes of keying material from the connection's master
+    /// secret as described in RFC 5705, e.g. for channel binding.
+    ///
+    /// Both ends derive the same material from the same `label` and
+    /// `context`. This fails if the handshake hasn't finished.
+    pub fn export_keying_material(&self, label: &str, context: Option<&[u8]>, len: uint)
+                                  -> Result<Vec<u8>, SslError> {
+        // OpenSSL dereferences the session without checking it exists
+        // This is defined as a macro:
+        //      #define SSL_is_init_finished(a) (SSL_state((a)) == SSL_ST_OK)
+        if unsafe { ffi::SSL_state(self.ssl as *const ffi::SSL) } != ffi::SSL_ST_OK {
+            return Err(InvalidInput("the handshake has not finished".to_string()));
+        }
+
+        let mut out = Vec::from_elem(len, 0u8);
+        let (context, context_len, use_context) = match context {
+            Some(context) => (context.as_ptr(), context.len(), 1),
+            None => (ptr::null(), 0, 0)
+        };
+        unsafe {
+            try_ssl!(ffi::SSL_export_keying_material(self.ssl, out.as_mut_ptr(),
+                                                     len as size_t,
+                                                     label.as_ptr() as *const c_char,
+                                                     label.len() as size_t, context,
+                                                     context_len as size_t, use_context));
+        }
+        Ok(out)
+    }
+
+    /// Sets the maximum size of the datagrams a DTLS connection sends,
+    /// excluding IP and UDP headers. Handshake messages are fragmented to
+    /// fit.
+    pub fn set_mtu(&self, mtu: uint) {
+        unsafe {
+            // OpenSSL can't query the MTU of the memory BIOs it's actually
+            // connected to
+            ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_OPTIONS, ffi::SSL_OP_NO_QUERY_MTU,
+                          ptr::null_mut());
+            // This is defined as a macro:
+            //      #define SSL_set_mtu(ssl, mtu) \
+            //          SSL_ctrl((ssl),SSL_CTRL_SET_MTU,(mtu),NULL)
+            ffi::SSL_ctrl(self.ssl, ffi::SSL_CTRL_SET_MTU, mtu as c_long, ptr::null_mut());
+            // OpenSSL has no way to read it back
+            ffi::SSL_set_ex_data(self.ssl, MTU_IDX, mtu as *mut c_void);
+        }
+    }
+
+    /// Returns the MTU set with `set_mtu`, if any.
+    pub fn get_mtu(&self) -> Option<uint> {
+        match unsafe { ffi::SSL_get_ex_data(self.ssl as *const ffi::SSL, MTU_IDX) as uint } {
+            0 => None,
+            mtu => Some(mtu)
+        }
+    }
+
+    /// Returns the time in milliseconds until a DTLS handshake message
+    /// should be retransmitted with `handle_dtls_timeout`, or None if no
+    /// retransmission is scheduled.
+    pub fn get_dtls_timeout(&self) -> Option<u64> {
+        let mut tv = ffi::timeval { tv_sec: 0, tv_usec: 0 };
+        let ret = unsafe {
+            // This is defined as a macro:
+            //      #define DTLSv1_get_timeout(ssl, arg) \
+            //          SSL_ctrl(ssl,DTLS_CTRL_GET_TIMEOUT,0, (void *)arg)
+            ffi::SSL_ctrl(self.ssl, ffi::DTLS_CTRL_GET_TIMEOUT, 0,
+                          &mut tv as *mut ffi::timeval as *mut c_void)
+        };
+
+        if ret == 1 {
+            Some(tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000)
+        } else {
+            None
+        }
+    }
+
+    /// Retransmits the last DTLS handshake flight if its timer has expired,
+    /// returning whether it did. This fails once too many retransmissions
+    /// have gone unanswered.
+    pub fn handle_dtls_timeout(&self) -> Result<bool, SslError> {
+        let ret = unsafe {
+            // This is defined as a macro:
+            //      #define DTLSv1_handle_timeout(ssl) \
+            //          SSL_ctrl(ssl,DTLS_CTRL_HANDLE_TIMEOUT,0, NULL)
+            ffi::SSL_ctrl(self.ssl, ffi::DTLS_CTRL_HANDLE_TIMEOUT, 0, ptr::null_mut())
+        };
+
+        if ret < 0 {
+            Err(SslError::get())
+        } else {
+            Ok(ret > 0)
+        }
+    }
+
+    /// Sets a callback inspecting each protocol message the connection sends
+    /// or receives, e.g. to debug interoperability problems.
+    pub fn set_msg_callback(&self, callback: MsgCallback) {
+        unsafe {
+            ffi::SSL_set_ex_data(self.ssl, MSG_IDX, mem::transmute(callback));
+            ffi::SSL_set_msg_callback(self.ssl, Some(raw_msg));
+        }
+    }
+
+    /// Describes the state of the connection, e.g. `SSLv3 read server hello
+    /// A`.
+    pub fn state_string(&self) -> String {
+        unsafe {
+            let state = ffi::SSL_state_string_long(self.ssl as *const ffi::SSL);
+            string::raw::from_buf(state as *const u8)
+        }
+    }
+
+    /// Returns the host name the client requested with SNI, if any.
+    pub fn get_servername(&self) -> Option<String> {
+        unsafe {
+            let name = ffi::SSL_get_servername(self.ssl as *const ffi::SSL,
+                                               ffi::TLSEXT_NAMETYPE_host_name as c_int);
+            if name.is_null() {
+                None
+            } else {
+                Some(string::raw::from_buf(name as *const u8))
+            }
+        }
+    }
+
+    /// Switches the connection to another context, e.g. from a server name
+    /// callback to serve a different certificate.
+    ///
+    /// Only the certificate and private key are taken from the new context;
+    /// settings such as verification and options are those of the original.
+    pub fn set_ssl_context(&mut self, ctx: &SslContext) -> Result<(), SslError> {
+        unsafe {
+            try_ssl_null!(ffi::SSL_set_SSL_CTX(self.ssl, ctx.ctx));
+        }
+        Ok(())
+    }
+
+    /// Returns the certificate presented by the peer, if any.
+    pub fn get_peer_certificate<'a>(&self) -> Option<X509<'a>> {
+        unsafe {
+            let cert = ffi::SSL_get_peer_certificate(self.ssl as *const ffi::SSL);
+            if cert.is_null() {
+                None
+            } else {
+                Some(X509::new(cert, true))
+            }
         }
     }
 
+    /// Returns the result of verifying the peer's certificate, or `None` if
+    /// it was verified successfully or no certificate was presented.
+    ///
+    /// The certificate is checked even in `SslVerifyNone` mode, where a
+    /// failure doesn't abort the handshake, so the result can be inspected
+    /// once it completes.
+    pub fn get_verify_result(&self) -> Option<X509ValidationError> {
+        let res = unsafe { ffi::SSL_get_verify_result(self.ssl as *const ffi::SSL) };
+        X509ValidationError::from_raw(res as c_int)
+    }
+
+    /// Returns the CAs named by the server when requesting a client
+    /// certificate, in OpenSSL's one line format, e.g. `/CN=example`.
+    ///
+    /// On a server, returns the names it sends instead.
+    pub fn get_client_ca_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        unsafe {
+            let list = ffi::SSL_get_client_CA_list(self.ssl as *const ffi::SSL);
+            if list.is_null() {
+                return names;
+            }
+            for i in range(0, ffi::sk_num(list as *const ffi::_STACK)) {
+                let name = ffi::sk_value(list as *const ffi::_STACK, i) as *mut ffi::X509_NAME;
+                let mut buf = [0 as c_char, ..256];
+                let s = ffi::X509_NAME_oneline(name, buf.as_mut_ptr(), buf.len() as c_int);
+                if !s.is_null() {
+                    names.push(string::raw::from_buf(s as *const u8));
+                }
+            }
+        }
+        names
+    }
+
+    /// Returns the certificate chain presented by the peer, starting with
+    /// its own certificate.
+    ///
+    /// Servers don't receive the client's own certificate as part of the
+    /// chain; it is only returned by `get_peer_certificate`.
+    pub fn get_peer_cert_chain<'a>(&self) -> Vec<X509<'a>> {
+        let mut chain = vec![];
+        unsafe {
+            let certs = ffi::SSL_get_peer_cert_chain(self.ssl as *const ffi::SSL);
+            if certs.is_null() {
+                return chain;
+            }
+            for i in range(0, ffi::sk_num(certs as *const ffi::_STACK)) {
+                let cert = ffi::sk_value(certs as *const ffi::_STACK, i) as *mut ffi::X509;
+                // Only fails if memory runs out, in which case the copy is
+                // left out rather than handed out as a null certificate
+                let cert = ffi::X509_dup(cert);
+                if !cert.is_null() {
+                    chain.push(X509::new(cert, true));
+                }
+            }
+        }
+        chain
+    }
 }
 
 #[deriving(FromPrimitive)]
@@ -397,28 +2549,159 @@ enum LibSslError {
 }
 
 /// A stream wrapper which handles SSL encryption for an underlying stream.
+///
+/// The underlying stream can be anything readable and writable, such as a
+/// TCP or Unix socket, or one which has already carried plaintext, e.g. to
+/// upgrade a connection with STARTTLS.
+///
+/// The underlying stream may be non-blocking or have timeouts set. A read
+/// which would block then fails with the stream's error, and written data
+/// is buffered until a `flush` succeeds.
 pub struct SslStream<S> {
     stream: S,
     ssl: Ssl,
-    buf: Vec<u8>
+    buf: Vec<u8>,
+    // Encrypted data not yet accepted by the underlying stream
+    write_buf: Vec<u8>
 }
 
-impl<S: Stream> SslStream<S> {
-    /// Attempts to create a new SSL stream from a given `Ssl` instance.
-    pub fn new_from(ssl: Ssl, stream: S) -> Result<SslStream<S>, SslError> {
-        let mut ssl = SslStream {
+/// A stream whose operations can be made to time out, so that a stalled peer
+/// can't block an `SslStream` forever.
+///
+/// As with `TcpStream`, timeouts are deadlines: operations started after
+/// `timeout_ms` milliseconds from the call fail with `TimedOut`.
+pub trait TimeoutStream {
+    /// Sets the deadline of both reads and writes, or clears it if None.
+    fn set_timeout(&mut self, timeout_ms: Option<u64>);
+    /// Sets the deadline of reads, or clears it if None.
+    fn set_read_timeout(&mut self, timeout_ms: Option<u64>);
+    /// Sets the deadline of writes, or clears it if None.
+    fn set_write_timeout(&mut self, timeout_ms: Option<u64>);
+}
+
+impl TimeoutStream for TcpStream {
+    fn set_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.set_timeout(timeout_ms)
+    }
+
+    fn set_read_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.set_read_timeout(timeout_ms)
+    }
+
+    fn set_write_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.set_write_timeout(timeout_ms)
+    }
+}
+
+// The most plaintext carried by a single TLS record
+static MAX_PLAINTEXT_LEN: uint = 16 * 1024;
+
+/// An error returned by a handshake performed with `SslStream::connect` or
+/// `SslStream::accept`
+pub enum HandshakeError<S> {
+    /// The handshake failed
+    HandshakeFailure(SslError),
+    /// The handshake is waiting on the underlying stream, and can be
+    /// resumed once it's ready
+    HandshakeInterrupted(MidHandshakeSslStream<S>)
+}
+
+impl<S> HandshakeError<S> {
+    fn into_error(self) -> SslError {
+        match self {
+            HandshakeFailure(err) => err,
+            HandshakeInterrupted(stream) => stream.error
+        }
+    }
+
+    // A handshake interrupted by a timeout can't be resumed by a caller
+    // expecting it to complete
+    fn into_timeout_error(self) -> SslError {
+        match self.into_error() {
+            WantRead(err) | WantWrite(err) => StreamError(err),
+            err => err
+        }
+    }
+}
+
+/// An SSL stream whose handshake is waiting on a non-blocking underlying
+/// stream
+pub struct MidHandshakeSslStream<S> {
+    stream: SslStream<S>,
+    error: SslError
+}
+
+impl<S: Reader + Writer> MidHandshakeSslStream<S> {
+    /// Returns the error which interrupted the handshake, either
+    /// `WantRead` or `WantWrite`.
+    pub fn get_error(&self) -> &SslError {
+        &self.error
+    }
+
+    /// Returns the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream.stream
+    }
+
+    /// Returns the underlying stream, e.g. to register it with an event
+    /// loop.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream.stream
+    }
+
+    /// Resumes the handshake.
+    pub fn handshake(mut self) -> Result<SslStream<S>, HandshakeError<S>> {
+        let ret = self.stream.in_retry_wrapper(|ssl| { ssl.do_handshake() });
+        self.stream.handshake_result(ret)
+    }
+}
+
+impl<S: Reader + Writer> SslStream<S> {
+    fn new_base(ssl: Ssl, stream: S) -> SslStream<S> {
+        SslStream {
             stream: stream,
             ssl: ssl,
             // Maximum TLS record size is 16k
-            buf: Vec::from_elem(16 * 1024, 0u8)
-        };
+            buf: Vec::from_elem(16 * 1024, 0u8),
+            write_buf: vec![]
+        }
+    }
+
+    /// Performs the client side of the handshake over `stream`, which may
+    /// be non-blocking.
+    pub fn connect(ssl: Ssl, stream: S) -> Result<SslStream<S>, HandshakeError<S>> {
+        let mut stream = SslStream::new_base(ssl, stream);
+        let ret = stream.in_retry_wrapper(|ssl| { ssl.connect() });
+        stream.handshake_result(ret)
+    }
+
+    /// Performs the server side of the handshake over `stream`, which may
+    /// be non-blocking.
+    pub fn accept(ssl: Ssl, stream: S) -> Result<SslStream<S>, HandshakeError<S>> {
+        let mut stream = SslStream::new_base(ssl, stream);
+        let ret = stream.in_retry_wrapper(|ssl| { ssl.accept() });
+        stream.handshake_result(ret)
+    }
 
-        match ssl.in_retry_wrapper(|ssl| { ssl.connect() }) {
-            Ok(_) => Ok(ssl),
-            Err(err) => Err(err)
+    fn handshake_result(self, ret: Result<c_int, SslError>)
+                        -> Result<SslStream<S>, HandshakeError<S>> {
+        match ret {
+            Ok(_) => Ok(self),
+            Err(err @ WantRead(_)) | Err(err @ WantWrite(_)) => {
+                Err(HandshakeInterrupted(MidHandshakeSslStream {
+                    stream: self,
+                    error: err
+                }))
+            }
+            Err(err) => Err(HandshakeFailure(err))
         }
     }
 
+    /// Attempts to create a new SSL stream from a given `Ssl` instance.
+    pub fn new_from(ssl: Ssl, stream: S) -> Result<SslStream<S>, SslError> {
+        SslStream::connect(ssl, stream).map_err(|err| err.into_error())
+    }
+
     /// Creates a new SSL stream
     pub fn new(ctx: &SslContext, stream: S) -> Result<SslStream<S>, SslError> {
         let ssl = match Ssl::new(ctx) {
@@ -429,24 +2712,70 @@ impl<S: Stream> SslStream<S> {
         SslStream::new_from(ssl, stream)
     }
 
+    /// Creates a new SSL stream, sending `hostname` to the server with SNI
+    /// (Server Name Indication) so that virtual hosts serve the right
+    /// certificate.
+    ///
+    /// This doesn't check that the certificate matches `hostname`; use an
+    /// `SslConnector` for that.
+    pub fn new_with_hostname(ctx: &SslContext, stream: S, hostname: &str)
+                             -> Result<SslStream<S>, SslError> {
+        let ssl = try!(Ssl::new(ctx));
+        try!(ssl.set_hostname(hostname));
+        SslStream::new_from(ssl, stream)
+    }
+
+    /// Attempts to create a new server side SSL stream from a given `Ssl`
+    /// instance, performing the handshake with the connected client.
+    pub fn new_server_from(ssl: Ssl, stream: S) -> Result<SslStream<S>, SslError> {
+        SslStream::accept(ssl, stream).map_err(|err| err.into_error())
+    }
+
+    /// Creates a new server side SSL stream
+    pub fn new_server(ctx: &SslContext, stream: S) -> Result<SslStream<S>, SslError> {
+        let ssl = try!(Ssl::new(ctx));
+        SslStream::new_server_from(ssl, stream)
+    }
+
+    /// Returns the `Ssl` object associated with this stream.
+    pub fn get_ssl(&self) -> &Ssl {
+        &self.ssl
+    }
+
+    /// Returns the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the underlying stream, e.g. to set timeouts.
+    ///
+    /// Reading from or writing to it directly will corrupt the SSL session.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
     fn in_retry_wrapper(&mut self, blk: |&Ssl| -> c_int)
             -> Result<c_int, SslError> {
         loop {
             let ret = blk(&self.ssl);
+            if self.ssl.client_renegotiated() {
+                return Err(RenegotiationRejected);
+            }
             if ret > 0 {
                 return Ok(ret);
             }
 
             match self.ssl.get_error(ret) {
                 ErrorWantRead => {
-                    try_ssl_stream!(self.flush());
-                    let len = try_ssl_stream!(self.stream.read(self.buf.as_mut_slice()));
+                    try!(self.flush().map_err(write_error));
+                    let len = try!(self.stream.read(self.buf.as_mut_slice()).map_err(read_error));
                     self.ssl.get_rbio().write(self.buf.slice_to(len));
                 }
-                ErrorWantWrite => { try_ssl_stream!(self.flush()) }
+                ErrorWantWrite => { try!(self.flush().map_err(write_error)) }
                 ErrorZeroReturn => return Err(SslSessionClosed),
-                ErrorSsl => return Err(SslError::get()),
-                _ => unreachable!()
+                ErrorSsl => return Err(self.ssl.get_ssl_error()),
+                ErrorSyscall => return Err(self.ssl.get_syscall_error()),
+                _ => return Err(self.ssl.get_ssl_error())
             }
         }
     }
@@ -454,11 +2783,29 @@ impl<S: Stream> SslStream<S> {
     fn write_through(&mut self) -> IoResult<()> {
         loop {
             match self.ssl.get_wbio().read(self.buf.as_mut_slice()) {
-                Some(len) => try!(self.stream.write(self.buf.slice_to(len))),
+                Some(len) => self.write_buf.push_all(self.buf.slice_to(len)),
                 None => break
             };
         }
-        Ok(())
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+
+        match self.stream.write(self.write_buf.as_slice()) {
+            Ok(()) => {
+                self.write_buf.clear();
+                Ok(())
+            }
+            Err(err) => {
+                match err.kind {
+                    ShortWrite(len) => {
+                        self.write_buf = self.write_buf.slice_from(len).to_vec();
+                    }
+                    _ => {}
+                }
+                Err(err)
+            }
+        }
     }
 
     /// Get the compression currently in use.  The result will be
@@ -475,36 +2822,399 @@ impl<S: Stream> SslStream<S> {
 
         Some(s)
     }
+
+    /// Shuts the connection down by sending a close_notify alert. The
+    /// underlying stream is left open.
+    ///
+    /// If `wait` is set and the peer's close_notify hasn't been received
+    /// yet, this reads until it is. Any application data read meanwhile is
+    /// discarded.
+    pub fn shutdown(&mut self, wait: bool) -> Result<ShutdownResult, SslError> {
+        let ret = self.ssl.shutdown();
+        try!(self.flush().map_err(write_error));
+        match ret {
+            1 => return Ok(ShutdownReceived),
+            0 => {}
+            _ => return Err(self.ssl.get_ssl_error())
+        }
+
+        if !wait {
+            return Ok(ShutdownSent);
+        }
+        try!(self.in_retry_wrapper(|ssl| { ssl.shutdown() }));
+        Ok(ShutdownReceived)
+    }
+
+    /// Returns which close_notify alerts have been sent and received.
+    ///
+    /// A read reporting the end of the stream without
+    /// `SSL_RECEIVED_SHUTDOWN` set means the connection was closed without
+    /// a close_notify, and the data may have been truncated by an attacker.
+    pub fn get_shutdown(&self) -> ShutdownState {
+        self.ssl.get_shutdown()
+    }
+
+    /// Renegotiates the connection, e.g. to request a client certificate
+    /// after the initial handshake.
+    ///
+    /// On the client the new handshake is completed before returning. A
+    /// server only sends the request; the handshake is carried out by the
+    /// following reads once the client responds.
+    pub fn renegotiate(&mut self) -> Result<(), SslError> {
+        try!(self.ssl.renegotiate());
+        try!(self.in_retry_wrapper(|ssl| { ssl.do_handshake() }));
+        try!(self.flush().map_err(write_error));
+        Ok(())
+    }
+
+    /// Derives keying material from the connection's master secret, as
+    /// with `Ssl::export_keying_material`.
+    pub fn export_keying_material(&self, label: &str, context: Option<&[u8]>, len: uint)
+                                  -> Result<Vec<u8>, SslError> {
+        self.ssl.export_keying_material(label, context, len)
+    }
+
+    /// Returns the result of verifying the peer's certificate, as with
+    /// `Ssl::get_verify_result`.
+    pub fn verify_result(&self) -> Option<X509ValidationError> {
+        self.ssl.get_verify_result()
+    }
+
+    /// Returns the certificate presented by the peer, if any.
+    pub fn peer_certificate<'a>(&self) -> Option<X509<'a>> {
+        self.ssl.get_peer_certificate()
+    }
+
+    /// Returns the certificate chain presented by the peer, as with
+    /// `Ssl::get_peer_cert_chain`.
+    pub fn peer_cert_chain<'a>(&self) -> Vec<X509<'a>> {
+        self.ssl.get_peer_cert_chain()
+    }
+
+    /// Returns the stream's session, for resumption by later connections
+    /// with `Ssl::set_session`.
+    pub fn session(&self) -> Option<SslSession> {
+        self.ssl.get_session()
+    }
+
+    /// Returns whether the handshake resumed a previous session.
+    pub fn session_reused(&self) -> bool {
+        self.ssl.session_reused()
+    }
+
+    /// Returns the number of bytes which can be read without reading from
+    /// the underlying stream.
+    ///
+    /// Event driven code must keep reading while this is nonzero, as the
+    /// buffered data won't make the underlying stream readable.
+    pub fn pending(&self) -> uint {
+        self.ssl.pending()
+    }
+
+    /// Writes the slices of `bufs` in order, as if they were concatenated.
+    ///
+    /// Small slices, e.g. a header followed by a body, are combined into as
+    /// few TLS records as possible rather than each being sent in a record
+    /// of its own. Data filling whole records is written without copying.
+    pub fn write_vectored(&mut self, bufs: &[&[u8]]) -> IoResult<()> {
+        let mut record = Vec::with_capacity(MAX_PLAINTEXT_LEN);
+        for buf in bufs.iter() {
+            let buf = *buf;
+            if record.len() + buf.len() < MAX_PLAINTEXT_LEN {
+                record.push_all(buf);
+                continue;
+            }
+
+            let fill = MAX_PLAINTEXT_LEN - record.len();
+            record.push_all(buf.slice_to(fill));
+            try!(self.write(record.as_slice()));
+            record.clear();
+
+            let rest = buf.slice_from(fill);
+            let whole = rest.len() - rest.len() % MAX_PLAINTEXT_LEN;
+            if whole > 0 {
+                try!(self.write(rest.slice_to(whole)));
+            }
+            record.push_all(rest.slice_from(whole));
+        }
+        if !record.is_empty() {
+            try!(self.write(record.as_slice()));
+        }
+        Ok(())
+    }
+
+    /// Reads data into `buf` like `read`, but leaves it in the stream to be
+    /// returned again by the next read.
+    ///
+    /// This allows a protocol to be identified from its first bytes before
+    /// handing the stream to the code implementing it.
+    pub fn peek(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let ret = self.in_retry_wrapper(|ssl| { ssl.peek(buf) });
+        stream_read_result(ret)
+    }
+
+    /// Returns the name of the negotiated cipher suite, e.g.
+    /// `ECDHE-RSA-AES128-GCM-SHA256`, or None before the handshake.
+    pub fn get_cipher_name(&self) -> Option<String> {
+        unsafe {
+            let cipher = ffi::SSL_get_current_cipher(self.ssl.ssl as *const ffi::SSL);
+            if cipher.is_null() {
+                return None;
+            }
+
+            let name = ffi::SSL_CIPHER_get_name(cipher);
+            Some(string::raw::from_buf(name as *const u8))
+        }
+    }
+
+    /// Returns the number of secret key bits of the negotiated cipher
+    /// suite, or None before the handshake.
+    pub fn get_cipher_bits(&self) -> Option<i32> {
+        unsafe {
+            let cipher = ffi::SSL_get_current_cipher(self.ssl.ssl as *const ffi::SSL);
+            if cipher.is_null() {
+                None
+            } else {
+                Some(ffi::SSL_CIPHER_get_bits(cipher, ptr::null_mut()) as i32)
+            }
+        }
+    }
+
+    /// Returns the negotiated protocol version, e.g. `TLSv1.2`.
+    pub fn get_version(&self) -> String {
+        unsafe {
+            let version = ffi::SSL_get_version(self.ssl.ssl as *const ffi::SSL);
+            string::raw::from_buf(version as *const u8)
+        }
+    }
+}
+
+// Whether an error from the underlying stream means it would have blocked
+fn would_block(err: &IoError) -> bool {
+    match err.kind {
+        ResourceUnavailable | TimedOut | ShortWrite(_) => true,
+        _ => false
+    }
+}
+
+fn read_error(err: IoError) -> SslError {
+    if would_block(&err) { WantRead(err) } else { StreamError(err) }
+}
+
+fn write_error(err: IoError) -> SslError {
+    if would_block(&err) { WantWrite(err) } else { StreamError(err) }
+}
+
+impl<S: Reader + Writer + TimeoutStream> SslStream<S> {
+    /// Performs the client side of the handshake over `stream`, failing with
+    /// a `TimedOut` `StreamError` if it takes longer than `timeout_ms`
+    /// milliseconds.
+    ///
+    /// Any timeouts set on `stream` are cleared once the handshake
+    /// completes.
+    pub fn connect_timeout(ssl: Ssl, mut stream: S, timeout_ms: u64)
+                           -> Result<SslStream<S>, SslError> {
+        stream.set_timeout(Some(timeout_ms));
+        let mut stream = try!(SslStream::connect(ssl, stream)
+                                  .map_err(|err| err.into_timeout_error()));
+        stream.stream.set_timeout(None);
+        Ok(stream)
+    }
+
+    /// Performs the server side of the handshake over `stream`, failing with
+    /// a `TimedOut` `StreamError` if it takes longer than `timeout_ms`
+    /// milliseconds.
+    ///
+    /// Any timeouts set on `stream` are cleared once the handshake
+    /// completes.
+    pub fn accept_timeout(ssl: Ssl, mut stream: S, timeout_ms: u64)
+                          -> Result<SslStream<S>, SslError> {
+        stream.set_timeout(Some(timeout_ms));
+        let mut stream = try!(SslStream::accept(ssl, stream)
+                                  .map_err(|err| err.into_timeout_error()));
+        stream.stream.set_timeout(None);
+        Ok(stream)
+    }
+
+    /// Sets the deadline of both reads and writes on the underlying stream,
+    /// as with `TimeoutStream::set_timeout`.
+    ///
+    /// An operation which times out fails with `TimedOut`. The stream
+    /// remains usable, and the operation can be retried with a new timeout;
+    /// any partially received record is kept by OpenSSL.
+    pub fn set_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.stream.set_timeout(timeout_ms)
+    }
+
+    /// Sets the deadline of reads on the underlying stream.
+    pub fn set_read_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.stream.set_read_timeout(timeout_ms)
+    }
+
+    /// Sets the deadline of writes on the underlying stream.
+    pub fn set_write_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.stream.set_write_timeout(timeout_ms)
+    }
+}
+
+/// A TLS connection which exchanges encrypted data through `read_tls` and
+/// `write_tls` rather than over a stream, for transports OpenSSL can't
+/// drive itself such as message queues or event loops.
+///
+/// Plaintext is read and written through the `Reader` and `Writer`
+/// implementations, which fail with `ResourceUnavailable` until enough
+/// encrypted data has been received.
+pub struct SslEngine {
+    ssl: Ssl
+}
+
+impl SslEngine {
+    /// Creates an engine for the client side of a connection.
+    pub fn new_client(ssl: Ssl) -> SslEngine {
+        unsafe { ffi::SSL_set_connect_state(ssl.ssl) }
+        SslEngine { ssl: ssl }
+    }
+
+    /// Creates an engine for the server side of a connection.
+    pub fn new_server(ssl: Ssl) -> SslEngine {
+        unsafe { ffi::SSL_set_accept_state(ssl.ssl) }
+        SslEngine { ssl: ssl }
+    }
+
+    /// Returns the `Ssl` object associated with this engine.
+    pub fn get_ssl(&self) -> &Ssl {
+        &self.ssl
+    }
+
+    /// Passes encrypted data received from the peer to the engine.
+    pub fn read_tls(&mut self, buf: &[u8]) {
+        self.ssl.get_rbio().write(buf);
+    }
+
+    /// Takes encrypted data to be sent to the peer, returning the number of
+    /// bytes copied into `buf` or None if there is none.
+    pub fn write_tls(&mut self, buf: &mut [u8]) -> Option<uint> {
+        self.ssl.get_wbio().read(buf)
+    }
+
+    /// Returns whether there is encrypted data to be taken with `write_tls`.
+    pub fn wants_write(&self) -> bool {
+        unsafe { ffi::BIO_ctrl_pending(ffi::SSL_get_wbio(self.ssl.ssl)) > 0 }
+    }
+
+    /// Returns whether the handshake has yet to complete.
+    pub fn is_handshaking(&self) -> bool {
+        unsafe { ffi::SSL_state(self.ssl.ssl as *const ffi::SSL) != ffi::SSL_ST_OK }
+    }
+
+    /// Advances the handshake as far as the data received allows, failing
+    /// with `WantRead` if it needs more.
+    pub fn handshake(&mut self) -> Result<(), SslError> {
+        let ret = self.ssl.do_handshake();
+        self.result(ret).map(|_| ())
+    }
+
+    fn result(&self, ret: c_int) -> Result<c_int, SslError> {
+        if self.ssl.client_renegotiated() {
+            return Err(RenegotiationRejected);
+        }
+        if ret > 0 {
+            return Ok(ret);
+        }
+
+        match self.ssl.get_error(ret) {
+            ErrorWantRead => Err(WantRead(IoError {
+                kind: ResourceUnavailable,
+                desc: "more TLS data must be received",
+                detail: None
+            })),
+            // Memory BIOs never refuse writes, but report it rather than
+            // panicking if OpenSSL says otherwise
+            ErrorWantWrite => Err(WantWrite(IoError {
+                kind: ResourceUnavailable,
+                desc: "TLS data must be sent",
+                detail: None
+            })),
+            ErrorZeroReturn => Err(SslSessionClosed),
+            ErrorSsl => Err(self.ssl.get_ssl_error()),
+            ErrorSyscall => Err(self.ssl.get_syscall_error()),
+            _ => Err(self.ssl.get_ssl_error())
+        }
+    }
+}
+
+// Converts errors from an SslStream, SslEngine or DtlsStream for their
+// Reader and Writer implementations
+fn ssl_io_error(err: SslError) -> IoError {
+    match err {
+        StreamError(err) | WantRead(err) | WantWrite(err) => err,
+        SslSessionClosed =>
+            IoError {
+                kind: EndOfFile,
+                desc: "SSL session closed",
+                detail: None
+            },
+        RenegotiationRejected => renegotiation_rejected(),
+        err => IoError {
+            kind: OtherIoError,
+            desc: "SSL error",
+            detail: Some(err.to_string())
+        }
+    }
 }
 
-impl<S: Stream> Reader for SslStream<S> {
+impl Reader for SslEngine {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
-        match self.in_retry_wrapper(|ssl| { ssl.read(buf) }) {
-            Ok(len) => Ok(len as uint),
-            Err(SslSessionClosed) =>
-                Err(IoError {
-                    kind: EndOfFile,
-                    desc: "SSL session closed",
-                    detail: None
-                }),
-            Err(StreamError(e)) => Err(e),
-            _ => unreachable!()
+        let ret = self.ssl.read(buf);
+        self.result(ret).map(|len| len as uint).map_err(ssl_io_error)
+    }
+}
+
+impl Writer for SslEngine {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut start = 0;
+        while start < buf.len() {
+            let ret = self.ssl.write(buf.slice_from(start));
+            let len = try!(self.result(ret).map_err(ssl_io_error));
+            start += len as uint;
         }
+        Ok(())
+    }
+}
+
+fn renegotiation_rejected() -> IoError {
+    IoError {
+        kind: ConnectionAborted,
+        desc: "client-initiated renegotiation rejected",
+        detail: None
+    }
+}
+
+impl<S: Reader + Writer> Reader for SslStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let ret = self.in_retry_wrapper(|ssl| { ssl.read(buf) });
+        stream_read_result(ret)
     }
 }
 
-impl<S: Stream> Writer for SslStream<S> {
+// Maps the result of an SSL_read or SSL_peek to that of a Reader
+fn stream_read_result(ret: Result<c_int, SslError>) -> IoResult<uint> {
+    ret.map(|len| len as uint).map_err(ssl_io_error)
+}
+
+impl<S: Reader + Writer> Writer for SslStream<S> {
     fn write(&mut self, buf: &[u8]) -> IoResult<()> {
         let mut start = 0;
         while start < buf.len() {
             let ret = self.in_retry_wrapper(|ssl| {
                 ssl.write(buf.split_at(start).val1())
             });
-            match ret {
-                Ok(len) => start += len as uint,
-                _ => unreachable!()
+            start += try!(ret.map_err(ssl_io_error)) as uint;
+            // Data the stream isn't ready for stays buffered until a flush
+            match self.write_through() {
+                Err(ref err) if would_block(err) => {}
+                ret => try!(ret)
             }
-            try!(self.write_through());
         }
         Ok(())
     }
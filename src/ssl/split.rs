@@ -0,0 +1,107 @@
+//! Independent read and write halves of an `SslStream`.
+//!
+//! `SslStream::split` lets full-duplex protocols read in one task while
+//! writing in another. The halves share the connection's `Ssl`, but each does
+//! its I/O through its own clone of the underlying stream, so a read waiting
+//! for data doesn't hold up writes.
+
+use std::io::{IoResult, Reader, Writer, ResourceUnavailable};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ssl::{SslEngine, SslStream};
+
+struct Shared<S> {
+    engine: Mutex<SslEngine>,
+    // Serializes writes to the underlying stream
+    writer: Mutex<S>
+}
+
+impl<S: Writer> Shared<S> {
+    // Sends the encrypted data produced by the engine. The engine's lock is
+    // only released once the writer's is held, so records are sent in the
+    // order they were produced, but the other half can carry on while the
+    // stream is busy.
+    fn send<'a>(&'a self, mut engine: MutexGuard<'a, SslEngine>) -> IoResult<()> {
+        let mut data = vec![];
+        let mut buf = Vec::from_elem(16 * 1024, 0u8);
+        loop {
+            match engine.write_tls(buf.as_mut_slice()) {
+                Some(len) => data.push_all(buf.slice_to(len)),
+                None => break
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let mut writer = self.writer.lock();
+        drop(engine);
+        try!(writer.write(data.as_slice()));
+        writer.flush()
+    }
+}
+
+/// The reading half of an `SslStream`
+pub struct SslReadHalf<S> {
+    shared: Arc<Shared<S>>,
+    stream: S,
+    buf: Vec<u8>
+}
+
+impl<S: Reader + Writer> Reader for SslReadHalf<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        loop {
+            let mut engine = self.shared.engine.lock();
+            let res = engine.read(buf);
+            // Reads may need to respond to the peer, e.g. to renegotiate
+            try!(self.shared.send(engine));
+            match res {
+                Err(ref err) if err.kind == ResourceUnavailable => {}
+                res => return res
+            }
+
+            let len = try!(self.stream.read(self.buf.as_mut_slice()));
+            self.shared.engine.lock().read_tls(self.buf.slice_to(len));
+        }
+    }
+}
+
+/// The writing half of an `SslStream`
+///
+/// Data is sent as it is written rather than when the half is flushed.
+pub struct SslWriteHalf<S> {
+    shared: Arc<Shared<S>>
+}
+
+impl<S: Reader + Writer> Writer for SslWriteHalf<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut engine = self.shared.engine.lock();
+        let res = engine.write(buf);
+        try!(self.shared.send(engine));
+        res
+    }
+}
+
+impl<S: Reader + Writer + Clone + Send> SslStream<S> {
+    /// Splits the stream into halves which can be used from different tasks,
+    /// after sending any data it buffered.
+    ///
+    /// Each half uses its own clone of the underlying stream, which must
+    /// support reading from one clone while writing to another, as
+    /// `TcpStream` does.
+    pub fn split(mut self) -> IoResult<(SslReadHalf<S>, SslWriteHalf<S>)> {
+        try!(self.flush());
+
+        let SslStream { stream, ssl, buf, .. } = self;
+        let shared = Arc::new(Shared {
+            engine: Mutex::new(SslEngine { ssl: ssl }),
+            writer: Mutex::new(stream.clone())
+        });
+        let reader = SslReadHalf {
+            shared: shared.clone(),
+            stream: stream,
+            buf: buf
+        };
+        Ok((reader, SslWriteHalf { shared: shared }))
+    }
+}
@@ -1,23 +1,283 @@
-use std::io::{File, Open, Write, Writer};
-use std::io::net::tcp::TcpStream;
+use std::io::{File, Open, Write, Writer, Listener, Acceptor, IoResult, ConnectionAborted};
+use std::io::net::ip::{Ipv4Addr, SocketAddr};
+use std::io::net::tcp::{TcpListener, TcpAcceptor, TcpStream};
+use std::io::net::udp::UdpSocket;
+use std::io::TempDir;
+use std::io::{ChanReader, ChanWriter, MemReader, OtherIoError, ResourceUnavailable, TimedOut};
 use std::num::FromStrRadix;
+use std::collections::HashMap;
 use std::str;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUint, INIT_ATOMIC_UINT, SeqCst};
 
 use crypto::hash::{SHA256};
-use ssl::{Sslv23, SslContext, SslStream, SslVerifyPeer, SslVerifyNone};
+use crypto::pkey::{PKey};
+use dh::{Dh};
+use ssl::{Ssl, Sslv23, SslContext, SslEngine, SslSession, SslStream, SslVerifyPeer, SslVerifyNone};
+use ssl::{SSL_OP_NO_SSLV2, SSL_OP_NO_SSLV3, SSL_OP_NO_TLSV1, SSL_OP_NO_TLSV1_1, SSL_OP_NO_TLSV1_2};
+use ssl::{TicketKey, TicketKeyAction, UseTicketKey, NoTicketKey};
+use ssl::{SSL_SESS_CACHE_OFF, SSL_SESS_CACHE_SERVER, SSL_SESS_CACHE_NO_AUTO_CLEAR};
+use ssl::{SSL_SESS_CACHE_NO_INTERNAL, SSL_OP_NO_TICKET};
+use ssl::{SSL_MODE_AUTO_RETRY, SSL_MODE_RELEASE_BUFFERS, SslContextIndex, SslIndex};
+use ssl::{SSL_SENT_SHUTDOWN, SSL_RECEIVED_SHUTDOWN, ShutdownReceived, HandshakeInterrupted};
+use ssl::{SSL_OP_NO_COMPRESSION, Tls1, Tls1_1, Tls1_2, CurveP256, CurveP384};
+use ssl::{Dtlsv1, StatusOcsp, InfoEvent, SSL_CB_HANDSHAKE_DONE, alert_string};
+use ssl::connector::{SslConnectorBuilder, SslAcceptor, SslAcceptorBuilder, verify_hostname};
+use ssl::connector::{MozillaModern, MozillaIntermediate, MozillaOld};
+use ssl::dtls::{DatagramStream, DtlsStream};
+use ssl::error::{VerifyError, HostnameMismatch, InvalidInput, WantRead, StreamError};
 use x509::{X509Generator, DigitalSignature, KeyEncipherment, ClientAuth, ServerAuth, X509StoreContext};
+use x509::{X509, X509DepthZeroSelfSignedCert};
+use x509::verify::X509Store;
+
+// A key and self-signed certificate for localhost
+fn server_cert() -> (X509, PKey) {
+    X509Generator::new()
+        .set_bitlength(1024)
+        .set_CN("localhost")
+        .set_sign_hash(SHA256)
+        .generate()
+        .unwrap()
+}
+
+// Listens on a local port, handing the listener to `handle` in a new task,
+// and returns the port
+fn spawn_server(handle: proc(TcpAcceptor): Send) -> u16 {
+    let mut listener = TcpListener::bind("127.0.0.1", 0).unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let server = listener.listen().unwrap();
+    spawn(proc() { handle(server) });
+    port
+}
+
+// Performs the server side of the handshake for the first `count`
+// connections to a local port, and returns the port
+fn spawn_acceptor(acceptor: SslAcceptor, count: uint) -> u16 {
+    spawn_server(proc(mut server) {
+        for _ in range(0, count) {
+            let stream = server.accept().unwrap();
+            let _ = acceptor.accept(stream);
+        }
+    })
+}
 
 #[test]
 fn test_new_ctx() {
     SslContext::new(Sslv23).unwrap();
 }
 
+#[test]
+fn test_set_private_key() {
+    let mut pkey = PKey::new();
+    pkey.gen(512);
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_private_key(&pkey).is_none());
+}
+
+#[test]
+fn test_check_private_key() {
+    let (cert, pkey) = server_cert();
+    let mut other = PKey::new();
+    other.gen(1024);
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.check_private_key().is_some());
+    // A key which doesn't match the certificate is refused, and the
+    // certificate dropped
+    assert!(ctx.set_private_key(&other).is_some());
+    assert!(ctx.check_private_key().is_some());
+
+    // A certificate which doesn't match the key is accepted, but the key is
+    // dropped, which the check reports
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_private_key(&other).is_none());
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.check_private_key().is_some());
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&pkey).is_none());
+    assert!(ctx.check_private_key().is_none());
+}
+
+#[test]
+fn test_ex_data() {
+    static DROPPED: AtomicUint = INIT_ATOMIC_UINT;
+
+    struct Counted(uint);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, SeqCst);
+        }
+    }
+
+    let ctx_index = SslContextIndex::<String>::new().unwrap();
+    let ssl_index = SslIndex::<Counted>::new().unwrap();
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.ex_data(&ctx_index).is_none());
+    assert!(ctx.set_ex_data(&ctx_index, "shared".to_string()).is_none());
+    assert_eq!(ctx.ex_data(&ctx_index).unwrap().as_slice(), "shared");
+
+    let mut ssl = Ssl::new(&ctx).unwrap();
+    assert_eq!(ssl.ctx_ex_data(&ctx_index).unwrap().as_slice(), "shared");
+    // Replacing the context's data would free it from under ssl
+    assert!(ctx.set_ex_data(&ctx_index, "other".to_string()).is_some());
+    assert_eq!(ssl.ctx_ex_data(&ctx_index).unwrap().as_slice(), "shared");
+    assert!(ssl.ex_data(&ssl_index).is_none());
+    ssl.set_ex_data(&ssl_index, Counted(1));
+    ssl.set_ex_data(&ssl_index, Counted(2));
+    assert_eq!(DROPPED.load(SeqCst), 1);
+    match ssl.ex_data(&ssl_index) {
+        Some(&Counted(n)) => assert_eq!(n, 2),
+        None => fail!("missing data")
+    }
+
+    drop(ssl);
+    assert_eq!(DROPPED.load(SeqCst), 2);
+}
+
+#[test]
+fn test_set_tmp_dh() {
+    let mut file = File::open(&Path::new("test/dhparams.pem")).unwrap();
+    let dh = Dh::from_pem(&mut file).unwrap();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_tmp_dh(&dh).is_none());
+}
+
+#[test]
+fn test_dhe_handshake() {
+    let (cert, pkey) = server_cert();
+    let mut file = File::open(&Path::new("test/dhparams.pem")).unwrap();
+    let dh = Dh::from_pem(&mut file).unwrap();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&pkey).is_none());
+    assert!(ctx.set_tmp_dh(&dh).is_none());
+    drop(dh);
+    assert!(ctx.set_cipher_list("DHE-RSA-AES128-SHA").is_none());
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let _ = SslStream::new_server(&ctx, stream);
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(stream.get_cipher_name().unwrap().as_slice(), "DHE-RSA-AES128-SHA");
+}
+
+#[test]
+fn test_set_tmp_ecdh() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_tmp_ecdh(CurveP256).is_none());
+    assert!(ctx.set_tmp_ecdh(CurveP384).is_none());
+}
+
+#[test]
+#[cfg(feature = "ecdh_auto")]
+fn test_set_ecdh_auto() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_ecdh_auto(true).is_none());
+}
+
+#[test]
+fn test_verify_depth() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert_eq!(ctx.get_verify_depth(), None);
+    ctx.set_verify_depth(3);
+    assert_eq!(ctx.get_verify_depth(), Some(3));
+}
+
+#[test]
+fn test_session_cache_settings() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_session_cache_mode(SSL_SESS_CACHE_SERVER | SSL_SESS_CACHE_NO_AUTO_CLEAR);
+    assert!(ctx.get_session_cache_mode() == SSL_SESS_CACHE_SERVER | SSL_SESS_CACHE_NO_AUTO_CLEAR);
+    let prev = ctx.set_session_cache_mode(SSL_SESS_CACHE_OFF);
+    assert!(prev == SSL_SESS_CACHE_SERVER | SSL_SESS_CACHE_NO_AUTO_CLEAR);
+
+    ctx.set_session_cache_size(128);
+    assert_eq!(ctx.get_session_cache_size(), 128);
+
+    ctx.set_session_timeout(600);
+    assert_eq!(ctx.get_session_timeout(), 600);
+}
+
+#[test]
+fn test_options() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    let opts = ctx.set_options(SSL_OP_NO_SSLV3 | SSL_OP_NO_COMPRESSION);
+    assert!(opts.contains(SSL_OP_NO_SSLV3 | SSL_OP_NO_COMPRESSION));
+    assert!(ctx.get_options().contains(SSL_OP_NO_SSLV3));
+
+    let opts = ctx.clear_options(SSL_OP_NO_SSLV3);
+    assert!(!opts.contains(SSL_OP_NO_SSLV3));
+    assert!(ctx.get_options().contains(SSL_OP_NO_COMPRESSION));
+}
+
+#[test]
+fn test_mode() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    let mode = ctx.set_mode(SSL_MODE_AUTO_RETRY | SSL_MODE_RELEASE_BUFFERS);
+    assert!(mode.contains(SSL_MODE_AUTO_RETRY | SSL_MODE_RELEASE_BUFFERS));
+    assert!(ctx.get_mode().contains(SSL_MODE_RELEASE_BUFFERS));
+
+    let mode = ctx.clear_mode(SSL_MODE_AUTO_RETRY);
+    assert!(!mode.contains(SSL_MODE_AUTO_RETRY));
+    assert!(ctx.get_mode().contains(SSL_MODE_RELEASE_BUFFERS));
+}
+
+#[test]
+fn test_set_cipher_list() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_cipher_list("ECDHE+AESGCM:DHE+AESGCM:!aNULL").is_none());
+    match ctx.set_cipher_list("NOT-A-CIPHER") {
+        Some(InvalidInput(_)) => {}
+        err => fail!("unexpected result {}", err)
+    }
+}
+
+#[test]
+fn test_protocol_range() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_protocol_range(Tls1, Tls1_1).is_none());
+    let opts = ctx.get_options();
+    assert!(opts.contains(SSL_OP_NO_SSLV2 | SSL_OP_NO_SSLV3 | SSL_OP_NO_TLSV1_2));
+    assert!(!opts.intersects(SSL_OP_NO_TLSV1 | SSL_OP_NO_TLSV1_1));
+
+    assert!(ctx.set_protocol_range(Tls1_1, Tls1_2).is_none());
+    let opts = ctx.get_options();
+    assert!(opts.contains(SSL_OP_NO_SSLV3 | SSL_OP_NO_TLSV1));
+    assert!(!opts.intersects(SSL_OP_NO_TLSV1_1 | SSL_OP_NO_TLSV1_2));
+
+    assert!(ctx.set_protocol_range(Tls1_2, Tls1).is_some());
+}
+
 #[test]
 fn test_new_sslstream() {
     let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
     SslStream::new(&SslContext::new(Sslv23).unwrap(), stream).unwrap();
 }
 
+#[test]
+fn test_negotiated_cipher() {
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    let stream = SslStream::new(&SslContext::new(Sslv23).unwrap(), stream).unwrap();
+    assert!(stream.get_cipher_name().is_some());
+    assert!(stream.get_cipher_bits().unwrap() >= 128);
+    assert!(stream.get_version().as_slice().starts_with("TLSv1"));
+}
+
+#[test]
+fn test_new_sslstream_with_hostname() {
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    SslStream::new_with_hostname(&SslContext::new(Sslv23).unwrap(), stream, "foobar.com").unwrap();
+}
+
 #[test]
 fn test_verify_untrusted() {
     let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
@@ -29,6 +289,18 @@ fn test_verify_untrusted() {
     }
 }
 
+#[test]
+fn test_verify_untrusted_error() {
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_verify(SslVerifyPeer, None);
+    match SslStream::new(&ctx, stream) {
+        Err(VerifyError(X509DepthZeroSelfSignedCert, _)) => {}
+        Ok(_) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+}
+
 #[test]
 fn test_verify_trusted() {
     let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
@@ -114,6 +386,154 @@ fn test_verify_callback_load_certs() {
     assert!(SslStream::new(&ctx, stream).is_ok());
 }
 
+#[test]
+fn test_verify_callback_depth_override() {
+    fn callback(preverify_ok: bool, x509_ctx: &X509StoreContext) -> bool {
+        assert_eq!(x509_ctx.get_error_depth(), 0);
+        assert!(x509_ctx.get_current_cert().is_some());
+        if !preverify_ok {
+            match x509_ctx.get_error() {
+                Some(X509DepthZeroSelfSignedCert) => x509_ctx.set_error(None),
+                err => fail!("unexpected error {}", err)
+            }
+        }
+        true
+    }
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_verify(SslVerifyPeer, Some(callback));
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    assert!(stream.get_ssl().get_peer_certificate().is_some());
+}
+
+#[test]
+fn test_verify_result() {
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_verify(SslVerifyNone, None);
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(stream.verify_result(), Some(X509DepthZeroSelfSignedCert));
+}
+
+#[test]
+fn test_set_default_verify_paths() {
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_default_verify_paths().is_none());
+}
+
+#[test]
+fn test_add_root_certificate() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_acceptor(acceptor, 2);
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_verify(SslVerifyPeer, None);
+    assert!(ctx.add_root_certificate(&cert).is_none());
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    assert!(SslStream::new(&ctx, stream).is_ok());
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_verify(SslVerifyPeer, None);
+    ctx.set_cert_store(X509Store::new().unwrap());
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    match SslStream::new(&ctx, stream) {
+        Err(VerifyError(X509DepthZeroSelfSignedCert, _)) => {}
+        Ok(_) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+}
+
+#[test]
+fn test_client_ca_list() {
+    let (cert, pkey) = server_cert();
+    let (ca, _) = X509Generator::new()
+        .set_bitlength(1024)
+        .set_CN("client-ca")
+        .set_sign_hash(SHA256)
+        .generate()
+        .unwrap();
+    let mut acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap();
+    acceptor.context_mut().set_verify(SslVerifyPeer, None);
+    assert!(acceptor.context_mut().set_client_ca_list(&[ca]).is_none());
+    let acceptor = acceptor.build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(stream.get_ssl().get_client_ca_names(), vec!["/CN=client-ca".to_string()]);
+}
+
+#[test]
+fn test_in_memory_cert_and_key() {
+    let (cert, pkey) = server_cert();
+    // Load both from bytes, as if fetched from a secret store
+    let mut pem = vec![];
+    cert.write_pem(&mut pem).unwrap();
+    let cert = X509::from_pem(&mut MemReader::new(pem)).unwrap();
+    let mut key = PKey::new();
+    key.load_priv(pkey.save_priv().as_slice());
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&key).is_none());
+    assert!(ctx.check_private_key().is_none());
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let _ = SslStream::new_server(&ctx, stream);
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    let peer = stream.peer_certificate().unwrap();
+    assert_eq!(peer.fingerprint(SHA256), cert.fingerprint(SHA256));
+}
+
+#[test]
+fn test_add_extra_chain_cert() {
+    let (cert, pkey) = server_cert();
+    let (intermediate, _) = X509Generator::new()
+        .set_bitlength(1024)
+        .set_CN("intermediate")
+        .set_sign_hash(SHA256)
+        .generate()
+        .unwrap();
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&pkey).is_none());
+    assert!(ctx.add_extra_chain_cert(&intermediate).is_none());
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let _ = SslStream::new_server(&ctx, stream);
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    let chain = stream.peer_cert_chain();
+    assert_eq!(chain.len(), 2);
+    assert_eq!(chain[1].fingerprint(SHA256), intermediate.fingerprint(SHA256));
+}
+
+#[test]
+fn test_peer_cert_chain() {
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+
+    let cert = stream.peer_certificate().unwrap();
+    let chain = stream.peer_cert_chain();
+    assert!(!chain.is_empty());
+    assert_eq!(chain[0].fingerprint(SHA256), cert.fingerprint(SHA256));
+}
+
 #[test]
 fn test_verify_trusted_get_error_ok() {
     fn callback(_preverify_ok: bool, x509_ctx: &X509StoreContext) -> bool {
@@ -176,7 +596,7 @@ fn test_verify_callback_data() {
     // Please update if "test/cert.pem" will ever change
     let node_hash_str = "6204f6617e1af7495394250655f43600cd483e2dfc2005e92d0fe439d0723c34";
     let node_id = hash_str_to_vec(node_hash_str);
-    ctx.set_verify_with_data(SslVerifyNone, callback, node_id);
+    assert!(ctx.set_verify_with_data(SslVerifyNone, callback, node_id).is_none());
     ctx.set_verify_depth(1);
 
     match SslStream::new(&ctx, stream) {
@@ -186,6 +606,24 @@ fn test_verify_callback_data() {
 }
 
 
+#[test]
+fn test_verify_callback_data_types() {
+    fn verify_bytes(_preverify_ok: bool, _x509_ctx: &X509StoreContext, data: &Vec<u8>) -> bool {
+        data.len() == 3
+    }
+    fn verify_string(_preverify_ok: bool, _x509_ctx: &X509StoreContext, data: &String) -> bool {
+        !data.is_empty()
+    }
+
+    // Each context's data is freed as the type it was stored with
+    let mut a = SslContext::new(Sslv23).unwrap();
+    assert!(a.set_verify_with_data(SslVerifyPeer, verify_bytes, vec![1u8, 2, 3]).is_none());
+    let mut b = SslContext::new(Sslv23).unwrap();
+    assert!(b.set_verify_with_data(SslVerifyPeer, verify_string, "data".to_string()).is_none());
+    drop(a);
+    drop(b);
+}
+
 #[test]
 fn test_write() {
     let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
@@ -206,6 +644,1039 @@ fn test_read() {
     print!("{}", str::from_utf8(buf.as_slice()));
 }
 
+#[test]
+fn test_connector_untrusted() {
+    let stream = TcpStream::connect("127.0.0.1", 15418).unwrap();
+    let connector = SslConnectorBuilder::new().unwrap().build();
+    match connector.connect("localhost", stream) {
+        Err(VerifyError(X509DepthZeroSelfSignedCert, _)) => {}
+        Ok(_) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+}
+
+#[test]
+fn test_connector_acceptor() {
+    let (cert, pkey) = server_cert();
+    let (intermediate, _) = X509Generator::new()
+        .set_bitlength(1024)
+        .set_CN("intermediate")
+        .set_sign_hash(SHA256)
+        .generate()
+        .unwrap();
+    let fingerprint = intermediate.fingerprint(SHA256);
+
+    let dir = TempDir::new("connector").unwrap();
+    let ca_path = dir.path().join("ca.pem");
+    cert.write_pem(&mut File::create(&ca_path).unwrap()).unwrap();
+
+    let acceptor = SslAcceptorBuilder::new_with_chain(&cert, &[intermediate], &pkey)
+                       .unwrap()
+                       .build();
+    let port = spawn_server(proc(mut server) {
+        for _ in range(0u, 2) {
+            let stream = server.accept().unwrap();
+            match acceptor.accept(stream) {
+                Ok(mut stream) => {
+                    stream.write(b"hello").unwrap();
+                    stream.flush().unwrap();
+                }
+                Err(_) => {}
+            }
+        }
+    });
+
+    let mut builder = SslConnectorBuilder::new().unwrap();
+    assert!(builder.context_mut().set_CA_file(&ca_path).is_none());
+    let connector = builder.build();
+
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = connector.connect("localhost", stream).unwrap();
+    let chain = stream.peer_cert_chain();
+    assert_eq!(chain.len(), 2);
+    assert_eq!(chain[1].fingerprint(SHA256), fingerprint);
+    assert_eq!(stream.read_exact(5).unwrap().as_slice(), b"hello");
+
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    match connector.connect("example.com", stream) {
+        Err(HostnameMismatch(ref host)) if host.as_slice() == "example.com" => {}
+        Ok(_) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+}
+
+#[test]
+fn test_connector_ip_address() {
+    let (cert, pkey) = X509Generator::new()
+        .set_bitlength(1024)
+        .set_CN("localhost")
+        .set_alt_names(["IP:127.0.0.1"])
+        .set_sign_hash(SHA256)
+        .generate()
+        .unwrap();
+
+    let dir = TempDir::new("connector_ip").unwrap();
+    let ca_path = dir.path().join("ca.pem");
+    cert.write_pem(&mut File::create(&ca_path).unwrap()).unwrap();
+
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+    let port = spawn_acceptor(acceptor, 2);
+
+    let mut builder = SslConnectorBuilder::new().unwrap();
+    assert!(builder.context_mut().set_CA_file(&ca_path).is_none());
+    let connector = builder.build();
+
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    connector.connect("127.0.0.1", stream).unwrap();
+
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    match connector.connect("127.0.0.2", stream) {
+        Err(HostnameMismatch(ref host)) if host.as_slice() == "127.0.0.2" => {}
+        Ok(_) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+    assert!(verify_hostname(&cert, "127.0.0.1"));
+    // The common name is ignored once there are alternative names
+    assert!(!verify_hostname(&cert, "localhost"));
+}
+
+#[test]
+fn test_mozilla_profiles() {
+    for profile in [MozillaModern, MozillaIntermediate, MozillaOld].iter() {
+        let mut ctx = SslContext::new(Sslv23).unwrap();
+        profile.apply(&mut ctx).unwrap();
+        assert!(ctx.get_options().contains(SSL_OP_NO_SSLV2 | SSL_OP_NO_COMPRESSION));
+    }
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    MozillaModern.apply(&mut ctx).unwrap();
+    assert!(ctx.get_options().contains(SSL_OP_NO_SSLV3 | SSL_OP_NO_TLSV1 | SSL_OP_NO_TLSV1_1));
+    assert!(!ctx.get_options().contains(SSL_OP_NO_TLSV1_2));
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    MozillaIntermediate.apply(&mut ctx).unwrap();
+    assert!(ctx.get_options().contains(SSL_OP_NO_SSLV3));
+    assert!(!ctx.get_options().intersects(SSL_OP_NO_TLSV1 | SSL_OP_NO_TLSV1_1 | SSL_OP_NO_TLSV1_2));
+}
+
+#[test]
+fn test_mozilla_intermediate_handshake() {
+    let (cert, pkey) = server_cert();
+    let mut acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap();
+    MozillaIntermediate.apply(acceptor.context_mut()).unwrap();
+    let acceptor = acceptor.build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    let cipher = stream.get_cipher_name().unwrap();
+    assert!(MozillaIntermediate.ciphers().split(':').any(|c| c == cipher.as_slice()));
+}
+
+#[test]
+fn test_verify_hostname() {
+    let (cert, _) = server_cert();
+    assert!(verify_hostname(&cert, "localhost"));
+    assert!(verify_hostname(&cert, "LOCALHOST"));
+    assert!(!verify_hostname(&cert, "example.com"));
+}
+
+#[test]
+fn test_connector_without_hostname_verification() {
+    let (cert, pkey) = server_cert();
+
+    let dir = TempDir::new("connector").unwrap();
+    let ca_path = dir.path().join("ca.pem");
+    cert.write_pem(&mut File::create(&ca_path).unwrap()).unwrap();
+
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+    let port = spawn_acceptor(acceptor, 1);
+
+    let mut builder = SslConnectorBuilder::new().unwrap();
+    assert!(builder.context_mut().set_CA_file(&ca_path).is_none());
+    builder.set_verify_hostname(false);
+    let connector = builder.build();
+
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    assert!(connector.connect("example.com", stream).is_ok());
+}
+
+#[test]
+fn test_servername_callback() {
+    fn select(ssl: &mut Ssl, alt: &SslContext) -> bool {
+        match ssl.get_servername() {
+            Some(ref name) if name.as_slice() == "alt.test" => ssl.set_ssl_context(alt).is_ok(),
+            Some(ref name) if name.as_slice() == "default.test" => true,
+            _ => false
+        }
+    }
+
+    fn server_ctx(cn: &str) -> (SslContext, Vec<u8>) {
+        let (cert, pkey) = X509Generator::new()
+            .set_bitlength(1024)
+            .set_CN(cn)
+            .set_sign_hash(SHA256)
+            .generate()
+            .unwrap();
+        let mut ctx = SslContext::new(Sslv23).unwrap();
+        assert!(ctx.set_certificate(&cert).is_none());
+        assert!(ctx.set_private_key(&pkey).is_none());
+        (ctx, cert.fingerprint(SHA256).unwrap())
+    }
+
+    let (mut ctx, default_fp) = server_ctx("default.test");
+    let (alt, alt_fp) = server_ctx("alt.test");
+    assert!(ctx.set_servername_callback_with_data(select, alt).is_none());
+
+    let port = spawn_server(proc(mut server) {
+        for _ in range(0u, 3) {
+            let stream = server.accept().unwrap();
+            let _ = SslStream::new_server(&ctx, stream);
+        }
+    });
+
+    let client = SslContext::new(Sslv23).unwrap();
+    for &(name, fp) in [("default.test", &default_fp), ("alt.test", &alt_fp)].iter() {
+        let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+        let stream = SslStream::new_with_hostname(&client, stream, name).unwrap();
+        let cert = stream.get_ssl().get_peer_certificate().unwrap();
+        assert_eq!(&cert.fingerprint(SHA256).unwrap(), fp);
+    }
+
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    assert!(SslStream::new_with_hostname(&client, stream, "unknown.test").is_err());
+}
+
+#[test]
+fn test_session_resumption() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_acceptor(acceptor, 2);
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    assert!(!stream.session_reused());
+    let session = stream.session().unwrap();
+
+    let session = SslSession::from_der(session.to_der().unwrap().as_slice()).unwrap();
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_session(&session).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new_from(ssl, stream).unwrap();
+    assert!(stream.session_reused());
+}
+
+#[test]
+fn test_shared_ticket_keys() {
+    fn ticket_key(key: &TicketKey, name: Option<&[u8]>) -> TicketKeyAction {
+        match name {
+            Some(name) if name != key.name.as_slice() => NoTicketKey,
+            _ => UseTicketKey(key.clone())
+        }
+    }
+
+    let (cert, pkey) = server_cert();
+    let key = TicketKey { name: [1, ..16], aes_key: [2, ..16], hmac_key: [3, ..16] };
+
+    // Two servers which share nothing but the ticket key
+    let mut ports = vec![];
+    for _ in range(0u, 2) {
+        let mut ctx = SslContext::new(Sslv23).unwrap();
+        assert!(ctx.set_certificate(&cert).is_none());
+        assert!(ctx.set_private_key(&pkey).is_none());
+        ctx.set_session_cache_mode(SSL_SESS_CACHE_OFF);
+        assert!(ctx.set_ticket_key_callback(ticket_key, key.clone()).is_none());
+
+        ports.push(spawn_server(proc(mut server) {
+            let stream = server.accept().unwrap();
+            let _ = SslStream::new_server(&ctx, stream);
+        }));
+    }
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", ports[0]).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    let session = stream.session().unwrap();
+
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_session(&session).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", ports[1]).unwrap();
+    let stream = SslStream::new_from(ssl, stream).unwrap();
+    assert!(stream.session_reused());
+}
+
+#[test]
+fn test_external_session_cache() {
+    type Cache = Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>;
+
+    fn new_session(cache: &Cache, session: &SslSession) {
+        cache.lock().insert(session.id(), session.to_der().unwrap());
+    }
+
+    fn get_session(cache: &Cache, id: &[u8]) -> Option<SslSession> {
+        cache.lock().find(&id.to_vec()).map(|der| SslSession::from_der(der.as_slice()).unwrap())
+    }
+
+    fn remove_session(cache: &Cache, id: &[u8]) {
+        cache.lock().remove(&id.to_vec());
+    }
+
+    let (cert, pkey) = server_cert();
+    let cache: Cache = Arc::new(Mutex::new(HashMap::new()));
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&pkey).is_none());
+    assert!(ctx.set_session_id_context(b"test").is_none());
+    ctx.set_options(SSL_OP_NO_TICKET);
+    ctx.set_session_cache_mode(SSL_SESS_CACHE_SERVER | SSL_SESS_CACHE_NO_INTERNAL);
+    assert!(ctx.set_session_cache_callbacks(new_session, get_session, remove_session,
+                                            cache.clone()).is_none());
+
+    let port = spawn_server(proc(mut server) {
+        for _ in range(0u, 2) {
+            let stream = server.accept().unwrap();
+            let _ = SslStream::new_server(&ctx, stream);
+        }
+    });
+
+    let client = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&client, stream).unwrap();
+    let session = stream.session().unwrap();
+    assert!(cache.lock().contains_key(&session.id()));
+
+    let ssl = Ssl::new(&client).unwrap();
+    ssl.set_session(&session).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new_from(ssl, stream).unwrap();
+    assert!(stream.session_reused());
+}
+
+fn renegotiation_server(reject: bool) -> (u16, Receiver<IoResult<Vec<u8>>>) {
+    let (cert, pkey) = server_cert();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&pkey).is_none());
+    ctx.set_reject_client_renegotiation(reject);
+
+    let (tx, rx) = channel();
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = SslStream::new_server(&ctx, stream).unwrap();
+        tx.send(stream.read_exact(5));
+    });
+    (port, rx)
+}
+
+#[test]
+fn test_client_renegotiation() {
+    let (port, rx) = renegotiation_server(false);
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    stream.renegotiate().unwrap();
+    assert_eq!(stream.get_ssl().total_renegotiations(), 1);
+    stream.write(b"hello").unwrap();
+    stream.flush().unwrap();
+    assert_eq!(rx.recv().unwrap().as_slice(), b"hello");
+}
+
+#[test]
+fn test_reject_client_renegotiation() {
+    let (port, rx) = renegotiation_server(true);
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    assert!(stream.renegotiate().is_err());
+    assert_eq!(rx.recv().unwrap_err().kind, ConnectionAborted);
+}
+
+#[test]
+fn test_shutdown() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        assert_eq!(stream.read_to_end().unwrap().as_slice(), b"hello");
+        assert!(stream.get_shutdown() == SSL_RECEIVED_SHUTDOWN);
+        assert_eq!(stream.shutdown(false).unwrap(), ShutdownReceived);
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    stream.write(b"hello").unwrap();
+    assert!(stream.get_shutdown().is_empty());
+    assert_eq!(stream.shutdown(true).unwrap(), ShutdownReceived);
+    assert!(stream.get_shutdown() == SSL_SENT_SHUTDOWN | SSL_RECEIVED_SHUTDOWN);
+}
+
+#[test]
+fn test_nonblocking_handshake() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let (tx, rx) = channel();
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        rx.recv();
+        let mut stream = acceptor.accept(stream).unwrap();
+        stream.write(b"hello").unwrap();
+        stream.flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let mut stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    stream.set_read_timeout(Some(10));
+    let mut stream = match SslStream::connect(Ssl::new(&ctx).unwrap(), stream) {
+        Err(HandshakeInterrupted(stream)) => stream,
+        _ => fail!("the handshake should wait for the server")
+    };
+    match *stream.get_error() {
+        WantRead(_) => {}
+        ref err => fail!("unexpected error {}", err)
+    }
+
+    tx.send(());
+    stream.get_mut().set_read_timeout(None);
+    let mut stream = match stream.handshake() {
+        Ok(stream) => stream,
+        Err(_) => fail!("the handshake should complete")
+    };
+    assert_eq!(stream.read_exact(5).unwrap().as_slice(), b"hello");
+}
+
+// One end of an in-memory connection
+struct ChanStream {
+    reader: ChanReader,
+    writer: ChanWriter
+}
+
+impl ChanStream {
+    fn pair() -> (ChanStream, ChanStream) {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        let a = ChanStream { reader: ChanReader::new(rx1), writer: ChanWriter::new(tx2) };
+        let b = ChanStream { reader: ChanReader::new(rx2), writer: ChanWriter::new(tx1) };
+        (a, b)
+    }
+}
+
+impl Reader for ChanStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.reader.read(buf)
+    }
+}
+
+impl Writer for ChanStream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.writer.write(buf)
+    }
+}
+
+#[test]
+fn test_in_memory_stream() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+    let (client, server) = ChanStream::pair();
+
+    spawn(proc() {
+        let mut stream = acceptor.accept(server).unwrap();
+        let msg = stream.read_exact(5).unwrap();
+        stream.write(msg.as_slice()).unwrap();
+        stream.flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let mut stream = SslStream::new(&ctx, client).unwrap();
+    stream.write(b"hello").unwrap();
+    stream.flush().unwrap();
+    assert_eq!(stream.read_exact(5).unwrap().as_slice(), b"hello");
+}
+
+#[test]
+fn test_engine() {
+    fn pump(from: &mut SslEngine, to: &mut SslEngine) {
+        let mut buf = [0u8, ..16 * 1024];
+        loop {
+            match from.write_tls(buf) {
+                Some(len) => to.read_tls(buf.slice_to(len)),
+                None => break
+            }
+        }
+    }
+
+    let (cert, pkey) = server_cert();
+    let mut server_ctx = SslContext::new(Sslv23).unwrap();
+    assert!(server_ctx.set_certificate(&cert).is_none());
+    assert!(server_ctx.set_private_key(&pkey).is_none());
+    let client_ctx = SslContext::new(Sslv23).unwrap();
+
+    let mut client = SslEngine::new_client(Ssl::new(&client_ctx).unwrap());
+    let mut server = SslEngine::new_server(Ssl::new(&server_ctx).unwrap());
+    let mut buf = [0u8, ..5];
+    assert_eq!(client.read(buf).unwrap_err().kind, ResourceUnavailable);
+    assert!(client.wants_write());
+
+    fn step(engine: &mut SslEngine) {
+        match engine.handshake() {
+            Ok(()) | Err(WantRead(_)) => {}
+            Err(err) => fail!("unexpected handshake error: {}", err)
+        }
+    }
+
+    while client.is_handshaking() || server.is_handshaking() {
+        step(&mut client);
+        pump(&mut client, &mut server);
+        step(&mut server);
+        pump(&mut server, &mut client);
+    }
+
+    client.write(b"hello").unwrap();
+    pump(&mut client, &mut server);
+    assert_eq!(server.read(buf).unwrap(), 5);
+    assert_eq!(buf.as_slice(), b"hello");
+    assert_eq!(server.read(buf).unwrap_err().kind, ResourceUnavailable);
+}
+
+// A UDP socket exchanging datagrams with a single peer, which loses the
+// first `lost` datagrams it sends
+struct UdpPeer {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    lost: uint
+}
+
+impl Reader for UdpPeer {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let (len, _) = try!(self.socket.recv_from(buf));
+        Ok(len)
+    }
+}
+
+impl Writer for UdpPeer {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        if self.lost > 0 {
+            self.lost -= 1;
+            return Ok(());
+        }
+        self.socket.send_to(buf, self.peer)
+    }
+}
+
+impl DatagramStream for UdpPeer {
+    fn set_read_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.socket.set_read_timeout(timeout_ms);
+    }
+}
+
+#[test]
+fn test_dtls() {
+    fn generate_cookie(_ssl: &Ssl, secret: &Vec<u8>) -> Vec<u8> {
+        secret.clone()
+    }
+
+    fn verify_cookie(_ssl: &Ssl, secret: &Vec<u8>, cookie: &[u8]) -> bool {
+        cookie == secret.as_slice()
+    }
+
+    let (cert, pkey) = server_cert();
+    let mut ctx = SslContext::new(Dtlsv1).unwrap();
+    assert!(ctx.set_certificate(&cert).is_none());
+    assert!(ctx.set_private_key(&pkey).is_none());
+    assert!(ctx.set_cookie_callbacks(generate_cookie, verify_cookie, b"secret".to_vec()).is_none());
+
+    let localhost = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 0 };
+    let mut server = UdpSocket::bind(localhost).unwrap();
+    let mut client = UdpSocket::bind(localhost).unwrap();
+    let server_addr = server.socket_name().unwrap();
+    let client_addr = client.socket_name().unwrap();
+
+    spawn(proc() {
+        let peer = UdpPeer { socket: server, peer: client_addr, lost: 0 };
+        let mut stream = DtlsStream::listen(Ssl::new(&ctx).unwrap(), peer).unwrap();
+        let mut buf = [0u8, ..5];
+        let len = stream.read(buf).unwrap();
+        stream.write(buf.slice_to(len)).unwrap();
+
+        // Writes larger than a record arrive in several
+        let mut buf = [0u8, ..16 * 1024];
+        let mut received = 0;
+        while received < 20000 {
+            received += stream.read(buf).unwrap();
+        }
+        stream.write(b"done").unwrap();
+    });
+
+    let ctx = SslContext::new(Dtlsv1).unwrap();
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_mtu(1200);
+    assert_eq!(ssl.get_mtu(), Some(1200));
+    assert_eq!(ssl.get_dtls_timeout(), None);
+    // The ClientHello is retransmitted once the handshake times out
+    let peer = UdpPeer { socket: client, peer: server_addr, lost: 1 };
+    let mut stream = DtlsStream::connect(ssl, peer).unwrap();
+    stream.write(b"hello").unwrap();
+    let mut buf = [0u8, ..5];
+    assert_eq!(stream.read(buf).unwrap(), 5);
+    assert_eq!(buf.as_slice(), b"hello");
+
+    stream.write(Vec::from_elem(20000, 1u8).as_slice()).unwrap();
+    let mut buf = [0u8, ..4];
+    assert_eq!(stream.read(buf).unwrap(), 4);
+    assert_eq!(buf.as_slice(), b"done");
+}
+
+#[test]
+fn test_psk() {
+    fn client_psk(_ssl: &Ssl, key: &Vec<u8>, hint: Option<&[u8]>)
+                  -> Option<(Vec<u8>, Vec<u8>)> {
+        assert_eq!(hint, Some(b"hint"));
+        Some((b"client".to_vec(), key.clone()))
+    }
+
+    fn server_psk(_ssl: &Ssl, key: &Vec<u8>, identity: &[u8]) -> Option<Vec<u8>> {
+        if identity == b"client" { Some(key.clone()) } else { None }
+    }
+
+    let key = b"0123456789abcdef".to_vec();
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_cipher_list("PSK").is_none());
+    assert!(ctx.set_psk_identity_hint("hint").is_none());
+    assert!(ctx.set_psk_server_callback(server_psk, key.clone()).is_none());
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = SslStream::new_server(&ctx, stream).unwrap();
+        stream.write(b"hello").unwrap();
+        stream.flush().unwrap();
+    });
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_cipher_list("PSK").is_none());
+    assert!(ctx.set_psk_client_callback(client_psk, key).is_none());
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    assert!(stream.get_cipher_name().unwrap().as_slice().starts_with("PSK-"));
+    assert_eq!(stream.read_exact(5).unwrap().as_slice(), b"hello");
+}
+
+#[test]
+fn test_callback_data_set_once() {
+    fn server_psk(_ssl: &Ssl, key: &Vec<u8>, _identity: &[u8]) -> Option<Vec<u8>> {
+        Some(key.clone())
+    }
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_psk_server_callback(server_psk, b"first".to_vec()).is_none());
+    match ctx.set_psk_server_callback(server_psk, b"second".to_vec()) {
+        Some(InvalidInput(_)) => {}
+        err => fail!("expected InvalidInput, got {}", err)
+    }
+}
+
+#[test]
+fn test_status_callback() {
+    fn require_status(_ssl: &Ssl, response: Option<&[u8]>) -> bool {
+        response.is_some()
+    }
+
+    fn allow_no_status(_ssl: &Ssl, response: Option<&[u8]>) -> bool {
+        response.is_none()
+    }
+
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_acceptor(acceptor, 2);
+
+    // The server doesn't staple responses
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_status_callback(require_status);
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_status_type(StatusOcsp).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    assert!(SslStream::new_from(ssl, stream).is_err());
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_status_callback(allow_no_status);
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_status_type(StatusOcsp).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new_from(ssl, stream).unwrap();
+    assert!(stream.get_ssl().get_ocsp_response().is_none());
+}
+
+#[test]
+fn test_ocsp_stapling() {
+    fn respond(_ssl: &Ssl, response: &Vec<u8>) -> Option<Vec<u8>> {
+        Some(response.clone())
+    }
+
+    // The client doesn't parse responses, so any bytes will do
+    fn check_response(_ssl: &Ssl, response: Option<&[u8]>) -> bool {
+        response == Some(b"ocsp response")
+    }
+
+    let (cert, pkey) = server_cert();
+    let mut builder = SslAcceptorBuilder::new(&cert, &pkey).unwrap();
+    assert!(builder.context_mut().set_ocsp_responder(respond, b"ocsp response".to_vec()).is_none());
+    let acceptor = builder.build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_status_callback(check_response);
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_status_type(StatusOcsp).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new_from(ssl, stream).unwrap();
+    assert_eq!(stream.get_ssl().get_ocsp_response(), Some(b"ocsp response".to_vec()));
+}
+
+#[test]
+fn test_empty_ocsp_response() {
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let ssl = Ssl::new(&ctx).unwrap();
+    match ssl.set_ocsp_response(&[]) {
+        Err(InvalidInput(_)) => {}
+        Ok(()) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+}
+
+#[test]
+fn test_export_keying_material() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let (tx, rx) = channel();
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let stream = acceptor.accept(stream).unwrap();
+        tx.send(stream.export_keying_material("EXPORTER-test", Some(b"ctx"), 32).unwrap());
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    let material = stream.export_keying_material("EXPORTER-test", Some(b"ctx"), 32).unwrap();
+    assert_eq!(material.len(), 32);
+    assert_eq!(material, rx.recv());
+
+    let other = stream.export_keying_material("EXPORTER-test", None, 32).unwrap();
+    assert!(other != material);
+}
+
+#[test]
+fn test_export_keying_material_before_handshake() {
+    let ssl = Ssl::new(&SslContext::new(Sslv23).unwrap()).unwrap();
+    match ssl.export_keying_material("EXPORTER-test", None, 32) {
+        Err(InvalidInput(..)) => {}
+        res => fail!("expected InvalidInput, got {}", res)
+    }
+}
+
+#[test]
+fn test_handshake_timeout() {
+    let (tx, rx) = channel::<()>();
+    let port = spawn_server(proc(mut server) {
+        // Hold the connection open without ever responding
+        let _stream = server.accept().unwrap();
+        rx.recv_opt().ok();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let ssl = Ssl::new(&ctx).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    match SslStream::connect_timeout(ssl, stream, 100) {
+        Err(StreamError(ref err)) if err.kind == TimedOut => {}
+        Ok(_) => fail!("expected failure"),
+        Err(err) => fail!("unexpected error {}", err)
+    }
+    tx.send(());
+}
+
+#[test]
+fn test_read_timeout() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let (tx, rx) = channel::<()>();
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        rx.recv();
+        stream.write(b"hello").unwrap();
+        stream.flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let ssl = Ssl::new(&ctx).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::connect_timeout(ssl, stream, 5000).unwrap();
+
+    stream.set_read_timeout(Some(100));
+    let mut buf = [0u8, ..5];
+    assert_eq!(stream.read(&mut buf).unwrap_err().kind, TimedOut);
+
+    tx.send(());
+    stream.set_read_timeout(None);
+    assert_eq!(stream.read_exact(5).unwrap().as_slice(), b"hello");
+}
+
+#[test]
+fn test_write_vectored() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let body = Vec::from_fn(40000, |i| i as u8);
+    let mut expected = b"HTTP/1.0 200 OK\r\n\r\n".to_vec();
+    expected.push_all(body.as_slice());
+    expected.push_all(b"done");
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        stream.write_vectored(&[b"HTTP/1.0 200 OK\r\n", b"\r\n", body.as_slice(), b"done"])
+              .unwrap();
+        stream.flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(stream.read_exact(expected.len()).unwrap(), expected);
+}
+
+#[test]
+fn test_split() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        for _ in range(0u, 3) {
+            let msg = stream.read_exact(5).unwrap();
+            stream.write(msg.as_slice()).unwrap();
+            stream.flush().unwrap();
+        }
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    let (mut reader, mut writer) = stream.split().unwrap();
+
+    let (tx, rx) = channel();
+    spawn(proc() {
+        for _ in range(0u, 3) {
+            tx.send(reader.read_exact(5).unwrap());
+        }
+    });
+    for msg in [b"hello", b"there", b"world"].iter() {
+        writer.write(*msg).unwrap();
+        assert_eq!(rx.recv().as_slice(), *msg);
+    }
+}
+
+#[test]
+fn test_pending() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        stream.write(b"hello world").unwrap();
+        stream.flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(stream.pending(), 0);
+
+    let mut buf = [0u8, ..5];
+    assert_eq!(stream.read(&mut buf).unwrap(), 5);
+    assert_eq!(stream.pending(), 6);
+    assert_eq!(stream.read_exact(6).unwrap().as_slice(), b" world");
+    assert_eq!(stream.pending(), 0);
+}
+
+#[test]
+fn test_read_garbage() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        // An application data record which fails to decrypt
+        let mut record = vec![23u8, 3, 3, 0, 32];
+        record.grow(32, 0);
+        stream.get_mut().write(record.as_slice()).unwrap();
+        stream.get_mut().flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+    let mut buf = [0u8, ..5];
+    let err = stream.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind, OtherIoError);
+    assert!(err.detail.is_some());
+}
+
+#[test]
+fn test_peek() {
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_server(proc(mut server) {
+        let stream = server.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+        stream.write(b"GET / HTTP/1.0").unwrap();
+        stream.flush().unwrap();
+    });
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let mut stream = SslStream::new(&ctx, stream).unwrap();
+
+    let mut buf = [0u8, ..4];
+    assert_eq!(stream.peek(&mut buf).unwrap(), 4);
+    assert_eq!(buf.as_slice(), b"GET ");
+    assert_eq!(stream.read_exact(14).unwrap().as_slice(), b"GET / HTTP/1.0");
+}
+
+#[test]
+#[cfg(feature = "custom_ext")]
+fn test_custom_ext() {
+    static EXT_TYPE: u16 = 0xffaa;
+    static RECEIVED: AtomicUint = INIT_ATOMIC_UINT;
+
+    fn add(_ssl: &Ssl, data: &Vec<u8>) -> Option<Vec<u8>> {
+        Some(data.clone())
+    }
+
+    fn parse(_ssl: &Ssl, data: &Vec<u8>, ext: &[u8]) -> bool {
+        assert_eq!(ext, data.as_slice());
+        RECEIVED.fetch_add(1, SeqCst);
+        true
+    }
+
+    let (cert, pkey) = server_cert();
+    let mut acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap();
+    assert!(acceptor.context_mut()
+                    .add_server_custom_ext(EXT_TYPE, add, parse, b"pong".to_vec())
+                    .is_none());
+    let acceptor = acceptor.build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    fn parse_pong(_ssl: &Ssl, _data: &Vec<u8>, ext: &[u8]) -> bool {
+        assert_eq!(ext, b"pong");
+        RECEIVED.fetch_add(1, SeqCst);
+        true
+    }
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.add_client_custom_ext(EXT_TYPE, add, parse_pong, b"pong".to_vec()).is_none());
+    assert!(ctx.add_client_custom_ext(EXT_TYPE, add, parse_pong, vec![]).is_some());
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(RECEIVED.load(SeqCst), 2);
+}
+
+#[test]
+fn test_info_callback() {
+    static HANDSHAKES: AtomicUint = INIT_ATOMIC_UINT;
+
+    fn count_handshakes(_ssl: &Ssl, event: InfoEvent, _value: i32) {
+        if event.contains(SSL_CB_HANDSHAKE_DONE) {
+            HANDSHAKES.fetch_add(1, SeqCst);
+        }
+    }
+
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_info_callback(count_handshakes);
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    let stream = SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(HANDSHAKES.load(SeqCst), 1);
+    assert_eq!(stream.get_ssl().state_string().as_slice(),
+               "SSL negotiation finished successfully");
+}
+
+#[test]
+fn test_msg_callback() {
+    static CLIENT_HELLOS: AtomicUint = INIT_ATOMIC_UINT;
+
+    // Handshake messages start with their type, which is 1 for ClientHello
+    fn count_client_hellos(_ssl: &Ssl, written: bool, _version: i32, content_type: i32,
+                           msg: &[u8]) {
+        if written && content_type == 22 && msg[0] == 1 {
+            CLIENT_HELLOS.fetch_add(1, SeqCst);
+        }
+    }
+
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    let ctx = SslContext::new(Sslv23).unwrap();
+    let ssl = Ssl::new(&ctx).unwrap();
+    ssl.set_msg_callback(count_client_hellos);
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    SslStream::new_from(ssl, stream).unwrap();
+    assert_eq!(CLIENT_HELLOS.load(SeqCst), 1);
+}
+
+#[test]
+fn test_keylog_callback() {
+    static LINES: AtomicUint = INIT_ATOMIC_UINT;
+
+    fn check_line(_ssl: &Ssl, line: &str) {
+        let fields: Vec<&str> = line.split(' ').collect();
+        assert_eq!(fields[0], "CLIENT_RANDOM");
+        assert_eq!(fields[1].len(), 64);
+        assert_eq!(fields[2].len(), 96);
+        LINES.fetch_add(1, SeqCst);
+    }
+
+    let (cert, pkey) = server_cert();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let port = spawn_acceptor(acceptor, 1);
+
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    ctx.set_keylog_callback(check_line);
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    SslStream::new(&ctx, stream).unwrap();
+    assert_eq!(LINES.load(SeqCst), 1);
+}
+
+#[test]
+fn test_alert_string() {
+    assert_eq!(alert_string((2 << 8) | 40).as_slice(), "fatal: handshake failure");
+}
+
 #[test]
 fn test_cert_gen() {
     let gen = X509Generator::new()
@@ -0,0 +1,11 @@
+use libc::c_char;
+use std::c_str::CString;
+
+// Copies a NUL terminated string owned by OpenSSL, which may be null
+pub fn c_str_to_string(s: *const c_char) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        unsafe { CString::new(s, false).to_string() }
+    }
+}
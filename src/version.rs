@@ -0,0 +1,72 @@
+//! Information about the version of OpenSSL in use.
+//!
+//! The `header_*` functions describe the headers this crate was compiled
+//! against, while the others describe the library actually linked at runtime.
+//! Version numbers are of the form `0xMNNFFPPS`: major, minor, fix, patch and
+//! status, e.g. `0x1000105f` for 1.0.1e.
+
+use libc::{c_int, c_ulong};
+
+use ffi;
+use util::c_str_to_string;
+
+fn get_string(t: c_int) -> String {
+    c_str_to_string(unsafe { ffi::SSLeay_version(t) })
+}
+
+/// Returns the version number of the headers this crate was built against.
+pub fn header_number() -> c_ulong {
+    unsafe { ffi::openssl_header_version() }
+}
+
+/// Returns the version text of the headers this crate was built against,
+/// e.g. `OpenSSL 1.0.1e 11 Feb 2013`.
+pub fn header_text() -> String {
+    c_str_to_string(unsafe { ffi::openssl_header_version_text() })
+}
+
+/// Returns the version number of the library linked at runtime.
+pub fn number() -> c_ulong {
+    unsafe { ffi::SSLeay() }
+}
+
+/// Returns the version text of the library linked at runtime.
+pub fn text() -> String {
+    get_string(ffi::SSLEAY_VERSION)
+}
+
+/// Returns the compiler flags the library was built with.
+pub fn cflags() -> String {
+    get_string(ffi::SSLEAY_CFLAGS)
+}
+
+/// Returns the date the library was built.
+pub fn built_on() -> String {
+    get_string(ffi::SSLEAY_BUILT_ON)
+}
+
+/// Returns the platform the library was built for.
+pub fn platform() -> String {
+    get_string(ffi::SSLEAY_PLATFORM)
+}
+
+/// Returns the directory OpenSSL looks for its configuration and
+/// certificates in.
+pub fn dir() -> String {
+    get_string(ffi::SSLEAY_DIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header_number, header_text, number, text, platform};
+
+    #[test]
+    fn test_versions() {
+        assert!(number() > 0);
+        assert!(text().as_slice().starts_with("OpenSSL"));
+        assert!(header_text().as_slice().starts_with("OpenSSL"));
+        assert!(platform().as_slice().starts_with("platform: "));
+        // The major and minor versions must agree for the ABI to match
+        assert_eq!(header_number() >> 20, number() >> 20);
+    }
+}
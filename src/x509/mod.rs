@@ -1,6 +1,8 @@
 use libc::{c_int, c_long, c_uint};
+use std::error::Error;
 use std::mem;
 use std::ptr;
+use std::str;
 
 use asn1::{Asn1Time};
 use bio::{MemBio};
@@ -10,6 +12,8 @@ use crypto::rand::rand_bytes;
 use ffi;
 use ssl::error::{SslError, StreamError};
 
+pub mod spki;
+pub mod verify;
 
 #[repr(i32)]
 pub enum X509FileType {
@@ -34,6 +38,25 @@ impl X509StoreContext {
         X509ValidationError::from_raw(err)
     }
 
+    /// Replaces the error of the certificate being verified.
+    ///
+    /// A verification callback which accepts a certificate despite an error
+    /// should clear it with `None`, so that the connection's final
+    /// verification result reports success.
+    pub fn set_error(&self, err: Option<X509ValidationError>) {
+        let err = match err {
+            Some(err) => err.to_raw(),
+            None => ffi::X509_V_OK
+        };
+        unsafe { ffi::X509_STORE_CTX_set_error(self.ctx, err) }
+    }
+
+    /// Returns the depth in the chain of the certificate being verified,
+    /// where the peer's own certificate is at depth 0.
+    pub fn get_error_depth(&self) -> uint {
+        unsafe { ffi::X509_STORE_CTX_get_error_depth(self.ctx) as uint }
+    }
+
     pub fn get_current_cert<'a>(&'a self) -> Option<X509<'a>> {
         let ptr = unsafe { ffi::X509_STORE_CTX_get_current_cert(self.ctx) };
 
@@ -169,6 +192,7 @@ pub struct X509Generator {
     CN: String,
     key_usage: Vec<KeyUsage>,
     ext_key_usage: Vec<ExtKeyUsage>,
+    alt_names: Vec<String>,
     hash_type: HashType,
 }
 
@@ -189,6 +213,7 @@ impl X509Generator {
             CN: "rust-openssl".to_string(),
             key_usage: Vec::new(),
             ext_key_usage: Vec::new(),
+            alt_names: Vec::new(),
             hash_type: SHA1
         }
     }
@@ -224,6 +249,13 @@ impl X509Generator {
         self
     }
 
+    /// Sets subject alternative names of certificate, in OpenSSL's format,
+    /// e.g. `DNS:example.com` or `IP:127.0.0.1`
+    pub fn set_alt_names(mut self, names: &[&str]) -> X509Generator {
+        self.alt_names = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
     pub fn set_sign_hash(mut self, hash_type: HashType) -> X509Generator {
         self.hash_type = hash_type;
         self
@@ -313,6 +345,11 @@ impl X509Generator {
                                                   self.ext_key_usage.to_str().as_slice()));
             }
 
+            if self.alt_names.len() > 0 {
+                try!(X509Generator::add_extension(x509.handle, ffi::NID_subject_alt_name,
+                                                  self.alt_names.as_slice().connect(",").as_slice()));
+            }
+
             let (hash_fn, _) = evpmd(self.hash_type);
             try_ssl!(ffi::X509_sign(x509.handle, p_key.get_handle(), hash_fn));
             Ok((x509, p_key))
@@ -329,6 +366,60 @@ pub struct X509<'ctx> {
 }
 
 impl<'ctx> X509<'ctx> {
+    /// Wraps an existing certificate, taking ownership of it if `owned` is
+    /// set
+    pub fn new(handle: *mut ffi::X509, owned: bool) -> X509<'ctx> {
+        X509 { ctx: None, handle: handle, owned: owned }
+    }
+
+    /// Returns raw handle
+    pub unsafe fn get_handle(&self) -> *mut ffi::X509 {
+        self.handle
+    }
+
+    /// Reads a PEM encoded certificate
+    pub fn from_pem(reader: &mut Reader) -> Result<X509<'ctx>, SslError> {
+        ffi::init();
+
+        let mut mem_bio = try!(MemBio::new());
+        let buf = try!(reader.read_to_end().map_err(StreamError));
+        try!(mem_bio.write(buf.as_slice()).map_err(StreamError));
+
+        let handle = unsafe {
+            ffi::PEM_read_bio_X509(mem_bio.get_handle(), ptr::null_mut(),
+                                   None, ptr::null_mut())
+        };
+        try_ssl_null!(handle);
+
+        Ok(X509 { ctx: None, handle: handle, owned: true })
+    }
+
+    /// Reads a DER encoded certificate
+    pub fn from_der(der: &[u8]) -> Result<X509<'ctx>, SslError> {
+        ffi::init();
+
+        let mut ptr = der.as_ptr();
+        let handle = unsafe {
+            ffi::d2i_X509(ptr::null_mut(), &mut ptr, der.len() as c_long)
+        };
+        try_ssl_null!(handle);
+
+        Ok(X509 { ctx: None, handle: handle, owned: true })
+    }
+
+    /// Returns the certificate in DER format
+    pub fn to_der(&self) -> Result<Vec<u8>, SslError> {
+        unsafe {
+            let len = ffi::i2d_X509(self.handle, ptr::null_mut());
+            try_ssl_if!(len < 0);
+
+            let mut buf = Vec::from_elem(len as uint, 0u8);
+            let mut ptr = buf.as_mut_ptr();
+            try_ssl_if!(ffi::i2d_X509(self.handle, &mut ptr) != len);
+            Ok(buf)
+        }
+    }
+
     pub fn subject_name<'a>(&'a self) -> X509Name<'a> {
         let name = unsafe { ffi::X509_get_subject_name(self.handle) };
         X509Name { x509: self, name: name }
@@ -386,6 +477,7 @@ pub struct X509Name<'x> {
 
 macro_rules! make_validation_error(
     ($ok_val:ident, $($name:ident = $val:ident,)+) => (
+        #[deriving(Show, Clone, PartialEq, Eq)]
         pub enum X509ValidationError {
             $($name,)+
             X509UnknownError(c_int)
@@ -400,10 +492,27 @@ macro_rules! make_validation_error(
                     err => Some(X509UnknownError(err))
                 }
             }
+
+            /// Returns the raw `X509_V_ERR_*` code of this error
+            pub fn to_raw(&self) -> c_int {
+                match *self {
+                    $($name => ffi::$val,)+
+                    X509UnknownError(err) => err
+                }
+            }
         }
     )
 )
 
+impl Error for X509ValidationError {
+    fn description(&self) -> &str {
+        unsafe {
+            let s = ffi::X509_verify_cert_error_string(self.to_raw() as c_long);
+            str::raw::c_str_to_static_slice(s)
+        }
+    }
+}
+
 make_validation_error!(X509_V_OK,
     X509UnableToGetIssuerCert = X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT,
     X509UnableToGetCrl = X509_V_ERR_UNABLE_TO_GET_CRL,
@@ -458,4 +567,6 @@ make_validation_error!(X509_V_OK,
     X509UnsupportedNameSyntax = X509_V_ERR_UNSUPPORTED_NAME_SYNTAX,
     X509CrlPathValidationError= X509_V_ERR_CRL_PATH_VALIDATION_ERROR,
     X509ApplicationVerification = X509_V_ERR_APPLICATION_VERIFICATION,
+    X509HostnameMismatch = X509_V_ERR_HOSTNAME_MISMATCH,
+    X509IpAddressMismatch = X509_V_ERR_IP_ADDRESS_MISMATCH,
 )
@@ -0,0 +1,159 @@
+//! Netscape signed public keys and challenges (SPKAC).
+//!
+//! These are produced by the legacy HTML `<keygen>` element and by
+//! `openssl spkac`, and prove possession of the private key matching the
+//! public key they carry.
+
+use libc::{c_char, c_int};
+use std::c_str::CString;
+use std::slice;
+
+use crypto::hash::{HashType, evpmd};
+use crypto::pkey::{PKey, Public};
+use ffi;
+use ssl::error::SslError;
+
+/// A signed public key and challenge
+pub struct Spki {
+    spki: *mut ffi::NETSCAPE_SPKI
+}
+
+impl Drop for Spki {
+    fn drop(&mut self) {
+        unsafe { ffi::NETSCAPE_SPKI_free(self.spki) }
+    }
+}
+
+impl Spki {
+    /// Creates a new, empty SPKAC
+    pub fn new() -> Result<Spki, SslError> {
+        ffi::init();
+
+        let spki = unsafe { ffi::NETSCAPE_SPKI_new() };
+        try_ssl_null!(spki);
+
+        Ok(Spki { spki: spki })
+    }
+
+    /// Parses a base64 encoded SPKAC, as submitted by `<keygen>`.
+    ///
+    /// The `SPKAC=` prefix used by `openssl spkac` must be stripped first.
+    pub fn from_base64(data: &str) -> Result<Spki, SslError> {
+        ffi::init();
+
+        let data = data.trim();
+        let spki = data.with_c_str(|s| unsafe {
+            ffi::NETSCAPE_SPKI_b64_decode(s, data.len() as c_int)
+        });
+        try_ssl_null!(spki);
+
+        Ok(Spki { spki: spki })
+    }
+
+    /// Returns the base64 encoding of this SPKAC
+    pub fn to_base64(&self) -> Result<String, SslError> {
+        unsafe {
+            let buf = ffi::NETSCAPE_SPKI_b64_encode(self.spki);
+            try_ssl_null!(buf);
+            let s = CString::new(buf as *const c_char, false).to_string();
+            ffi::CRYPTO_free(buf as *const c_char);
+            Ok(s)
+        }
+    }
+
+    /// Returns the public key carried by this SPKAC
+    pub fn public_key(&self) -> Result<PKey, SslError> {
+        let pkey = unsafe { ffi::NETSCAPE_SPKI_get_pubkey(self.spki) };
+        try_ssl_null!(pkey);
+        Ok(PKey::from_handle(pkey, Public))
+    }
+
+    /// Sets the public key carried by this SPKAC
+    pub fn set_public_key(&mut self, key: &PKey) -> Result<(), SslError> {
+        lift_ssl!(unsafe { ffi::NETSCAPE_SPKI_set_pubkey(self.spki, key.get_handle()) })
+    }
+
+    /// Returns the challenge string
+    pub fn challenge(&self) -> Vec<u8> {
+        unsafe {
+            let challenge = (*(*self.spki).spkac).challenge;
+            let len = ffi::ASN1_STRING_length(challenge) as uint;
+            let data = ffi::ASN1_STRING_data(challenge) as *const u8;
+            slice::raw::buf_as_slice(data, len, |s| s.to_vec())
+        }
+    }
+
+    /// Sets the challenge string
+    pub fn set_challenge(&mut self, challenge: &[u8]) -> Result<(), SslError> {
+        lift_ssl!(unsafe {
+            ffi::ASN1_STRING_set((*(*self.spki).spkac).challenge,
+                                 challenge.as_ptr(), challenge.len() as c_int)
+        })
+    }
+
+    /// Signs the public key and challenge with `key`, which must be the
+    /// private key matching the public key.
+    pub fn sign(&mut self, key: &PKey, hash: HashType) -> Result<(), SslError> {
+        let (evp, _) = evpmd(hash);
+        unsafe {
+            try_ssl_if!(ffi::NETSCAPE_SPKI_sign(self.spki, key.get_handle(), evp) <= 0);
+        }
+        Ok(())
+    }
+
+    /// Checks that the SPKAC was signed by the private key matching the public
+    /// key it carries.
+    pub fn verify(&self) -> Result<bool, SslError> {
+        let pkey = try!(self.public_key());
+        match unsafe { ffi::NETSCAPE_SPKI_verify(self.spki, pkey.get_handle()) } {
+            1 => Ok(true),
+            0 => {
+                SslError::get();
+                Ok(false)
+            }
+            _ => Err(SslError::get())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto::hash::SHA256;
+    use crypto::pkey::PKey;
+    use super::Spki;
+
+    fn signed_spki(key: &PKey) -> Spki {
+        let mut spki = Spki::new().unwrap();
+        spki.set_public_key(key).unwrap();
+        spki.set_challenge(b"challenge").unwrap();
+        spki.sign(key, SHA256).unwrap();
+        spki
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut key = PKey::new();
+        key.gen(512);
+
+        let encoded = signed_spki(&key).to_base64().unwrap();
+        let spki = Spki::from_base64(encoded.as_slice()).unwrap();
+        assert!(spki.verify().unwrap());
+        assert_eq!(spki.challenge().as_slice(), b"challenge");
+        assert_eq!(spki.public_key().unwrap().save_pub(), key.save_pub());
+    }
+
+    #[test]
+    fn test_tampered() {
+        let mut key = PKey::new();
+        key.gen(512);
+
+        let mut spki = signed_spki(&key);
+        spki.set_challenge(b"other").unwrap();
+        assert!(!spki.verify().unwrap());
+    }
+
+    #[test]
+    fn test_bad_base64() {
+        assert!(Spki::from_base64("not an spkac").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Standalone certificate chain verification.
+
+use libc::{c_int, c_ulong, c_void};
+use std::mem;
+use std::ptr;
+use sync::one::{Once, ONCE_INIT};
+
+use ffi;
+use ssl::error::SslError;
+use x509::{X509, X509ValidationError};
+
+/// A set of trusted certificates
+pub struct X509Store {
+    store: *mut ffi::X509_STORE
+}
+
+impl Drop for X509Store {
+    fn drop(&mut self) {
+        unsafe { ffi::X509_STORE_free(self.store) }
+    }
+}
+
+impl X509Store {
+    /// Creates an empty store
+    pub fn new() -> Result<X509Store, SslError> {
+        ffi::init();
+
+        let store = unsafe { ffi::X509_STORE_new() };
+        try_ssl_null!(store);
+
+        Ok(X509Store { store: store })
+    }
+
+    /// Adds a trusted certificate
+    pub fn add_cert(&mut self, cert: &X509) -> Result<(), SslError> {
+        lift_ssl!(unsafe { ffi::X509_STORE_add_cert(self.store, cert.handle) })
+    }
+
+    /// Adds the certificates in the PEM file at `file`
+    pub fn load_file(&mut self, file: &Path) -> Result<(), SslError> {
+        lift_ssl!(file.with_c_str(|file| unsafe {
+            ffi::X509_STORE_load_locations(self.store, file, ptr::null())
+        }))
+    }
+
+    /// Adds the system's default trusted certificates
+    pub fn set_default_paths(&mut self) -> Result<(), SslError> {
+        lift_ssl!(unsafe { ffi::X509_STORE_set_default_paths(self.store) })
+    }
+
+    /// Returns raw handle
+    pub unsafe fn get_handle(&self) -> *mut ffi::X509_STORE {
+        self.store
+    }
+}
+
+/// Options controlling `verify_cert`
+#[deriving(Clone, Default)]
+pub struct VerifyParams {
+    /// The maximum number of intermediate certificates allowed in the chain
+    pub depth: Option<uint>,
+    /// The time, in seconds since the Unix epoch, at which validity is
+    /// checked instead of the current time
+    pub time: Option<i64>,
+    /// `X509_V_FLAG_*` flags, e.g. `ffi::X509_V_FLAG_CRL_CHECK`
+    pub flags: c_ulong
+}
+
+/// A problem found with one certificate in the chain
+pub struct VerificationFailure<'a> {
+    /// The position of the certificate in the chain; the leaf is at depth 0
+    pub depth: uint,
+    /// The certificate at fault
+    pub cert: X509<'a>,
+    /// The problem found
+    pub error: X509ValidationError
+}
+
+/// The outcome of `verify_cert`
+pub struct VerificationReport<'a> {
+    /// The chain that was built, from the leaf up towards a root
+    pub chain: Vec<X509<'a>>,
+    /// Every problem found while checking the chain
+    pub failures: Vec<VerificationFailure<'a>>
+}
+
+impl<'a> VerificationReport<'a> {
+    /// Returns `true` if the chain verified without any problems
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+fn get_failures_idx() -> c_int {
+    static mut FAILURES_IDX: c_int = -1;
+    static mut INIT: Once = ONCE_INIT;
+
+    unsafe {
+        INIT.doit(|| {
+            let idx = ffi::X509_STORE_CTX_get_ex_new_index(0, ptr::null(), None,
+                                                           None, None);
+            assert!(idx >= 0);
+            FAILURES_IDX = idx;
+        });
+        FAILURES_IDX
+    }
+}
+
+extern fn raw_collect_failures(preverify_ok: c_int,
+                               x509_ctx: *mut ffi::X509_STORE_CTX) -> c_int {
+    unsafe {
+        if preverify_ok == 0 {
+            let failures = ffi::X509_STORE_CTX_get_ex_data(x509_ctx, get_failures_idx());
+            let failures: &mut Vec<VerificationFailure> = mem::transmute(failures);
+
+            let err = ffi::X509_STORE_CTX_get_error(x509_ctx);
+            let cert = ffi::X509_dup(ffi::X509_STORE_CTX_get_current_cert(x509_ctx));
+            match X509ValidationError::from_raw(err) {
+                Some(error) if !cert.is_null() => failures.push(VerificationFailure {
+                    depth: ffi::X509_STORE_CTX_get_error_depth(x509_ctx) as uint,
+                    cert: X509 { ctx: None, handle: cert, owned: true },
+                    error: error
+                }),
+                // Without the failure recorded verification must stop here
+                _ => return 0
+            }
+        }
+
+        // Carry on so that every problem in the chain is reported
+        1
+    }
+}
+
+/// Verifies `leaf` against the certificates trusted by `store`, using
+/// `intermediates` to build the chain.
+///
+/// Unlike a handshake, verification does not stop at the first problem:
+/// every failure found in the chain is reported. An error is only returned
+/// if verification could not be carried out at all.
+pub fn verify_cert<'a>(leaf: &X509, intermediates: &[&X509], store: &X509Store,
+                       params: &VerifyParams)
+                       -> Result<VerificationReport<'a>, SslError> {
+    unsafe {
+        let untrusted = ffi::sk_new_null();
+        try_ssl_null!(untrusted);
+        for cert in intermediates.iter() {
+            if ffi::sk_push(untrusted, cert.handle as *mut c_void) == 0 {
+                ffi::sk_free(untrusted);
+                return Err(SslError::get());
+            }
+        }
+
+        let ctx = ffi::X509_STORE_CTX_new();
+        if ctx.is_null() {
+            ffi::sk_free(untrusted);
+            return Err(SslError::get());
+        }
+
+        let mut failures = vec!();
+        let res = verify_in_ctx(ctx, leaf, untrusted, store, params, &mut failures);
+
+        let chain = match res {
+            Ok(()) => Ok(get_chain(ctx)),
+            Err(err) => Err(err)
+        };
+
+        ffi::X509_STORE_CTX_free(ctx);
+        ffi::sk_free(untrusted);
+
+        Ok(VerificationReport {
+            chain: try!(chain),
+            failures: failures
+        })
+    }
+}
+
+unsafe fn verify_in_ctx(ctx: *mut ffi::X509_STORE_CTX, leaf: &X509,
+                        untrusted: *mut ffi::_STACK, store: &X509Store,
+                        params: &VerifyParams,
+                        failures: &mut Vec<VerificationFailure>) -> Result<(), SslError> {
+    try_ssl!(ffi::X509_STORE_CTX_init(ctx, store.store, leaf.handle, untrusted));
+
+    match params.depth {
+        Some(depth) => ffi::X509_STORE_CTX_set_depth(ctx, depth as c_int),
+        None => {}
+    }
+    match params.time {
+        Some(time) => ffi::X509_STORE_CTX_set_time(ctx, 0, time as ffi::time_t),
+        None => {}
+    }
+    if params.flags != 0 {
+        ffi::X509_STORE_CTX_set_flags(ctx, params.flags);
+    }
+
+    try_ssl!(ffi::X509_STORE_CTX_set_ex_data(ctx, get_failures_idx(),
+                                             failures as *mut _ as *mut c_void));
+    ffi::X509_STORE_CTX_set_verify_cb(ctx, Some(raw_collect_failures));
+
+    try_ssl_if!(ffi::X509_verify_cert(ctx) <= 0);
+    Ok(())
+}
+
+unsafe fn get_chain<'a>(ctx: *mut ffi::X509_STORE_CTX) -> Vec<X509<'a>> {
+    let chain = ffi::X509_STORE_CTX_get1_chain(ctx);
+    if chain.is_null() {
+        return vec!();
+    }
+
+    let certs = range(0, ffi::sk_num(chain as *const _)).map(|i| {
+        X509 {
+            ctx: None,
+            handle: ffi::sk_value(chain as *const _, i) as *mut ffi::X509,
+            owned: true
+        }
+    }).collect();
+    // The certificates' references now belong to the wrappers
+    ffi::sk_free(chain);
+
+    certs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::default::Default;
+    use std::io::File;
+
+    use x509::{X509, X509DepthZeroSelfSignedCert, X509CertHasExpired};
+    use super::{X509Store, VerifyParams, verify_cert};
+
+    fn test_cert<'a>() -> X509<'a> {
+        let mut file = File::open(&Path::new("test/cert.pem")).unwrap();
+        X509::from_pem(&mut file).unwrap()
+    }
+
+    // test/cert.pem is valid from October 2013 to October 2014
+    fn params_at(time: i64) -> VerifyParams {
+        VerifyParams { time: Some(time), ..Default::default() }
+    }
+
+    #[test]
+    fn test_trusted() {
+        let cert = test_cert();
+        let mut store = X509Store::new().unwrap();
+        store.add_cert(&cert).unwrap();
+
+        let report = verify_cert(&cert, [], &store, &params_at(1400000000)).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.chain.len(), 1);
+    }
+
+    #[test]
+    fn test_reports_all_failures() {
+        let cert = test_cert();
+        let store = X509Store::new().unwrap();
+
+        let report = verify_cert(&cert, [], &store, &params_at(2000000000)).unwrap();
+        assert!(!report.is_valid());
+        assert!(report.failures.iter().any(|f| f.error == X509DepthZeroSelfSignedCert));
+        assert!(report.failures.iter().any(|f| f.error == X509CertHasExpired));
+        assert!(report.failures.iter().all(|f| f.depth == 0));
+    }
+
+    #[test]
+    fn test_der_roundtrip() {
+        let cert = test_cert();
+        let der = cert.to_der().unwrap();
+        assert_eq!(X509::from_der(der.as_slice()).unwrap().to_der().unwrap(), der);
+    }
+}
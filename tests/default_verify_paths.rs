@@ -0,0 +1,44 @@
+// Kept in its own test binary because it sets SSL_CERT_FILE, which would
+// race with the other tests' contexts if it ran alongside them.
+
+extern crate openssl;
+
+use std::io::{File, Listener, Acceptor, TempDir};
+use std::io::net::tcp::{TcpListener, TcpStream};
+use std::os;
+
+use openssl::crypto::hash::SHA256;
+use openssl::ssl::{SslContext, SslStream, Sslv23, SslVerifyPeer};
+use openssl::ssl::connector::SslAcceptorBuilder;
+use openssl::x509::X509Generator;
+
+#[test]
+fn test_set_default_verify_paths() {
+    let (cert, pkey) = X509Generator::new()
+        .set_bitlength(1024)
+        .set_CN("localhost")
+        .set_sign_hash(SHA256)
+        .generate()
+        .unwrap();
+    let dir = TempDir::new("verify_paths").unwrap();
+    let ca_path = dir.path().join("ca.pem");
+    cert.write_pem(&mut File::create(&ca_path).unwrap()).unwrap();
+    let acceptor = SslAcceptorBuilder::new(&cert, &pkey).unwrap().build();
+
+    let mut listener = TcpListener::bind("127.0.0.1", 0).unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let mut server = listener.listen().unwrap();
+    spawn(proc() {
+        let stream = server.accept().unwrap();
+        let _ = acceptor.accept(stream);
+    });
+
+    // Stand in for the system bundle, which is read as the paths are set
+    os::setenv("SSL_CERT_FILE", ca_path.as_str().unwrap());
+    let mut ctx = SslContext::new(Sslv23).unwrap();
+    assert!(ctx.set_default_verify_paths().is_none());
+
+    ctx.set_verify(SslVerifyPeer, None);
+    let stream = TcpStream::connect("127.0.0.1", port).unwrap();
+    assert!(SslStream::new(&ctx, stream).is_ok());
+}